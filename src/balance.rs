@@ -1,38 +1,74 @@
 use std::str::FromStr;
 
+use serde::Serialize;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::signature::Signer;
 
-use crate::Miner;
+use crate::{
+    output::{Output, OutputFormat},
+    summary::Summary,
+    utils::format_ore,
+    Miner,
+};
+
+#[derive(Serialize)]
+struct BalanceOutput {
+    address: String,
+    balance: u64,
+    #[serde(skip)]
+    decimals: usize,
+}
+
+impl Output for BalanceOutput {
+    const CSV_HEADER: &'static [&'static str] = &["address", "balance"];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.address.clone(), self.balance.to_string()]]
+    }
+
+    fn text(&self) -> Vec<String> {
+        vec![format!("{:} ORE", format_ore(self.balance, self.decimals))]
+    }
+}
 
 impl Miner {
-    pub async fn balance(&self, address: Option<String>) {
+    pub async fn balance(
+        &self,
+        address: Option<String>,
+        output: OutputFormat,
+        summary: &mut Summary,
+    ) {
         let signer = self.signer();
         let address = if let Some(address) = address {
             if let Ok(address) = Pubkey::from_str(&address) {
                 address
             } else {
                 println!("Invalid address: {:?}", address);
+                summary.failed += 1;
                 return;
             }
         } else {
             signer.pubkey()
         };
         let client = self.rpc_client.clone();
-        let token_account_address = spl_associated_token_account::get_associated_token_address(
-            &address,
-            &ore::MINT_ADDRESS,
-        );
+        let token_account_address = self.token_program.ore_token_account(&address);
         match client.get_token_account(&token_account_address).await {
             Ok(token_account) => {
                 if let Some(token_account) = token_account {
-                    println!("{:} ORE", token_account.token_amount.ui_amount_string);
+                    output.print(&BalanceOutput {
+                        address: address.to_string(),
+                        balance: token_account.token_amount.amount.parse().unwrap_or(0),
+                        decimals: self.decimals,
+                    });
+                    summary.success += 1;
                 } else {
                     println!("Account not found");
+                    summary.skipped += 1;
                 }
             }
             Err(err) => {
                 println!("{:?}", err);
+                summary.failed += 1;
             }
         }
     }
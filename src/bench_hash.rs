@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::{hashv, Hash as KeccakHash};
+
+use crate::{
+    hash_pool::{HashOutput, HashPool},
+    miner_v2::MinerV2,
+    summary::Summary,
+    utils::{difficulty_zero_bits, get_treasury},
+};
+
+// A thread count whose speedup over the previous one is below this is not worth it
+const MIN_MARGINAL_SPEEDUP: f64 = 1.1;
+
+const BENCH_CSV_HEADER: &str = "threads,hashes_per_sec,avg_secs_to_solution,speedup";
+
+struct BenchResult {
+    threads: u64,
+    hashes_per_sec: f64,
+    avg_secs: f64,
+}
+
+impl MinerV2 {
+    /// Measures the hash rate of every thread count from 1 up to `max_threads` on dummy
+    /// challenges at `difficulty_bits` leading zero bits, either over `samples` solutions or,
+    /// with `duration_secs`, for that long each, in which case the time to a solution at the
+    /// network's current difficulty is also estimated. Nothing is signed or sent.
+    pub async fn bench_hash(
+        rpc_client: &RpcClient,
+        max_threads: u64,
+        samples: u64,
+        duration_secs: Option<u64>,
+        difficulty_bits: u32,
+        csv: Option<String>,
+        summary: &mut Summary,
+    ) {
+        let max_threads = max_threads.max(1);
+        let samples = samples.max(1);
+        let difficulty = difficulty_from_zero_bits(difficulty_bits);
+        let pubkey = Pubkey::new_unique();
+        match duration_secs {
+            Some(duration_secs) => println!(
+                "Benchmarking 1..={} threads at {} leading zero bits, {} seconds each",
+                max_threads, difficulty_bits, duration_secs
+            ),
+            None => println!(
+                "Benchmarking 1..={} threads at {} leading zero bits, {} samples each",
+                max_threads, difficulty_bits, samples
+            ),
+        }
+
+        let mut results = vec![];
+        for threads in 1..=max_threads {
+            let pool = HashPool::new(threads);
+            let result = match duration_secs {
+                Some(duration_secs) => {
+                    bench_duration(&pool, threads, pubkey, difficulty, duration_secs).await
+                }
+                None => match bench_samples(&pool, threads, pubkey, difficulty, samples).await {
+                    Some(result) => result,
+                    None => {
+                        println!("No solution found with {} threads", threads);
+                        summary.failed += 1;
+                        return;
+                    }
+                },
+            };
+            println!(
+                "{} threads: {:.0} H/s, {:.3}s to solution",
+                threads, result.hashes_per_sec, result.avg_secs
+            );
+            results.push(result);
+            summary.success += 1;
+        }
+
+        let baseline = results[0].hashes_per_sec;
+        println!("\n{:>7} {:>14} {:>12} {:>8}", "threads", "H/s", "secs/sol", "speedup");
+        for result in results.iter() {
+            println!(
+                "{:>7} {:>14.0} {:>12.3} {:>7.2}x",
+                result.threads,
+                result.hashes_per_sec,
+                result.avg_secs,
+                result.hashes_per_sec / baseline
+            );
+        }
+
+        match results
+            .windows(2)
+            .find(|pair| pair[1].hashes_per_sec / pair[0].hashes_per_sec < MIN_MARGINAL_SPEEDUP)
+        {
+            Some(pair) => println!(
+                "\nDiminishing returns after {} threads: {} threads only adds {:.0}%",
+                pair[0].threads,
+                pair[1].threads,
+                (pair[1].hashes_per_sec / pair[0].hashes_per_sec - 1.0) * 100.0
+            ),
+            None => println!("\nNo diminishing returns up to {} threads", max_threads),
+        }
+
+        if duration_secs.is_some() {
+            let fastest = results
+                .iter()
+                .max_by(|a, b| a.hashes_per_sec.total_cmp(&b.hashes_per_sec))
+                .unwrap();
+            match get_treasury(rpc_client).await {
+                Ok(treasury) => {
+                    let network_bits = difficulty_zero_bits(treasury.difficulty.as_ref());
+                    let expected_hashes = 2f64.powi(network_bits as i32);
+                    println!(
+                        "Network difficulty is {} leading zero bits: ~{:.1}s to a solution with {} threads",
+                        network_bits,
+                        expected_hashes / fastest.hashes_per_sec,
+                        fastest.threads
+                    );
+                }
+                Err(e) => println!("Failed to load network difficulty, no estimate: {}", e),
+            }
+        }
+
+        if let Some(csv) = csv {
+            let mut contents = format!("{}\n", BENCH_CSV_HEADER);
+            for result in results.iter() {
+                contents.push_str(&format!(
+                    "{},{:.0},{:.6},{:.4}\n",
+                    result.threads,
+                    result.hashes_per_sec,
+                    result.avg_secs,
+                    result.hashes_per_sec / baseline
+                ));
+            }
+            match tokio::fs::write(&csv, contents).await {
+                Ok(_) => println!("Wrote results to {}", csv),
+                Err(e) => {
+                    println!("Failed to write {}: {}", csv, e);
+                    summary.failed += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Finds `samples` solutions, or None if a search runs out of nonces.
+async fn bench_samples(
+    pool: &HashPool,
+    threads: u64,
+    pubkey: Pubkey,
+    difficulty: KeccakHash,
+    samples: u64,
+) -> Option<BenchResult> {
+    let mut total_hashes = 0;
+    let started = Instant::now();
+    for sample in 0..samples {
+        // Same challenges for every thread count so runs are comparable
+        let hash = hashv(&[&sample.to_le_bytes()]);
+        let (solution, hashes) = pool
+            .search(pubkey, hash, difficulty, HashOutput::Quiet)
+            .await;
+        solution?;
+        total_hashes += hashes;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    Some(BenchResult {
+        threads,
+        hashes_per_sec: total_hashes as f64 / elapsed,
+        avg_secs: elapsed / samples as f64,
+    })
+}
+
+/// Hashes challenges one after another until `duration_secs` are up, cutting the last search
+/// off at the deadline.
+async fn bench_duration(
+    pool: &HashPool,
+    threads: u64,
+    pubkey: Pubkey,
+    difficulty: KeccakHash,
+    duration_secs: u64,
+) -> BenchResult {
+    let mut total_hashes = 0;
+    let mut solutions = 0u64;
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(duration_secs);
+    loop {
+        let hash = hashv(&[&solutions.to_le_bytes()]);
+        let (solution, hashes) = pool
+            .search_until(pubkey, hash, difficulty, HashOutput::Quiet, deadline)
+            .await;
+        total_hashes += hashes;
+        if solution.is_none() {
+            break;
+        }
+        solutions += 1;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    BenchResult {
+        threads,
+        hashes_per_sec: total_hashes as f64 / elapsed,
+        // Infinite when the difficulty was too high to solve once in time
+        avg_secs: elapsed / solutions as f64,
+    }
+}
+
+/// Difficulty hash whose first `bits` bits are zero and the rest set, the same shape the
+/// program uses.
+fn difficulty_from_zero_bits(bits: u32) -> KeccakHash {
+    let mut bytes = [0xff; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        let bit = i as u32 * 8;
+        if bit + 8 <= bits {
+            *byte = 0;
+        } else if bit < bits {
+            *byte = 0xff >> (bits - bit);
+        }
+    }
+    KeccakHash::new_from_array(bytes)
+}
@@ -0,0 +1,107 @@
+use std::{
+    sync::{Arc, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use solana_client::{client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient};
+use log::warn;
+use solana_sdk::hash::Hash;
+use tokio::{sync::Semaphore, time::sleep};
+
+use crate::utils::{get_latest_blockhash, rpc_permit, BlockhashError};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+// Cached values older than this are ignored, e.g. while the rpc is failing, and callers go
+// back to asking the rpc themselves
+const MAX_AGE: Duration = Duration::from_secs(10);
+
+static SHARED: OnceLock<BlockhashCache> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T: Copy> Cached<T> {
+    fn fresh(cached: &RwLock<Option<Self>>) -> Option<T> {
+        let cached = (*cached.read().unwrap())?;
+        (cached.fetched_at.elapsed() < MAX_AGE).then_some(cached.value)
+    }
+
+    fn store(cached: &RwLock<Option<Self>>, value: T) {
+        *cached.write().unwrap() = Some(Cached {
+            value,
+            fetched_at: Instant::now(),
+        });
+    }
+}
+
+/// Process-wide latest blockhash and block height, started by mine-v2. One task refreshes
+/// both on a timer and every batch and confirmation reads them from here, instead of each
+/// in-flight transaction fetching its own. Without a running cache the lookups fall back to
+/// the rpc.
+pub struct BlockhashCache {
+    blockhash: Arc<RwLock<Option<Cached<(Hash, u64)>>>>,
+    block_height: Arc<RwLock<Option<Cached<u64>>>>,
+    rpc_limit: Option<Arc<Semaphore>>,
+}
+
+impl BlockhashCache {
+    /// Starts the shared cache, its refreshes and rpc fallbacks held to `rpc_limit`. Later
+    /// calls are ignored.
+    pub fn start(client: Arc<RpcClient>, rpc_limit: Option<Arc<Semaphore>>) {
+        let cache = BlockhashCache {
+            blockhash: Arc::new(RwLock::new(None)),
+            block_height: Arc::new(RwLock::new(None)),
+            rpc_limit: rpc_limit.clone(),
+        };
+        let blockhash = cache.blockhash.clone();
+        let block_height = cache.block_height.clone();
+        if SHARED.set(cache).is_err() {
+            return;
+        }
+        tokio::spawn(async move {
+            loop {
+                let permit = rpc_permit(rpc_limit.as_ref()).await;
+                match get_latest_blockhash(&client).await {
+                    Ok(latest) => Cached::store(&blockhash, latest),
+                    Err(e) => warn!("Blockhash cache: {}", e),
+                }
+                match client.get_block_height().await {
+                    Ok(height) => Cached::store(&block_height, height),
+                    Err(e) => warn!("Blockhash cache: failed to get block height: {}", e),
+                }
+                drop(permit);
+                sleep(REFRESH_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Latest blockhash and last valid block height, from the cache when it is running and
+    /// fresh.
+    pub async fn latest_blockhash(client: &RpcClient) -> Result<(Hash, u64), BlockhashError> {
+        match SHARED.get().and_then(|cache| Cached::fresh(&cache.blockhash)) {
+            Some(latest) => Ok(latest),
+            None => {
+                let _permit = rpc_permit(Self::rpc_limit()).await;
+                get_latest_blockhash(client).await
+            }
+        }
+    }
+
+    /// Current block height, from the cache when it is running and fresh.
+    pub async fn block_height(client: &RpcClient) -> ClientResult<u64> {
+        match SHARED.get().and_then(|cache| Cached::fresh(&cache.block_height)) {
+            Some(height) => Ok(height),
+            None => {
+                let _permit = rpc_permit(Self::rpc_limit()).await;
+                client.get_block_height().await
+            }
+        }
+    }
+
+    fn rpc_limit() -> Option<&'static Arc<Semaphore>> {
+        SHARED.get()?.rpc_limit.as_ref()
+    }
+}
@@ -1,26 +1,102 @@
-use ore::{state::Bus, utils::AccountDeserialize, BUS_ADDRESSES, TOKEN_DECIMALS};
-use solana_client::client_error::Result;
+use ore::BUS_ADDRESSES;
+use serde::Serialize;
 
-use crate::Miner;
+use crate::{
+    output::{Output, OutputFormat},
+    summary::Summary,
+    utils::{format_ore, BusAccount},
+    Miner,
+};
 
-impl Miner {
-    pub async fn busses(&self) {
-        let client = self.rpc_client.clone();
-        for address in BUS_ADDRESSES.iter() {
-            let data = client.get_account_data(address).await.unwrap();
-            match Bus::try_from_bytes(&data) {
-                Ok(bus) => {
-                    let rewards = (bus.rewards as f64) / 10f64.powf(TOKEN_DECIMALS as f64);
-                    println!("Bus {}: {:} ORE", bus.id, rewards);
+#[derive(Serialize)]
+struct BusOutput {
+    id: usize,
+    /// None when the bus account couldn't be read, see `error`.
+    rewards: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(transparent)]
+struct BussesOutput {
+    busses: Vec<BusOutput>,
+    #[serde(skip)]
+    decimals: usize,
+}
+
+impl Output for BussesOutput {
+    const CSV_HEADER: &'static [&'static str] = &["id", "rewards", "error"];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        self.busses
+            .iter()
+            .map(|bus| {
+                vec![
+                    bus.id.to_string(),
+                    bus.rewards.map(|r| r.to_string()).unwrap_or_default(),
+                    bus.error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn text(&self) -> Vec<String> {
+        self.busses
+            .iter()
+            .map(|bus| match (bus.rewards, &bus.error) {
+                (Some(rewards), _) => {
+                    format!("Bus {}: {:} ORE", bus.id, format_ore(rewards, self.decimals))
                 }
-                Err(_) => {}
-            }
-        }
+                (None, error) => format!(
+                    "Bus {}: unavailable ({})",
+                    bus.id,
+                    error.as_deref().unwrap_or_default()
+                ),
+            })
+            .collect()
     }
+}
 
-    pub async fn get_bus(&self, id: usize) -> Result<Bus> {
+impl Miner {
+    pub async fn busses(&self, output: OutputFormat, summary: &mut Summary) {
         let client = self.rpc_client.clone();
-        let data = client.get_account_data(&BUS_ADDRESSES[id]).await?;
-        Ok(*Bus::try_from_bytes(&data).unwrap())
+        let accounts = match client.get_multiple_accounts(&BUS_ADDRESSES).await {
+            Ok(accounts) => accounts,
+            Err(err) => {
+                println!("Failed to get bus accounts: {}", err);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let mut busses = vec![];
+        for (id, account) in accounts.iter().enumerate() {
+            let bus = match account.as_ref().map(|account| BusAccount::parse(&account.data)) {
+                Some(Ok(bus)) => BusOutput {
+                    id: bus.id,
+                    rewards: Some(bus.rewards),
+                    error: None,
+                },
+                Some(Err(e)) => BusOutput {
+                    id,
+                    rewards: None,
+                    error: Some(e.to_string()),
+                },
+                None => BusOutput {
+                    id,
+                    rewards: None,
+                    error: Some("account not found".to_string()),
+                },
+            };
+            if bus.rewards.is_some() {
+                summary.success += 1;
+            } else {
+                summary.failed += 1;
+            }
+            busses.push(bus);
+        }
+        output.print(&BussesOutput {
+            busses,
+            decimals: self.decimals,
+        });
     }
 }
@@ -1,39 +1,113 @@
 use std::str::FromStr;
 
-use ore::{self, state::Proof, utils::AccountDeserialize};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{compute_budget::ComputeBudgetInstruction, signature::Signer};
 
-use crate::{cu_limits::CU_LIMIT_CLAIM, utils::proof_pubkey, Miner};
+use crate::{
+    cu_limits::CU_LIMIT_CLAIM,
+    ledger::{ClaimLedger, ClaimRecord},
+    summary::Summary,
+    utils::{estimate_tx_fee, format_ore, proof_pubkey, wait_for_next_epoch, ProofAccount},
+    Miner,
+};
 
 impl Miner {
-    pub async fn claim(&self, beneficiary: Option<String>, amount: Option<f64>) {
+    pub async fn claim(
+        &self,
+        beneficiary: Option<String>,
+        beneficiary_owner: Option<String>,
+        amount: Option<u64>,
+        wait_for_epoch: bool,
+        ledger: Option<String>,
+        summary: &mut Summary,
+    ) {
+        let mut ledger = match ledger.as_deref().map(ClaimLedger::open) {
+            Some(Ok(ledger)) => Some(ledger),
+            Some(Err(e)) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+            None => None,
+        };
         let signer = self.signer();
         let pubkey = signer.pubkey();
         let client = self.rpc_client.clone();
-        let beneficiary = match beneficiary {
-            Some(beneficiary) => {
+        let beneficiary = match (beneficiary, beneficiary_owner) {
+            (Some(beneficiary), _) => {
                 Pubkey::from_str(&beneficiary).expect("Failed to parse beneficiary address")
             }
-            None => self.initialize_ata().await,
-        };
-        let amount = if let Some(amount) = amount {
-            (amount * 10f64.powf(ore::TOKEN_DECIMALS as f64)) as u64
-        } else {
-            match client.get_account(&proof_pubkey(pubkey)).await {
-                Ok(proof_account) => {
-                    let proof = Proof::try_from_bytes(&proof_account.data).unwrap();
-                    proof.claimable_rewards
+            (None, Some(owner)) => match Pubkey::from_str(&owner) {
+                Ok(owner) => match self.initialize_ata(owner).await {
+                    Ok(token_account) => token_account,
+                    Err(e) => {
+                        println!("{}", e);
+                        summary.failed += 1;
+                        return;
+                    }
+                },
+                Err(e) => {
+                    println!("Invalid beneficiary owner {}: {}", owner, e);
+                    summary.failed += 1;
+                    return;
                 }
-                Err(err) => {
-                    println!("Error looking up claimable rewards: {:?}", err);
+            },
+            (None, None) => match self.initialize_ata(pubkey).await {
+                Ok(token_account) => token_account,
+                Err(e) => {
+                    println!("{}", e);
+                    summary.failed += 1;
                     return;
                 }
+            },
+        };
+        let claimable = match client.get_account(&proof_pubkey(pubkey)).await {
+            Ok(proof_account) => match ProofAccount::parse(&proof_account.data) {
+                Ok(proof) => proof.claimable_rewards,
+                Err(e) => {
+                    println!("{}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            },
+            Err(err) => {
+                println!("Error looking up claimable rewards: {:?}", err);
+                summary.failed += 1;
+                return;
             }
         };
-        let amountf = (amount as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+        let amount = amount.unwrap_or(claimable);
+        if amount > claimable {
+            println!(
+                "Requested {} ORE but only {} ORE is claimable.",
+                format_ore(amount, self.decimals),
+                format_ore(claimable, self.decimals)
+            );
+            summary.failed += 1;
+            return;
+        }
+        let fee = estimate_tx_fee(1, CU_LIMIT_CLAIM, self.priority_fee);
+        if let Err(e) = self
+            .tx_settings
+            .check_sol_reserve(&client, &pubkey, fee)
+            .await
+        {
+            println!("Skipping claim: {}", e);
+            summary.skipped += 1;
+            return;
+        }
+        if wait_for_epoch {
+            if let Err(e) = wait_for_next_epoch(&client).await {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        }
+        let amountf = format_ore(amount, self.decimals);
         let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM);
-        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
+        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+            self.priority_fee.cu_price(CU_LIMIT_CLAIM),
+        );
         let ix = ore::instruction::claim(pubkey, beneficiary, amount);
         println!("Submitting claim transaction...");
         match self
@@ -43,43 +117,54 @@ impl Miner {
             Ok(sig) => {
                 println!("Claimed {:} ORE to account {:}", amountf, beneficiary);
                 println!("{:?}", sig);
+                summary.success += 1;
+                summary.ore_claimed += amount;
+                if let Some(ledger) = ledger.as_mut() {
+                    let record = ClaimRecord::new(&client, pubkey, amount, beneficiary, sig).await;
+                    if let Err(e) = ledger.append(&record) {
+                        println!("{}", e);
+                    }
+                }
             }
             Err(err) => {
                 println!("Error: {:?}", err);
+                summary.failed += 1;
             }
         }
     }
 
-    async fn initialize_ata(&self) -> Pubkey {
+    /// Creates `owner`'s ORE token account if it doesn't exist yet, paid by the signer, and
+    /// returns its address.
+    async fn initialize_ata(&self, owner: Pubkey) -> Result<Pubkey, String> {
         // Initialize client.
         let signer = self.signer();
         let client = self.rpc_client.clone();
 
         // Build instructions.
-        let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
-            &signer.pubkey(),
-            &ore::MINT_ADDRESS,
-        );
+        let token_account_pubkey = self.token_program.ore_token_account(&owner);
 
         // Check if ata already exists
         if let Ok(Some(_ata)) = client.get_token_account(&token_account_pubkey).await {
-            return token_account_pubkey;
+            return Ok(token_account_pubkey);
         }
 
         // Sign and send transaction.
         let ix = spl_associated_token_account::instruction::create_associated_token_account(
             &signer.pubkey(),
-            &signer.pubkey(),
+            &owner,
             &ore::MINT_ADDRESS,
-            &spl_token::id(),
+            &self.token_program.id(),
         );
         println!("Creating token account {}...", token_account_pubkey);
         match self.send_and_confirm(&[ix], true, false).await {
-            Ok(_sig) => println!("Created token account {:?}", token_account_pubkey),
-            Err(e) => println!("Transaction failed: {:?}", e),
+            Ok(_sig) => {
+                println!("Created token account {:?}", token_account_pubkey);
+                Ok(token_account_pubkey)
+            }
+            Err(e) => Err(format!(
+                "Failed to create token account {}: {}",
+                token_account_pubkey, e
+            )),
         }
-
-        // Return token account address
-        token_account_pubkey
     }
 }
@@ -0,0 +1,146 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+
+// Files whose path ends in this are gzip compressed
+const GZIP_EXTENSION: &str = ".gz";
+
+/// Whether the file at `path` is read and written gzip compressed.
+pub fn is_compressed(path: &str) -> bool {
+    path.ends_with(GZIP_EXTENSION)
+}
+
+/// `path` with the gzip extension added when `compress` is set and it isn't there already.
+pub fn output_path(path: String, compress: bool) -> String {
+    if compress && !is_compressed(&path) {
+        format!("{}{}", path, GZIP_EXTENSION)
+    } else {
+        path
+    }
+}
+
+/// Reads a whole file, decompressing it if its extension says so. A compressed file cut off
+/// mid-write, e.g. by a crash, reads up to its last complete line.
+pub fn read_to_string(path: &str) -> std::io::Result<String> {
+    if !is_compressed(path) {
+        return std::fs::read_to_string(path);
+    }
+    let mut bytes = vec![];
+    if let Err(e) = MultiGzDecoder::new(File::open(path)?).read_to_end(&mut bytes) {
+        if e.kind() != ErrorKind::UnexpectedEof {
+            return Err(e);
+        }
+        let complete = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        bytes.truncate(complete);
+    }
+    String::from_utf8(bytes).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// `contents` as they should be written to `path`, compressed if its extension says so.
+pub fn encode(path: &str, contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    if !is_compressed(path) {
+        return Ok(contents.to_vec());
+    }
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(contents)?;
+    encoder.finish()
+}
+
+/// A file opened for appending, compressed if its extension says so. Every flush ends a
+/// compressed block, so flushed records can be read back even if the process dies before
+/// the stream is finished.
+pub enum AppendFile {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl AppendFile {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        if is_compressed(path) {
+            // Appends to an existing file start a new gzip member, which readers continue
+            // into
+            Ok(AppendFile::Gzip(GzEncoder::new(
+                file,
+                Compression::default(),
+            )))
+        } else {
+            Ok(AppendFile::Plain(file))
+        }
+    }
+}
+
+impl Write for AppendFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            AppendFile::Plain(file) => file.write(buf),
+            AppendFile::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            AppendFile::Plain(file) => file.flush(),
+            AppendFile::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("ore-cli-compress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn output_path_adds_extension_once() {
+        assert_eq!(
+            output_path("stats.jsonl".to_string(), true),
+            "stats.jsonl.gz"
+        );
+        assert_eq!(
+            output_path("stats.jsonl.gz".to_string(), true),
+            "stats.jsonl.gz"
+        );
+        assert_eq!(output_path("stats.jsonl".to_string(), false), "stats.jsonl");
+    }
+
+    #[test]
+    fn appends_across_reopens_read_back() {
+        let path = temp_path("append.jsonl.gz");
+        let _ = std::fs::remove_file(&path);
+        for line in ["first\n", "second\n"] {
+            let mut file = AppendFile::open(&path).unwrap();
+            file.write_all(line.as_bytes()).unwrap();
+            file.flush().unwrap();
+        }
+        assert_eq!(read_to_string(&path).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn unfinished_stream_reads_flushed_lines() {
+        let path = temp_path("unfinished.jsonl.gz");
+        let _ = std::fs::remove_file(&path);
+        let mut file = AppendFile::open(&path).unwrap();
+        file.write_all(b"flushed\n").unwrap();
+        file.flush().unwrap();
+        // Never finished, as if the process died
+        std::mem::forget(file);
+        assert_eq!(read_to_string(&path).unwrap(), "flushed\n");
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let path = temp_path("report.json.gz");
+        std::fs::write(&path, encode(&path, b"{}").unwrap()).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "{}");
+        assert_eq!(encode("report.json", b"{}").unwrap(), b"{}");
+    }
+}
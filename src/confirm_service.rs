@@ -0,0 +1,105 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use log::warn;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{signature::Signature, transaction::TransactionError};
+use solana_transaction_status::TransactionConfirmationStatus;
+use tokio::{
+    sync::{mpsc, oneshot, Semaphore},
+    time::sleep,
+};
+
+use crate::utils::rpc_permit;
+
+// getSignatureStatuses accepts at most 256 signatures per request
+pub const MAX_SIG_STATUS_BATCH_SIZE: u64 = 256;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Waiter = oneshot::Sender<Result<(), TransactionError>>;
+
+/// Handle to a confirmation checker. Every in-flight transaction registers its signatures
+/// here, and one task checks all of them with batched `get_signature_statuses` calls
+/// instead of each transaction polling the rpc on its own. The task stops once every
+/// handle is dropped.
+#[derive(Clone)]
+pub struct ConfirmService {
+    client: Arc<RpcClient>,
+    watches: mpsc::UnboundedSender<(Signature, Waiter)>,
+}
+
+impl ConfirmService {
+    /// Starts the checker task, its status checks held to `rpc_limit`.
+    pub fn start(
+        client: Arc<RpcClient>,
+        batch_size: usize,
+        rpc_limit: Option<Arc<Semaphore>>,
+    ) -> ConfirmService {
+        let (watches, mut watch_reader) = mpsc::unbounded_channel();
+        let service = ConfirmService {
+            client: client.clone(),
+            watches,
+        };
+        let batch_size = batch_size.clamp(1, MAX_SIG_STATUS_BATCH_SIZE as usize);
+        tokio::spawn(async move {
+            let mut pending: HashMap<Signature, Vec<Waiter>> = HashMap::new();
+            loop {
+                // Sleep until something needs confirming
+                if pending.is_empty() {
+                    match watch_reader.recv().await {
+                        Some((sig, waiter)) => pending.entry(sig).or_default().push(waiter),
+                        None => return,
+                    }
+                }
+                while let Ok((sig, waiter)) = watch_reader.try_recv() {
+                    pending.entry(sig).or_default().push(waiter);
+                }
+                // Drop signatures nobody is waiting on anymore, e.g. expired blockhashes
+                pending.retain(|_, waiters| {
+                    waiters.retain(|waiter| !waiter.is_closed());
+                    !waiters.is_empty()
+                });
+
+                let sigs: Vec<Signature> = pending.keys().copied().collect();
+                for chunk in sigs.chunks(batch_size) {
+                    let _permit = rpc_permit(rpc_limit.as_ref()).await;
+                    let statuses = match client.get_signature_statuses(chunk).await {
+                        Ok(statuses) => statuses.value,
+                        Err(e) => {
+                            warn!("Failed to get signature statuses: {}", e);
+                            continue;
+                        }
+                    };
+                    for (sig, status) in chunk.iter().zip(statuses) {
+                        let Some(status) = status else {
+                            continue;
+                        };
+                        if matches!(
+                            status.confirmation_status,
+                            Some(TransactionConfirmationStatus::Confirmed)
+                                | Some(TransactionConfirmationStatus::Finalized)
+                        ) {
+                            for waiter in pending.remove(sig).unwrap_or_default() {
+                                let _ = waiter.send(status.status.clone());
+                            }
+                        }
+                    }
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+        });
+        service
+    }
+
+    /// The client confirmations are checked against, `--confirm-rpc` when set.
+    pub fn client(&self) -> Arc<RpcClient> {
+        self.client.clone()
+    }
+
+    /// Resolves with the transaction's result once `sig` is confirmed.
+    pub fn watch(&self, sig: Signature) -> oneshot::Receiver<Result<(), TransactionError>> {
+        let (waiter, receiver) = oneshot::channel();
+        let _ = self.watches.send((sig, waiter));
+        receiver
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client::http_sender::HttpSender;
+
+/// Spreads rpc requests over several `--rpc` endpoints behind one `RpcClient`. Requests go
+/// to the current endpoint, and when it can't be reached the request is retried on the
+/// next one, which stays current for later requests. Transactions are broadcast to every
+/// endpoint at once to improve the odds of landing.
+pub struct FailoverSender {
+    senders: Vec<HttpSender>,
+    current: AtomicUsize,
+}
+
+impl FailoverSender {
+    pub fn new(senders: Vec<HttpSender>) -> Self {
+        assert!(!senders.is_empty(), "FailoverSender needs at least one endpoint");
+        Self {
+            senders,
+            current: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Whether the endpoint itself failed, as opposed to answering with an error for the
+/// request, which any other endpoint would answer the same way.
+fn is_endpoint_error(kind: &ClientErrorKind) -> bool {
+    matches!(
+        kind,
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::SerdeJson(_)
+    )
+}
+
+#[async_trait]
+impl RpcSender for FailoverSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        if request == RpcRequest::SendTransaction && self.senders.len() > 1 {
+            let sends = self
+                .senders
+                .iter()
+                .map(|sender| sender.send(request, params.clone()));
+            return futures::future::select_ok(sends)
+                .await
+                .map(|(result, _)| result);
+        }
+
+        let start = self.current.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for i in 0..self.senders.len() {
+            let index = (start + i) % self.senders.len();
+            match self.senders[index].send(request, params.clone()).await {
+                Err(e) if is_endpoint_error(e.kind()) => {
+                    warn!("Rpc {} failed: {}", self.senders[index].url(), e);
+                    last_error = Some(e);
+                }
+                result => {
+                    if index != start
+                        && self
+                            .current
+                            .compare_exchange(start, index, Ordering::Relaxed, Ordering::Relaxed)
+                            .is_ok()
+                    {
+                        info!("Switched rpc to {}", self.senders[index].url());
+                    }
+                    return result;
+                }
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut stats = RpcTransportStats::default();
+        for sender in self.senders.iter() {
+            let sender_stats = sender.get_transport_stats();
+            stats.request_count += sender_stats.request_count;
+            stats.elapsed_time += sender_stats.elapsed_time;
+            stats.rate_limited_time += sender_stats.rate_limited_time;
+        }
+        stats
+    }
+
+    fn url(&self) -> String {
+        self.senders[self.current.load(Ordering::Relaxed)].url()
+    }
+}
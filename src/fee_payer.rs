@@ -0,0 +1,93 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::Arc,
+};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer, SignerError};
+
+/// The shared mine-v2 fee payer. Wraps any `Signer`, so the payer's signature doesn't have
+/// to come from a local keypair file, e.g. an `ExternalSigner`.
+#[derive(Clone)]
+pub struct FeePayer(Arc<dyn Signer + Send + Sync>);
+
+impl FeePayer {
+    pub fn new(signer: impl Signer + Send + Sync + 'static) -> Self {
+        Self(Arc::new(signer))
+    }
+}
+
+impl Signer for FeePayer {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        self.0.try_pubkey()
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.0.try_sign_message(message)
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.0.is_interactive()
+    }
+}
+
+/// Signs by running the `--fee-payer-signer` command, for keys held outside this machine
+/// such as a multisig or key-management service. The command is run through `sh -c` with
+/// the base64 message to sign on stdin, and must print the base58 signature on stdout.
+pub struct ExternalSigner {
+    pubkey: Pubkey,
+    command: String,
+}
+
+impl ExternalSigner {
+    pub fn new(pubkey: Pubkey, command: String) -> Self {
+        Self { pubkey, command }
+    }
+}
+
+impl Signer for ExternalSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| SignerError::Custom(format!("failed to run fee payer signer: {}", e)))?;
+        // Dropping stdin closes it, so the command sees the end of the message
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(BASE64.encode(message).as_bytes())
+                .map_err(|e| SignerError::Connection(e.to_string()))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| SignerError::Connection(e.to_string()))?;
+        if !output.status.success() {
+            return Err(SignerError::Custom(format!(
+                "fee payer signer exited with {}",
+                output.status
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let signature = Signature::from_str(stdout.trim()).map_err(|e| {
+            SignerError::Protocol(format!("fee payer signer printed an invalid signature: {}", e))
+        })?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::KeypairPubkeyMismatch);
+        }
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use crate::{hash_pool::HashPool, summary::Summary, Miner};
+
+impl Miner {
+    /// Reproduces a hash search offline from explicit inputs instead of on-chain state.
+    pub async fn hash(
+        &self,
+        proof_hash: String,
+        pubkey: String,
+        difficulty: String,
+        threads: u64,
+        progress: bool,
+        summary: &mut Summary,
+    ) {
+        let inputs = (
+            parse_hex_hash(&proof_hash),
+            Pubkey::from_str(&pubkey).map_err(|e| format!("Invalid pubkey {}: {}", pubkey, e)),
+            parse_hex_hash(&difficulty),
+        );
+        let (proof_hash, pubkey, difficulty) = match inputs {
+            (Ok(proof_hash), Ok(pubkey), Ok(difficulty)) => (proof_hash, pubkey, difficulty),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+
+        let hash_pool = HashPool::new(threads);
+        let Some((hash, nonce)) = hash_pool
+            .find_next_hash(pubkey, proof_hash, difficulty, progress)
+            .await
+        else {
+            println!("No nonce meets the difficulty.");
+            summary.failed += 1;
+            return;
+        };
+        println!();
+        println!("Nonce: {}", nonce);
+        println!("Hash: {} ({})", to_hex(&hash), hash);
+
+        if self.validate_hash(hash, proof_hash, pubkey, nonce, difficulty) {
+            println!("Hash is valid.");
+            summary.success += 1;
+        } else {
+            println!("Hash failed validation.");
+            summary.failed += 1;
+        }
+    }
+}
+
+pub fn parse_hex_hash(s: &str) -> Result<KeccakHash, String> {
+    let hex = s.trim_start_matches("0x");
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(format!("Invalid hash {}: expected 32 bytes of hex", s));
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("Invalid hash {}: expected 32 bytes of hex", s))?;
+    }
+    Ok(KeccakHash::new_from_array(bytes))
+}
+
+fn to_hex(hash: &KeccakHash) -> String {
+    hash.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
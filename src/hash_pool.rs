@@ -0,0 +1,304 @@
+use std::{
+    io::{stdout, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash as KeccakHash;
+
+use crate::{
+    nonce_hasher::{NonceHasher, NONCE_BATCH},
+    utils::{difficulty_zero_bits, nonce_range, spawn_hash_progress},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// What a search prints while it runs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HashOutput {
+    /// Scroll the current hash on one line.
+    Scroll,
+    /// Spinner with the running hashrate.
+    Progress,
+    /// Nothing, used by benchmarks.
+    Quiet,
+}
+
+/// Fixed pool of OS threads used for hashing, kept separate from the tokio runtime so hashing
+/// never blocks async tasks and CPU usage stays at `threads` no matter how many wallets hash.
+pub struct HashPool {
+    jobs: mpsc::Sender<Job>,
+    threads: u64,
+}
+
+impl HashPool {
+    pub fn new(threads: u64) -> Self {
+        let threads = threads.max(1);
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let job = receiver.lock().expect("failed to lock job queue").recv();
+                match job {
+                    Ok(job) => job(),
+                    // Pool was dropped
+                    Err(_) => return,
+                }
+            });
+        }
+        Self { jobs, threads }
+    }
+
+    /// Searches for a nonce whose hash meets `difficulty`, splitting the nonce space across
+    /// every thread in the pool. Returns None if the whole space was searched without a hit.
+    pub async fn find_next_hash(
+        &self,
+        pubkey: Pubkey,
+        hash: KeccakHash,
+        difficulty: KeccakHash,
+        progress: bool,
+    ) -> Option<(KeccakHash, u64)> {
+        println!(
+            "Difficulty: {} leading zero bits",
+            difficulty_zero_bits(difficulty.as_ref())
+        );
+        let output = if progress {
+            HashOutput::Progress
+        } else {
+            HashOutput::Scroll
+        };
+        self.search(pubkey, hash, difficulty, output).await.0
+    }
+
+    /// Same as `find_next_hash`, also returning how many hashes were computed in total.
+    pub async fn search(
+        &self,
+        pubkey: Pubkey,
+        hash: KeccakHash,
+        difficulty: KeccakHash,
+        output: HashOutput,
+    ) -> (Option<(KeccakHash, u64)>, u64) {
+        self.search_split(pubkey, hash, difficulty, output, self.threads)
+            .await
+    }
+
+    /// Same as `search`, but splits the nonce space into `threads` jobs instead of one per
+    /// pool thread, so several searches can share the pool at once.
+    pub async fn search_split(
+        &self,
+        pubkey: Pubkey,
+        hash: KeccakHash,
+        difficulty: KeccakHash,
+        output: HashOutput,
+        threads: u64,
+    ) -> (Option<(KeccakHash, u64)>, u64) {
+        self.run_search(pubkey, hash, difficulty, output, threads, None)
+            .await
+    }
+
+    /// Same as `search`, but gives up once `deadline` passes, returning no solution and the
+    /// hashes computed until then.
+    pub async fn search_until(
+        &self,
+        pubkey: Pubkey,
+        hash: KeccakHash,
+        difficulty: KeccakHash,
+        output: HashOutput,
+        deadline: Instant,
+    ) -> (Option<(KeccakHash, u64)>, u64) {
+        self.run_search(
+            pubkey,
+            hash,
+            difficulty,
+            output,
+            self.threads,
+            Some(deadline),
+        )
+        .await
+    }
+
+    async fn run_search(
+        &self,
+        pubkey: Pubkey,
+        hash: KeccakHash,
+        difficulty: KeccakHash,
+        output: HashOutput,
+        threads: u64,
+        deadline: Option<Instant>,
+    ) -> (Option<(KeccakHash, u64)>, u64) {
+        let threads = threads.clamp(1, self.threads);
+        let progress = output == HashOutput::Progress;
+        let scroll = output == HashOutput::Scroll;
+        let hashes = Arc::new(AtomicU64::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let progress_handle =
+            progress.then(|| spawn_hash_progress(hashes.clone(), done.clone()));
+        let found_solution = Arc::new(AtomicBool::new(false));
+        let (results, mut results_reader) = tokio::sync::mpsc::channel(threads as usize);
+
+        for i in 0..threads {
+            let found_solution = found_solution.clone();
+            let hashes = hashes.clone();
+            let results = results.clone();
+            let job: Job = Box::new(move || {
+                let range = nonce_range(i, threads);
+                let result = search_range(
+                    pubkey,
+                    hash,
+                    difficulty,
+                    range,
+                    &found_solution,
+                    &hashes,
+                    deadline,
+                    scroll,
+                );
+                results.blocking_send(result).ok();
+            });
+            self.jobs.send(job).expect("hash pool stopped");
+        }
+        drop(results);
+
+        // Wait for every partition so the pool is idle again before the next job
+        let mut solution = None;
+        let mut total_hashes = 0;
+        while let Some((result, hashed)) = results_reader.recv().await {
+            total_hashes += hashed;
+            if solution.is_none() {
+                solution = result;
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+        if let Some(progress_handle) = progress_handle {
+            progress_handle.join().unwrap();
+        }
+        (solution, total_hashes)
+    }
+}
+
+/// Hashes the inclusive nonce `range` until a hash meets `difficulty`, `found_solution` is
+/// set by another partition or `deadline` passes, setting `found_solution` on a hit. Returns
+/// the solution, None once the range is exhausted or the deadline passed, and how many
+/// nonces were hashed.
+fn search_range(
+    pubkey: Pubkey,
+    hash: KeccakHash,
+    difficulty: KeccakHash,
+    (start, end): (u64, u64),
+    found_solution: &AtomicBool,
+    hashes: &AtomicU64,
+    deadline: Option<Instant>,
+    scroll: bool,
+) -> (Option<(KeccakHash, u64)>, u64) {
+    let mut stdout = stdout();
+    let hasher = NonceHasher::new(hash, pubkey);
+    let mut batch = [KeccakHash::default(); NONCE_BATCH];
+    let mut next_hash: KeccakHash;
+    let mut nonce: u64 = start;
+    let solution = loop {
+        let batch_index = ((nonce - start) % NONCE_BATCH as u64) as usize;
+        if batch_index == 0 {
+            batch = hasher.hash_batch(nonce);
+        }
+        next_hash = batch[batch_index];
+        if nonce % 10_000 == 0 {
+            if found_solution.load(Ordering::Relaxed)
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break None;
+            }
+            hashes.fetch_add(10_000, Ordering::Relaxed);
+            if start == 0 && scroll {
+                stdout.write_all(format!("\r{}", next_hash).as_bytes()).ok();
+            }
+        }
+        if next_hash.le(&difficulty) {
+            if scroll {
+                stdout.write_all(format!("\r{}", next_hash).as_bytes()).ok();
+            }
+            found_solution.store(true, Ordering::Relaxed);
+            break Some((next_hash, nonce));
+        }
+        if nonce == end {
+            break None;
+        }
+        nonce += 1;
+    };
+    (solution, nonce - start + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_range_without_solution_returns_none() {
+        // No hash is below the zero difficulty
+        let (solution, hashed) = search_range(
+            Pubkey::new_unique(),
+            KeccakHash::new_unique(),
+            KeccakHash::default(),
+            (0, 99),
+            &AtomicBool::new(false),
+            &AtomicU64::new(0),
+            None,
+            false,
+        );
+        assert!(solution.is_none());
+        assert_eq!(hashed, 100);
+    }
+
+    #[test]
+    fn search_range_finds_solution() {
+        let difficulty = KeccakHash::new_from_array([0xff; 32]);
+        let (solution, hashed) = search_range(
+            Pubkey::new_unique(),
+            KeccakHash::new_unique(),
+            difficulty,
+            (5, 99),
+            &AtomicBool::new(false),
+            &AtomicU64::new(0),
+            None,
+            false,
+        );
+        let (_, nonce) = solution.unwrap();
+        assert_eq!(nonce, 5);
+        assert_eq!(hashed, 1);
+    }
+
+    #[test]
+    fn search_range_stops_at_max_nonce() {
+        let (solution, hashed) = search_range(
+            Pubkey::new_unique(),
+            KeccakHash::new_unique(),
+            KeccakHash::default(),
+            (u64::MAX - 9, u64::MAX),
+            &AtomicBool::new(false),
+            &AtomicU64::new(0),
+            None,
+            false,
+        );
+        assert!(solution.is_none());
+        assert_eq!(hashed, 10);
+    }
+
+    #[test]
+    fn search_range_stops_at_deadline() {
+        let (solution, hashed) = search_range(
+            Pubkey::new_unique(),
+            KeccakHash::new_unique(),
+            KeccakHash::default(),
+            (0, u64::MAX),
+            &AtomicBool::new(false),
+            &AtomicU64::new(0),
+            Some(Instant::now()),
+            false,
+        );
+        assert!(solution.is_none());
+        assert_eq!(hashed, 1);
+    }
+}
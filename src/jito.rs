@@ -0,0 +1,166 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine as _;
+use log::{debug, info, warn};
+use rand::seq::SliceRandom;
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_instruction};
+use solana_sdk::{signature::Signature, transaction::Transaction};
+use tokio::time::sleep;
+
+use crate::{
+    blockhash_cache::BlockhashCache,
+    utils::{CONFIRM_TIMEOUT_ERROR, TX_FAILED_ERROR},
+};
+
+// Jito's mainnet tip accounts, one is picked at random per bundle
+const TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+// How often a bundle is resubmitted and its status checked until it lands
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lands mine-v2 batches as Jito bundles with `--jito`, instead of resending the tx to the
+/// rpc until it confirms. Each batch tx pays the block engine a `--jito-tip-lamports` tip.
+#[derive(Clone)]
+pub struct Jito {
+    url: String,
+    tip_lamports: u64,
+    client: reqwest::Client,
+}
+
+impl Jito {
+    pub fn new(url: String, tip_lamports: u64) -> Self {
+        Self {
+            url,
+            tip_lamports,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn tip_lamports(&self) -> u64 {
+        self.tip_lamports
+    }
+
+    /// Transfers the tip from `payer` to one of the tip accounts.
+    pub fn tip_ix(&self, payer: &Pubkey) -> Instruction {
+        let tip_account = TIP_ACCOUNTS.choose(&mut rand::thread_rng()).unwrap();
+        let tip_account = Pubkey::from_str(tip_account).unwrap();
+        system_instruction::transfer(payer, &tip_account, self.tip_lamports)
+    }
+
+    /// Submits the signed `tx` as a single-transaction bundle, resubmitting until the block
+    /// engine reports it landed, the blockhash expires or `confirm_timeout` passes. Returns the
+    /// tx signature and the seconds it took to land.
+    pub async fn send_and_confirm(
+        &self,
+        rpc_client: &RpcClient,
+        tx: &Transaction,
+        last_valid_blockheight: u64,
+        confirm_timeout: Option<Duration>,
+    ) -> Result<(Signature, u64), String> {
+        let sent_at = Instant::now();
+        let bytes = bincode::serialize(tx).map_err(|e| e.to_string())?;
+        let encoded_tx = BASE64.encode(bytes);
+        let mut bundle_ids: Vec<String> = vec![];
+        loop {
+            match self
+                .request("sendBundle", json!([[encoded_tx], { "encoding": "base64" }]))
+                .await
+            {
+                Ok(Value::String(bundle_id)) => {
+                    if !bundle_ids.contains(&bundle_id) {
+                        debug!("Sent bundle {}", bundle_id);
+                        bundle_ids.push(bundle_id);
+                    }
+                }
+                Ok(res) => warn!("Unexpected sendBundle result: {}", res),
+                Err(e) => warn!("Failed to send bundle: {}", e),
+            }
+            sleep(POLL_INTERVAL).await;
+
+            if !bundle_ids.is_empty() {
+                match self.request("getBundleStatuses", json!([bundle_ids])).await {
+                    Ok(statuses) => {
+                        if let Some(landed) = landed_bundle(&statuses) {
+                            info!("Bundle landed!");
+                            return match landed {
+                                Ok(()) => Ok((tx.signatures[0], sent_at.elapsed().as_secs())),
+                                Err(e) => {
+                                    warn!("STATUS: {}", e);
+                                    Err(TX_FAILED_ERROR.to_string())
+                                }
+                            };
+                        }
+                    }
+                    Err(e) => warn!("Failed to get bundle statuses: {}", e),
+                }
+            }
+
+            if confirm_timeout.is_some_and(|timeout| sent_at.elapsed() > timeout) {
+                return Err(CONFIRM_TIMEOUT_ERROR.to_string());
+            }
+            if let Ok(current_blockheight) = BlockhashCache::block_height(rpc_client).await {
+                if current_blockheight > last_valid_blockheight {
+                    return Err("Last valid blockheight exceeded!".to_string());
+                }
+            }
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let res = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        let res: Value = serde_json::from_str(&res).map_err(|e| e.to_string())?;
+        if let Some(error) = res.get("error") {
+            return Err(error.to_string());
+        }
+        Ok(res["result"].clone())
+    }
+}
+
+/// The outcome of the first confirmed bundle in a `getBundleStatuses` result, `None` while
+/// none has landed.
+fn landed_bundle(statuses: &Value) -> Option<Result<(), String>> {
+    let statuses = statuses["value"].as_array()?;
+    statuses.iter().find_map(|status| {
+        match status["confirmation_status"].as_str() {
+            Some("confirmed") | Some("finalized") => {}
+            _ => return None,
+        }
+        match &status["err"] {
+            Value::Object(err) if err.contains_key("Ok") => Some(Ok(())),
+            Value::Null => Some(Ok(())),
+            err => Some(Err(err.to_string())),
+        }
+    })
+}
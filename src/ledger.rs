@@ -0,0 +1,80 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+};
+
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use crate::utils::format_ore;
+
+/// One successful claim, written as a JSON line to the `--ledger` file.
+#[derive(Serialize)]
+pub struct ClaimRecord {
+    pub timestamp: String,
+    pub wallet: String,
+    pub amount_raw: u64,
+    pub amount_ui: String,
+    pub beneficiary: String,
+    pub signature: String,
+    pub slot: Option<u64>,
+}
+
+impl ClaimRecord {
+    /// Builds the record for a landed claim, looking up the slot it landed in.
+    pub async fn new(
+        client: &RpcClient,
+        wallet: Pubkey,
+        amount: u64,
+        beneficiary: Pubkey,
+        signature: Signature,
+    ) -> Self {
+        let slot = match client.get_signature_statuses(&[signature]).await {
+            Ok(statuses) => statuses.value[0].as_ref().map(|status| status.slot),
+            Err(_) => None,
+        };
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            wallet: wallet.to_string(),
+            amount_raw: amount,
+            amount_ui: format_ore(amount, ore::TOKEN_DECIMALS as usize),
+            beneficiary: beneficiary.to_string(),
+            signature: signature.to_string(),
+            slot,
+        }
+    }
+}
+
+/// Append-only claim ledger. Existing records are never rewritten, so runs accumulate in
+/// one file.
+pub struct ClaimLedger {
+    path: String,
+    file: File,
+}
+
+impl ClaimLedger {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open ledger {}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    /// Appends one record and syncs it to disk before returning, so a crash can't lose it.
+    pub fn append(&mut self, record: &ClaimRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.flush())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("Failed to write to ledger {}: {}", self.path, e))
+    }
+}
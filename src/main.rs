@@ -1,35 +1,80 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
 mod balance;
+mod bench_hash;
+mod blockhash_cache;
 mod busses;
 mod claim;
+mod compress;
+mod confirm_service;
 mod cu_limits;
+mod failover_sender;
+mod fee_payer;
+mod hash;
+mod hash_pool;
 #[cfg(feature = "admin")]
 mod initialize;
+mod jito;
+mod ledger;
+mod metrics;
 mod mine;
+mod mine_csv;
+mod mine_queue;
+mod mine_report;
+mod mine_stats;
+mod nonce_hasher;
 mod miner_v2;
+mod output;
+mod profitability;
+mod pubkeys;
 mod register;
 mod rewards;
 mod send_and_confirm;
+mod share_stats;
+mod snapshot;
+mod summary;
 mod treasury;
 #[cfg(feature = "admin")]
 mod update_admin;
 #[cfg(feature = "admin")]
 mod update_difficulty;
 mod utils;
+mod verify_tx;
+mod webhook;
 
-use std::sync::Arc;
+use std::{io::Write, net::IpAddr, str::FromStr, sync::Arc, time::Duration};
 
-use clap::{command, Parser, Subcommand};
-use miner_v2::MinerV2;
+use clap::{command, error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use confirm_service::ConfirmService;
+use fee_payer::{ExternalSigner, FeePayer};
+use jito::Jito;
+use log::{warn, Level, LevelFilter};
+use miner_v2::{AutoTopup, BusStrategy, ClaimConfig, MineV2Config, MinerV2};
+use output::OutputFormat;
+use share_stats::SharedStats;
+use webhook::{Webhook, WebhookEvent};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{native_token::lamports_to_sol, pubkey::Pubkey};
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
     signature::{read_keypair_file, Keypair},
 };
+use summary::{Summary, NOTHING_TO_DO_EXIT_CODE};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use utils::{
+    check_token_program, estimate_tx_fee, get_priority_fee_percentile, new_rpc_client,
+    refresh_priority_fee, PriorityFee, Threads, TokenProgram, TxSettings, WalletSource,
+    RPC_KEEP_ALIVE, RPC_TIMEOUT,
+};
 
 struct Miner {
     pub keypair_filepath: Option<String>,
-    pub priority_fee: u64,
+    pub priority_fee: PriorityFee,
     pub rpc_client: Arc<RpcClient>,
+    pub tx_settings: TxSettings,
+    pub decimals: usize,
+    pub token_program: TokenProgram,
 }
 
 #[derive(Parser, Debug)]
@@ -38,10 +83,11 @@ struct Args {
     #[arg(
         long,
         value_name = "NETWORK_URL",
-        help = "Network address of your RPC provider",
+        help = "Network address of your RPC provider. Give several, comma separated or repeated, to fail over to the next one when an endpoint can't be reached and to broadcast transactions to all of them.",
+        value_delimiter = ',',
         global = true
     )]
-    rpc: Option<String>,
+    rpc: Vec<String>,
 
     #[clap(
         global = true,
@@ -69,6 +115,238 @@ struct Args {
     )]
     priority_fee: u64,
 
+    #[arg(
+        long,
+        value_name = "DECIMALS",
+        help = "Number of decimal places to display for ORE amounts",
+        default_value = "9",
+        global = true
+    )]
+    decimals: usize,
+
+    #[arg(
+        long,
+        value_name = "CONNECTIONS",
+        help = "Max keep-alive connections to hold open to the RPC. Set this to at least the number of transactions sending and confirming at once (batches in flight), otherwise requests queue behind reconnects.",
+        global = true
+    )]
+    rpc_max_connections: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "ENDPOINT",
+        help = "Opt-in: when the command finishes, POST anonymized aggregate stats (landing rate, average confirm time, priority fee bucket and --share-stats-region) as JSON to this URL. No pubkeys, signatures or amounts are sent. Nothing is sent unless this is set.",
+        global = true
+    )]
+    share_stats: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "POST a JSON payload to this URL on the --webhook-events, e.g. for Slack, Discord or PagerDuty alerts. Failed posts are retried with backoff in the background.",
+        global = true
+    )]
+    webhook: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "EVENTS",
+        help = "Comma separated events to send to --webhook.",
+        value_delimiter = ',',
+        default_value = "first-success,failures,low-balance,summary",
+        global = true
+    )]
+    webhook_events: Vec<WebhookEvent>,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Send the failures webhook event after this many transactions fail in a row.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "3",
+        global = true
+    )]
+    webhook_failures: u64,
+
+    #[arg(
+        long,
+        value_name = "PERCENTILE",
+        help = "Use this percentile (0-100) of recent prioritization fees on the ore program as the priority fee instead of --priority-fee, e.g. 50 to save, 90 to land faster.",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        global = true
+    )]
+    priority_fee_percentile: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Total priority fee to pay per transaction in lamports, instead of --priority-fee per compute unit. The per compute unit price is worked out from each transaction's compute unit limit.",
+        conflicts_with = "priority_fee_percentile",
+        global = true
+    )]
+    priority_fee_total: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Re-estimate the priority fee from recent prioritization fees on the ore program before every mine-v2 batch and claim-v2 transaction, at --priority-fee-percentile (default 75). Falls back to --priority-fee until the first estimate.",
+        conflicts_with = "priority_fee_total",
+        global = true
+    )]
+    auto_priority_fee: bool,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Never pay more than this many microlamports per compute unit when the priority fee comes from recent prioritization fees.",
+        global = true
+    )]
+    max_priority_fee: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "REGION",
+        help = "Free-text RPC region to include with --share-stats, e.g. us-east",
+        global = true
+    )]
+    share_stats_region: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PROGRAM",
+        help = "Token program that owns the ORE mint. Used to derive and create ORE token accounts.",
+        default_value = "spl",
+        global = true
+    )]
+    token_program: TokenProgram,
+
+    #[arg(
+        long,
+        help = "Print the effective configuration after merging args, the config file and defaults as JSON, then exit without running the command.",
+        global = true
+    )]
+    dump_config: bool,
+
+    #[arg(
+        long,
+        value_name = "SIGNATURES",
+        help = "Max signatures to check per getSignatureStatuses request. Confirmations of every in-flight transaction are checked together in batches of this size.",
+        value_parser = clap::value_parser!(u64).range(1..=confirm_service::MAX_SIG_STATUS_BATCH_SIZE as i64),
+        default_value = "256",
+        global = true
+    )]
+    sig_status_batch_size: u64,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Warn and ask for confirmation before running if a typical transaction would cost more than this many lamports in fees.",
+        default_value = "1000000",
+        global = true
+    )]
+    warn_on_high_fee: u64,
+
+    #[arg(
+        long,
+        short = 'y',
+        help = "Skip confirmation prompts, e.g. the --warn-on-high-fee check.",
+        global = true
+    )]
+    yes: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Abandon a transaction simulation that takes longer than this, e.g. an RPC waiting on a context slot it never reaches, and retry it instead.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "10",
+        global = true
+    )]
+    sim_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Give up on a transaction that hasn't confirmed after this long, instead of waiting for its blockhash to expire. Mine-v2 requeues its wallets.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = None,
+        global = true
+    )]
+    confirm_timeout_secs: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "REQUESTS",
+        help = "Cap how many transaction sends and confirmation checks are in flight against the rpc at once, across all wallets. Sends wait for a free slot instead of failing.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = None,
+        global = true
+    )]
+    max_rpc_concurrency: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Never let a wallet's SOL drop below this many lamports to pay fees or send SOL, e.g. to keep it rent-exempt. Claims, transfers and transactions that would break it are skipped.",
+        default_value = "0",
+        global = true
+    )]
+    min_sol_reserve: u64,
+
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "Send mine-v2 transactions through this RPC instead of --rpc, e.g. a fast low-commitment endpoint.",
+        global = true
+    )]
+    send_rpc: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMITMENT",
+        help = "Commitment for --send-rpc: processed, confirmed or finalized.",
+        value_parser = parse_commitment,
+        default_value = "confirmed",
+        global = true
+    )]
+    send_commitment: CommitmentLevel,
+
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "Check mine-v2 transaction confirmations against this RPC instead of --rpc, e.g. a reliable high-commitment endpoint.",
+        global = true
+    )]
+    confirm_rpc: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMITMENT",
+        help = "Commitment for --confirm-rpc: processed, confirmed or finalized.",
+        value_parser = parse_commitment,
+        default_value = "confirmed",
+        global = true
+    )]
+    confirm_commitment: CommitmentLevel,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Format for command results: text, json or csv. Supported by balance, busses, rewards, treasury, wallets and pubkeys, the other commands only print text and reject json or csv.",
+        default_value = "text",
+        global = true
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        help = "Log level: error, warn, info, debug or trace. Debug adds per-transaction details, trace also scrolls the hashes while mining.",
+        value_parser = parse_log_level,
+        default_value = "info",
+        global = true
+    )]
+    log_level: LevelFilter,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -90,7 +368,7 @@ enum Commands {
     #[command(about = "Claim available mining rewards")]
     Claim(ClaimArgs),
 
-    #[command(about = "Claim available mining rewards. Uses v2 send logic and has a few additional commands.")]
+    #[command(about = "Claim available mining rewards. Uses v2 send logic and has a few additional commands. Exits with code 3 when no wallet has rewards.")]
     ClaimV2(ClaimV2Args),
 
     #[command(about = "Fetch your balance of unclaimed mining rewards")]
@@ -102,9 +380,33 @@ enum Commands {
     #[command(about = "Log data about the wallets in the supplied directory.")]
     Wallets(WalletsArgs),
 
+    #[command(about = "Print the pubkey of every wallet in the supplied directory, without any RPC calls.")]
+    Pubkeys(PubkeysArgs),
+
     #[command(about = "Send sol from supplied wallet key file, to wallets in supplied directory.")]
     SendSol(SendSolArgs),
 
+    #[command(about = "Send the SOL of every wallet in the supplied directory, minus the transfer fee, to one destination.")]
+    CollectSol(CollectSolArgs),
+
+    #[command(about = "Estimate the best MineV2 batch size against current network conditions.")]
+    TuneBatch(TuneBatchArgs),
+
+    #[command(about = "Write the SOL, ORE and claimable ORE balances of every wallet in the supplied directory to a snapshot file.")]
+    Snapshot(SnapshotArgs),
+
+    #[command(about = "Show per-wallet and fleet balance changes between two snapshot files.")]
+    SnapshotDiff(SnapshotDiffArgs),
+
+    #[command(about = "Benchmark hashing speed for 1 up to --max-threads threads, over a number of solutions or a fixed duration.")]
+    BenchHash(BenchHashArgs),
+
+    #[command(about = "Find and validate a hash for explicit proof hash, pubkey and difficulty inputs, independent of on-chain state.")]
+    Hash(HashArgs),
+
+    #[command(about = "Verify the ORE mine instructions of a landed transaction and report what it earned.")]
+    VerifyTx(VerifyTxArgs),
+
     #[cfg(feature = "admin")]
     #[command(about = "Initialize the program")]
     Initialize(InitializeArgs),
@@ -118,6 +420,84 @@ enum Commands {
     UpdateDifficulty(UpdateDifficultyArgs),
 }
 
+impl Commands {
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Balance(_) => "balance",
+            Commands::Busses(_) => "busses",
+            Commands::Mine(_) => "mine",
+            Commands::MineV2(_) => "mine-v2",
+            Commands::Claim(_) => "claim",
+            Commands::ClaimV2(_) => "claim-v2",
+            Commands::Rewards(_) => "rewards",
+            Commands::Treasury(_) => "treasury",
+            Commands::Wallets(_) => "wallets",
+            Commands::Pubkeys(_) => "pubkeys",
+            Commands::SendSol(_) => "send-sol",
+            Commands::CollectSol(_) => "collect-sol",
+            Commands::TuneBatch(_) => "tune-batch",
+            Commands::Snapshot(_) => "snapshot",
+            Commands::SnapshotDiff(_) => "snapshot-diff",
+            Commands::BenchHash(_) => "bench-hash",
+            Commands::Hash(_) => "hash",
+            Commands::VerifyTx(_) => "verify-tx",
+            #[cfg(feature = "admin")]
+            Commands::Initialize(_) => "initialize",
+            #[cfg(feature = "admin")]
+            Commands::UpdateAdmin(_) => "update-admin",
+            #[cfg(feature = "admin")]
+            Commands::UpdateDifficulty(_) => "update-difficulty",
+        }
+    }
+
+    /// Whether the command prints its results through `--output`, the others only print text.
+    fn supports_output(&self) -> bool {
+        matches!(
+            self,
+            Commands::Balance(_)
+                | Commands::Busses(_)
+                | Commands::Rewards(_)
+                | Commands::Treasury(_)
+                | Commands::Wallets(_)
+                | Commands::Pubkeys(_)
+        )
+    }
+
+    /// Whether the command reads or creates ORE token accounts.
+    fn uses_token_accounts(&self) -> bool {
+        matches!(
+            self,
+            Commands::Balance(_)
+                | Commands::Mine(_)
+                | Commands::MineV2(_)
+                | Commands::Claim(_)
+                | Commands::ClaimV2(_)
+                | Commands::Treasury(_)
+                | Commands::Wallets(_)
+                | Commands::Snapshot(_)
+        )
+    }
+
+    /// Whether the command sends or simulates transactions, the only ones that pay a
+    /// priority fee.
+    fn sends_transactions(&self) -> bool {
+        match self {
+            Commands::Mine(_)
+            | Commands::MineV2(_)
+            | Commands::Claim(_)
+            | Commands::ClaimV2(_)
+            | Commands::SendSol(_)
+            | Commands::CollectSol(_)
+            | Commands::TuneBatch(_) => true,
+            #[cfg(feature = "admin")]
+            Commands::Initialize(_) | Commands::UpdateAdmin(_) | Commands::UpdateDifficulty(_) => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 struct BalanceArgs {
     #[arg(
@@ -147,10 +527,11 @@ struct MineArgs {
         long,
         short,
         value_name = "THREAD_COUNT",
-        help = "The number of threads to dedicate to mining",
+        help = "The number of threads to dedicate to mining, or auto to use every core.",
+        value_parser = utils::parse_threads,
         default_value = "1"
     )]
-    threads: u64,
+    threads: Threads,
     #[arg(
         long,
         short = 's',
@@ -159,6 +540,20 @@ struct MineArgs {
         default_value = "1000"
     )]
     send_interval: u64,
+    #[arg(
+        long,
+        help = "Show a spinner with the running hashrate instead of the scrolling hash.",
+        default_value = "false"
+    )]
+    progress: bool,
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Stop after this many successful submissions. Mines until interrupted by default.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = None
+    )]
+    max_rounds: Option<u64>,
 }
 
 #[derive(Parser, Debug)]
@@ -167,10 +562,12 @@ struct MineV2Args {
         long,
         short,
         value_name = "THREAD_COUNT",
-        help = "The number of threads to dedicate to mining",
+        visible_alias = "total-threads",
+        help = "The number of threads to dedicate to mining, shared by every wallet hashing at once, or auto to use every core.",
+        value_parser = utils::parse_threads,
         default_value = "1"
     )]
-    threads: u64,
+    threads: Threads,
     #[arg(
         long,
         short = 's',
@@ -181,12 +578,44 @@ struct MineV2Args {
     send_interval: u64,
     #[arg(
         long,
-        short = 's',
-        value_name = "SIMULTATION_ATTEMPS",
-        help = "The amount of simulation attempts before sending transaction. Useful for debugging ",
+        help = "Stop the whole run with a nonzero exit code on the first failed transaction.",
+        default_value = "false"
+    )]
+    fail_fast: bool,
+    #[arg(
+        long,
+        value_name = "SIMULATION_ATTEMPTS",
+        help = "Simulate each batch transaction before sending it, up to this many times, and drop the batch if it never simulates cleanly. Useful for debugging failing transactions.",
+        value_parser = clap::value_parser!(u64).range(1..),
         default_value = None,
     )]
     sim_attempts: Option<u64>,
+    #[arg(
+        long,
+        help = "Dry run: hash, build and simulate each batch's transaction, printing the compute units used and any program error, without registering wallets or sending anything.",
+        default_value = "false"
+    )]
+    simulate_only: bool,
+    #[arg(
+        long,
+        help = "Land each batch as a Jito bundle with a tip, instead of resending the transaction to the rpc until it confirms.",
+        default_value = "false"
+    )]
+    jito: bool,
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Jito block engine bundles endpoint for --jito.",
+        default_value = "https://mainnet.block-engine.jito.wtf/api/v1/bundles"
+    )]
+    jito_url: String,
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Tip paid by each --jito bundle's fee payer.",
+        default_value = "10000"
+    )]
+    jito_tip_lamports: u64,
     #[arg(
         long,
         short = 'b',
@@ -195,6 +624,14 @@ struct MineV2Args {
         default_value = "1"
     )]
     batch_size: u64,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Mine a partial batch once no new wallet has arrived for this many seconds, so wallets left over when the wallet count isn't a multiple of --batch-size still mine.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "10"
+    )]
+    batch_timeout_secs: u64,
     #[arg(
         long,
         short = 'f',
@@ -203,6 +640,23 @@ struct MineV2Args {
         default_value = None
     )]
     fee_payer: Option<String>,
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Sign for the fee payer with this shell command instead of a key file, e.g. for a multisig or remote signer. It gets the base64 message on stdin and must print the base58 signature.",
+        conflicts_with = "fee_payer",
+        requires = "fee_payer_pubkey",
+        default_value = None
+    )]
+    fee_payer_signer: Option<String>,
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Address of the fee payer --fee-payer-signer signs for.",
+        requires = "fee_payer_signer",
+        default_value = None
+    )]
+    fee_payer_pubkey: Option<String>,
     #[arg(
         long,
         short = 'w',
@@ -211,90 +665,453 @@ struct MineV2Args {
         default_value = None
     )]
     miner_wallets: Option<String>,
-}
-
-#[derive(Parser, Debug)]
-struct TreasuryArgs {}
-
-#[derive(Parser, Debug)]
-struct ClaimArgs {
     #[arg(
-        // long,
-        value_name = "AMOUNT",
-        help = "The amount of rewards to claim. Defaults to max."
+        long,
+        value_name = "FILE",
+        help = "Derive the wallets from the BIP39 seed phrase in this file instead of reading --miner-wallets, along the standard Solana path m/44'/501'/i'/0'.",
+        conflicts_with = "miner_wallets",
+        requires = "derivation_count",
+        default_value = None
     )]
-    amount: Option<f64>,
-
+    mnemonic_file: Option<String>,
     #[arg(
-        // long,
-        value_name = "TOKEN_ACCOUNT_ADDRESS",
-        help = "Token account to receive mining rewards."
+        long,
+        value_name = "N",
+        help = "How many wallets to derive from --mnemonic-file.",
+        requires = "mnemonic_file",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value = None
     )]
-    beneficiary: Option<String>,
-}
-
-#[derive(Parser, Debug)]
-struct ClaimV2Args {
+    derivation_count: Option<u32>,
     #[arg(
-        // long,
-        value_name = "AMOUNT",
-        help = "The amount of rewards to claim. Defaults to max."
+        long,
+        help = "Show a spinner with the running hashrate instead of the scrolling hash.",
+        default_value = "false"
     )]
-    amount: Option<f64>,
+    progress: bool,
     #[arg(
-        // long,
-        short = 'b',
-        value_name = "TOKEN_ACCOUNT_ADDRESS",
-        help = "Token account to receive mining rewards."
+        long,
+        value_name = "LAMPORTS",
+        help = "Stop once the fee payers have paid this many lamports in fees in total this session, waiting for the transactions in flight and exiting with an error.",
+        default_value = None
     )]
-    beneficiary: Option<String>,
+    max_fee_payer_spend: Option<u64>,
     #[arg(
         long,
-        short = 's',
-        value_name = "SEND_INTERVAL",
-        help = "The amount of time to wait between tx sends. 100ms is 10 sends per second.",
-        default_value = "1000"
+        value_name = "ROUNDS",
+        help = "Mine until every wallet has landed this many times, then exit. Runs forever if not set.",
+        default_value = None
     )]
-    send_interval: u64,
+    rounds: Option<u64>,
     #[arg(
         long,
-        short = 'w',
-        value_name = "MINER_WALLETS",
-        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        help = "Mine until every wallet has landed once, then exit. Same as `--rounds 1`.",
+        conflicts_with = "rounds",
+        default_value = "false"
+    )]
+    once: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Keep a JSON report of landed and failed transactions per wallet in this file, for `--retry-failed`.",
         default_value = None
     )]
-    miner_wallets: Option<String>,
-}
-
-
-#[derive(Parser, Debug)]
-struct WalletsArgs {
+    report: Option<String>,
     #[arg(
         long,
-        short = 'w',
-        value_name = "MINER_WALLETS",
-        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        value_name = "REPORT_FILE",
+        help = "Only mine the wallets whose latest transaction failed in this `--report` file.",
         default_value = None
     )]
-    miner_wallets: Option<String>,
-}
-
-#[derive(Parser, Debug)]
-struct SendSolArgs {
+    retry_failed: Option<String>,
     #[arg(
         long,
-        short = 'p',
-        value_name = "SENDER_WALLET",
-        help = "The wallet key file to send the sol from.",
+        value_name = "RATIO",
+        help = "Stop mining once the ORE mined per SOL spent on fees, averaged over the last landed transactions, falls below this ratio.",
+        default_value = None
     )]
-    sender_wallet: String,
+    min_ore_per_sol: Option<f64>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Persist which wallets are queued, in flight and done this round to this file, and resume in that order after a restart.",
+        default_value = None
+    )]
+    queue_file: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append every transaction result to this JSON lines file, and include the results already in it in the running stats.",
+        default_value = None
+    )]
+    stats_file: Option<String>,
+    #[arg(
+        long = "csv",
+        value_name = "PATH",
+        help = "Append every wallet's result to this CSV file: wallet_pubkey, bus_id, nonce, hash_time_secs, tx_time_secs, signature, landed and timestamp_unix. The header is written when the file is new.",
+        default_value = None
+    )]
+    csv_file: Option<String>,
+    #[arg(
+        long,
+        help = "Gzip the --report, --stats-file and --csv files, adding .gz to their paths. Paths already ending in .gz are read and written compressed either way.",
+        default_value = "false"
+    )]
+    compress: bool,
+    #[arg(
+        long,
+        value_name = "ATTEMPTS",
+        help = "Give up registering a wallet's proof account after this many failed attempts, backing off between them.",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value = "5"
+    )]
+    max_register_attempts: u32,
+    #[arg(
+        long,
+        value_name = "TARGET_LAMPORTS",
+        help = "Top a paying wallet back up to this many lamports from --funding-wallet once it drops below half of it.",
+        requires_all = ["funding_wallet", "max_topup_total"],
+        default_value = None
+    )]
+    auto_topup: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FUNDING_WALLET",
+        help = "The wallet key file --auto-topup sends SOL from.",
+        default_value = None
+    )]
+    funding_wallet: Option<String>,
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Most lamports --auto-topup may send in total this session.",
+        default_value = None
+    )]
+    max_topup_total: Option<u64>,
+    #[arg(
+        long,
+        value_name = "THREAD_COUNT",
+        help = "Threads each wallet hashes with. --threads / this many wallets hash at once. Defaults to splitting --threads evenly between the wallets of each batch.",
+        default_value = None
+    )]
+    threads_per_wallet: Option<u64>,
+    #[arg(
+        long,
+        help = "Give every wallet in a batch all of --threads instead of splitting them, running up to --threads x --batch-size hashing threads.",
+        conflicts_with = "threads_per_wallet",
+        default_value = "false"
+    )]
+    overcommit: bool,
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How the wallets of a batch are spread over the busses.",
+        default_value = "roundrobin"
+    )]
+    bus_strategy: BusStrategy,
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "Claim every wallet to --claim-beneficiary once their combined claimable rewards reach this amount, with a unit, e.g. `10ore`.",
+        value_parser = utils::parse_ore_amount,
+        requires = "claim_beneficiary",
+        default_value = None
+    )]
+    claim_threshold_ore: Option<u64>,
+    #[arg(
+        long,
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Token account --claim-threshold-ore claims rewards to.",
+        default_value = None
+    )]
+    claim_beneficiary: Option<String>,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How often to check claimable rewards against --claim-threshold-ore.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "60"
+    )]
+    claim_check_interval: u64,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Log how many wallets made it through the pipeline this often.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "60"
+    )]
+    heartbeat_interval: u64,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Log a prominent error when no wallet has landed or failed for this long.",
+        default_value = None
+    )]
+    stall_timeout: Option<u64>,
+    #[arg(
+        long,
+        help = "Exit with a nonzero code once --stall-timeout is hit, so a supervisor can restart the miner.",
+        requires = "stall_timeout",
+        default_value = "false"
+    )]
+    exit_on_stall: bool,
+    #[arg(
+        long,
+        value_name = "PORT",
+        help = "Serve Prometheus metrics on this port at /metrics: hashes, confirmed and failed transactions, average hash and tx times, and active wallets.",
+        default_value = None
+    )]
+    metrics_port: Option<u16>,
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Address the --metrics-port server listens on. Use 0.0.0.0 to let other hosts scrape it.",
+        default_value = "127.0.0.1"
+    )]
+    metrics_bind: IpAddr,
+}
+
+#[derive(Parser, Debug)]
+struct TreasuryArgs {}
+
+#[derive(Parser, Debug)]
+struct ClaimArgs {
+    #[arg(
+        // long,
+        value_name = "AMOUNT",
+        help = "The amount of rewards to claim with a unit, e.g. `1.5ore` or `1500000000raw`. Defaults to max.",
+        value_parser = utils::parse_ore_amount
+    )]
+    amount: Option<u64>,
+
+    #[arg(
+        // long,
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Token account to receive mining rewards."
+    )]
+    beneficiary: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "Wallet to receive mining rewards. Claims to its ORE token account, creating it if missing.",
+        conflicts_with = "beneficiary"
+    )]
+    beneficiary_owner: Option<String>,
+
+    #[arg(
+        long,
+        help = "Wait until just after the next epoch reset before claiming.",
+        default_value = "false"
+    )]
+    wait_for_epoch: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append a JSON line for every successful claim to this file. Existing lines are never overwritten.",
+        default_value = None
+    )]
+    ledger: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ClaimV2Args {
+    #[arg(
+        // long,
+        value_name = "AMOUNT",
+        help = "The amount of rewards to claim with a unit, e.g. `1.5ore` or `1500000000raw`. Defaults to max.",
+        value_parser = utils::parse_ore_amount
+    )]
+    amount: Option<u64>,
+    #[arg(
+        // long,
+        short = 'b',
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Token account to receive mining rewards."
+    )]
+    beneficiary: Option<String>,
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "Wallet to receive mining rewards. Claims to its ORE token account, creating it if missing.",
+        conflicts_with_all = ["beneficiary", "claim_to_self_then_transfer"]
+    )]
+    beneficiary_owner: Option<String>,
+    #[arg(
+        long,
+        short = 's',
+        value_name = "SEND_INTERVAL",
+        help = "The amount of time to wait between tx sends. 100ms is 10 sends per second.",
+        default_value = "1000"
+    )]
+    send_interval: u64,
+    #[arg(
+        long,
+        short = 'w',
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Derive the wallets from the BIP39 seed phrase in this file instead of reading --miner-wallets, along the standard Solana path m/44'/501'/i'/0'.",
+        conflicts_with = "miner_wallets",
+        requires = "derivation_count",
+        default_value = None
+    )]
+    mnemonic_file: Option<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "How many wallets to derive from --mnemonic-file.",
+        requires = "mnemonic_file",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value = None
+    )]
+    derivation_count: Option<u32>,
+    #[arg(
+        long,
+        help = "Stop the whole run with a nonzero exit code on the first failed claim.",
+        default_value = "false"
+    )]
+    fail_fast: bool,
+    #[arg(
+        long,
+        help = "Wait until just after the next epoch reset before claiming.",
+        default_value = "false"
+    )]
+    wait_for_epoch: bool,
+    #[arg(
+        long,
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Claim to each wallet's own token account, then transfer the claimed ORE to this token account.",
+        conflicts_with = "beneficiary",
+        default_value = None
+    )]
+    claim_to_self_then_transfer: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append a JSON line for every successful claim to this file. Existing lines are never overwritten.",
+        default_value = None
+    )]
+    ledger: Option<String>,
+    #[arg(
+        long,
+        help = "Claim every wallet to the beneficiary with up to 5 claims per transaction, each paid by its first wallet. Needs a beneficiary or --beneficiary-owner.",
+        conflicts_with = "claim_to_self_then_transfer",
+        default_value = "false"
+    )]
+    aggregate: bool,
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "Skip wallets with fewer claimable rewards than this, with a unit, e.g. `0.01ore`, so dust isn't claimed at a loss.",
+        value_parser = utils::parse_ore_amount,
+        default_value = None
+    )]
+    min_claim_amount: Option<u64>,
+}
+
+
+#[derive(Parser, Debug)]
+struct WalletsArgs {
+    #[arg(
+        long,
+        short = 'w',
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Derive the wallets from the BIP39 seed phrase in this file instead of reading --miner-wallets, along the standard Solana path m/44'/501'/i'/0'.",
+        conflicts_with = "miner_wallets",
+        requires = "derivation_count",
+        default_value = None
+    )]
+    mnemonic_file: Option<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "How many wallets to derive from --mnemonic-file.",
+        requires = "mnemonic_file",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value = None
+    )]
+    derivation_count: Option<u32>,
+    #[arg(
+        long,
+        help = "Also show wallets with no claimable rewards.",
+        default_value = "false"
+    )]
+    include_zero: bool,
+}
+
+#[derive(Parser, Debug)]
+struct PubkeysArgs {
+    #[arg(
+        long,
+        short = 'w',
+        visible_alias = "wallets",
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct SendSolArgs {
+    #[arg(
+        long,
+        short = 'p',
+        value_name = "SENDER_WALLET",
+        help = "The wallet key file to send the sol from.",
+    )]
+    sender_wallet: String,
     #[arg(
         long,
         value_name = "AMOUNT",
         help = "The amount of lamports to send.",
+        conflicts_with = "top_up",
         default_value = None,
     )]
     amount: Option<u64>,
+    #[arg(
+        long,
+        value_name = "TARGET_LAMPORTS",
+        help = "Top up each wallet to this many lamports instead of sending a flat amount. Wallets already at the target are skipped.",
+        default_value = None,
+    )]
+    top_up: Option<u64>,
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Only send to wallets holding fewer than this many lamports, so a rerun skips wallets that are already funded.",
+        default_value = None,
+    )]
+    only_if_below: Option<u64>,
+    #[arg(
+        long,
+        help = "Split the sender's balance, minus its fees and --min-sol-reserve, evenly between the wallets instead of sending a flat amount.",
+        conflicts_with_all = ["amount", "top_up"],
+        default_value = "false"
+    )]
+    equal_split: bool,
+    #[arg(
+        long,
+        short = 'c',
+        value_name = "CONCURRENCY",
+        help = "Number of transfer transactions to have in flight at once.",
+        default_value = "1"
+    )]
+    concurrency: usize,
     #[arg(
         long,
         short = 's',
@@ -313,6 +1130,205 @@ struct SendSolArgs {
     receiving_wallets: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+struct CollectSolArgs {
+    #[arg(
+        long,
+        short = 'd',
+        value_name = "ADDRESS",
+        help = "The address to send the collected sol to."
+    )]
+    destination: String,
+    #[arg(
+        long,
+        short = 's',
+        value_name = "SEND_INTERVAL",
+        help = "The amount of time to wait between tx sends. 100ms is 10 sends per second.",
+        default_value = "1000"
+    )]
+    send_interval: u64,
+    #[arg(
+        long,
+        short = 'w',
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets to collect the sol from. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct TuneBatchArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The number of threads to dedicate to mining, or auto to use every core.",
+        value_parser = utils::parse_threads,
+        default_value = "1"
+    )]
+    threads: Threads,
+    #[arg(
+        long,
+        short = 's',
+        value_name = "SEND_INTERVAL",
+        help = "The amount of time to wait between tx sends. 100ms is 10 sends per second.",
+        default_value = "1000"
+    )]
+    send_interval: u64,
+    #[arg(
+        long,
+        short = 'r',
+        value_name = "ROUNDS",
+        help = "The number of transactions to try for each batch size.",
+        default_value = "3"
+    )]
+    rounds: u64,
+    #[arg(
+        long,
+        help = "Send real transactions instead of simulating them. This spends SOL.",
+        default_value = "false"
+    )]
+    real: bool,
+    #[arg(
+        long,
+        short = 'w',
+        alias = "wallets",
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct SnapshotArgs {
+    #[arg(
+        long,
+        short = 'w',
+        alias = "wallets",
+        value_name = "MINER_WALLETS",
+        help = "The directory/folder with the json wallets. Use solana-keygen to make keys.",
+        default_value = None
+    )]
+    miner_wallets: Option<String>,
+    #[arg(
+        long,
+        short = 'o',
+        value_name = "FILE",
+        help = "File to write the snapshot to. Defaults to snapshot-<timestamp>.csv",
+        default_value = None
+    )]
+    out: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct SnapshotDiffArgs {
+    #[arg(value_name = "BEFORE", help = "The older snapshot file.")]
+    a: String,
+    #[arg(value_name = "AFTER", help = "The newer snapshot file.")]
+    b: String,
+}
+
+#[derive(Parser, Debug)]
+struct BenchHashArgs {
+    #[arg(
+        long,
+        short = 'm',
+        value_name = "THREAD_COUNT",
+        help = "The highest thread count to benchmark. Every count from 1 up to this is run.",
+        default_value = "4"
+    )]
+    max_threads: u64,
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        help = "The number of solutions to find for each thread count.",
+        default_value = "5"
+    )]
+    samples: u64,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Hash for this long at each thread count instead of finding --samples solutions, and estimate the time to a solution at the current network difficulty.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "samples",
+        default_value = None
+    )]
+    duration_secs: Option<u64>,
+    #[arg(
+        long,
+        value_name = "ZERO_BITS",
+        help = "Leading zero bits of the dummy challenges. The default takes around a million hashes per solution.",
+        value_parser = clap::value_parser!(u32).range(1..=64),
+        default_value = "20"
+    )]
+    difficulty: u32,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also write the results as CSV to this file.",
+        default_value = None
+    )]
+    csv: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct HashArgs {
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The proof's current hash, as 32 bytes of hex."
+    )]
+    proof_hash: String,
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "The miner authority the hash is computed for."
+    )]
+    pubkey: String,
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The difficulty the hash has to meet, as 32 bytes of hex."
+    )]
+    difficulty: String,
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The number of threads to hash with",
+        default_value = "1"
+    )]
+    threads: u64,
+    #[arg(
+        long,
+        help = "Show a spinner with the live hashrate instead of scrolling hashes.",
+        default_value = "false"
+    )]
+    progress: bool,
+}
+
+#[derive(Parser, Debug)]
+struct VerifyTxArgs {
+    #[arg(value_name = "SIGNATURE", help = "Signature of the transaction to verify.")]
+    signature: String,
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The proof hash the transaction was mined against, as 32 bytes of hex. Without it only the difficulty is checked.",
+        default_value = None
+    )]
+    proof_hash: Option<String>,
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "The difficulty the hashes had to meet, as 32 bytes of hex. Defaults to the current treasury difficulty.",
+        default_value = None
+    )]
+    difficulty: Option<String>,
+}
+
 #[cfg(feature = "admin")]
 #[derive(Parser, Debug)]
 struct InitializeArgs {}
@@ -327,89 +1343,498 @@ struct UpdateAdminArgs {
 #[derive(Parser, Debug)]
 struct UpdateDifficultyArgs {}
 
+/// Settings in effect after resolving args, the solana config file and defaults, for
+/// `--dump-config`.
+#[derive(Serialize)]
+struct EffectiveConfig {
+    command: &'static str,
+    config_file: Option<String>,
+    rpc_urls: Vec<String>,
+    keypair: String,
+    commitment: String,
+    send_rpc_url: String,
+    send_commitment: String,
+    confirm_rpc_url: String,
+    confirm_commitment: String,
+    rpc_timeout_secs: u64,
+    rpc_max_connections: Option<usize>,
+    rpc_keep_alive_secs: Option<u64>,
+    sig_status_batch_size: u64,
+    sim_timeout_secs: u64,
+    confirm_timeout_secs: Option<u64>,
+    max_rpc_concurrency: Option<u64>,
+    min_sol_reserve: u64,
+    priority_fee: PriorityFee,
+    priority_fee_percentile: Option<u8>,
+    decimals: usize,
+    output: OutputFormat,
+    log_level: String,
+    token_program: TokenProgram,
+    share_stats: Option<String>,
+    share_stats_region: Option<String>,
+}
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    // Keep stdout for the json or csv document
+    let log_target = if args.output == OutputFormat::Text {
+        env_logger::Target::Stdout
+    } else {
+        env_logger::Target::Stderr
+    };
+    init_logger(args.log_level, log_target);
+    if args.output != OutputFormat::Text && !args.command.supports_output() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--output {} is not supported by {}, it only prints text",
+                    args.output.to_possible_value().unwrap().get_name(),
+                    args.command.name()
+                ),
+            )
+            .exit();
+    }
 
     // Load the config file from custom path, the default path, or use default config values
+    let mut config_file_used = None;
     let cli_config = if let Some(config_file) = &args.config_file {
+        config_file_used = Some(config_file.clone());
         solana_cli_config::Config::load(config_file).unwrap_or_else(|_| {
             eprintln!("error: Could not find config file `{}`", config_file);
             std::process::exit(1);
         })
     } else if let Some(config_file) = &*solana_cli_config::CONFIG_FILE {
-        solana_cli_config::Config::load(config_file).unwrap_or_default()
+        match solana_cli_config::Config::load(config_file) {
+            Ok(config) => {
+                config_file_used = Some(config_file.clone());
+                config
+            }
+            Err(_) => solana_cli_config::Config::default(),
+        }
     } else {
         solana_cli_config::Config::default()
     };
 
     // Initialize miner.
-    let cluster = args.rpc.unwrap_or(cli_config.json_rpc_url);
+    let cluster = if args.rpc.is_empty() {
+        vec![cli_config.json_rpc_url]
+    } else {
+        args.rpc
+    };
     let default_keypair = args.keypair.unwrap_or(cli_config.keypair_path);
-    let rpc_client = RpcClient::new_with_commitment(cluster.clone(), CommitmentConfig::confirmed());
+    let rpc_client = new_rpc_client(
+        &cluster,
+        CommitmentConfig::confirmed(),
+        args.rpc_max_connections,
+    );
+
+    let rpc_client_2 = Arc::new(new_rpc_client(
+        &cluster,
+        CommitmentConfig::confirmed(),
+        args.rpc_max_connections,
+    ));
+
+    // Split send/confirm endpoints, both default to --rpc
+    let send_rpc_client = Arc::new(new_rpc_client(
+        &args.send_rpc.map_or(cluster.clone(), |url| vec![url]),
+        CommitmentConfig { commitment: args.send_commitment },
+        args.rpc_max_connections,
+    ));
+    let confirm_rpc_client = Arc::new(new_rpc_client(
+        &args.confirm_rpc.map_or(cluster.clone(), |url| vec![url]),
+        CommitmentConfig { commitment: args.confirm_commitment },
+        args.rpc_max_connections,
+    ));
+
+    let priority_fee = match (args.priority_fee_total, args.priority_fee_percentile) {
+        (Some(total), _) => PriorityFee::Total(total),
+        // Nothing to estimate for a command that never sends a transaction
+        _ if !args.command.sends_transactions() => PriorityFee::PerCu(args.priority_fee),
+        _ if args.auto_priority_fee => {
+            let priority_fee = PriorityFee::Auto {
+                percentile: args.priority_fee_percentile.unwrap_or(75),
+                max: args.max_priority_fee,
+                fee: args.priority_fee,
+            };
+            refresh_priority_fee(&rpc_client_2, priority_fee).await
+        }
+        (None, Some(percentile)) => {
+            match get_priority_fee_percentile(&rpc_client_2, percentile).await {
+                Ok(fee) => PriorityFee::PerCu(args.max_priority_fee.map_or(fee, |max| fee.min(max))),
+                Err(e) => {
+                    warn!("{}, using --priority-fee {}", e, args.priority_fee);
+                    PriorityFee::PerCu(args.priority_fee)
+                }
+            }
+        }
+        (None, None) => PriorityFee::PerCu(args.priority_fee),
+    };
+
+    if args.dump_config {
+        let commitment = rpc_client_2.commitment();
+        let config = EffectiveConfig {
+            command: args.command.name(),
+            config_file: config_file_used,
+            rpc_urls: cluster,
+            keypair: default_keypair,
+            commitment: format!("{:?}", commitment.commitment).to_lowercase(),
+            send_rpc_url: send_rpc_client.url(),
+            send_commitment: format!("{:?}", args.send_commitment).to_lowercase(),
+            confirm_rpc_url: confirm_rpc_client.url(),
+            confirm_commitment: format!("{:?}", args.confirm_commitment).to_lowercase(),
+            rpc_timeout_secs: RPC_TIMEOUT.as_secs(),
+            rpc_max_connections: args.rpc_max_connections,
+            rpc_keep_alive_secs: args.rpc_max_connections.map(|_| RPC_KEEP_ALIVE.as_secs()),
+            sig_status_batch_size: args.sig_status_batch_size,
+            sim_timeout_secs: args.sim_timeout,
+            confirm_timeout_secs: args.confirm_timeout_secs,
+            max_rpc_concurrency: args.max_rpc_concurrency,
+            min_sol_reserve: args.min_sol_reserve,
+            priority_fee,
+            priority_fee_percentile: args.priority_fee_percentile,
+            decimals: args.decimals,
+            output: args.output,
+            log_level: args.log_level.to_string().to_lowercase(),
+            token_program: args.token_program,
+            share_stats: args.share_stats,
+            share_stats_region: args.share_stats_region,
+        };
+        println!("{}", serde_json::to_string_pretty(&config).unwrap());
+        return;
+    }
+
+    // Guard against a fat-fingered priority fee before anything is sent
+    let typical_fee = estimate_tx_fee(1, cu_limits::CU_LIMIT_CLAIM, priority_fee);
+    if args.command.sends_transactions() && typical_fee > args.warn_on_high_fee {
+        println!(
+            "\nWARNING: a typical transaction would cost {} SOL ({} lamports) in fees with {:?}, above the --warn-on-high-fee limit of {} lamports.\n",
+            lamports_to_sol(typical_fee),
+            typical_fee,
+            priority_fee,
+            args.warn_on_high_fee
+        );
+        if !args.yes && !confirm("Continue anyway?") {
+            println!("Aborted.");
+            std::process::exit(1);
+        }
+    }
 
-    let rpc_client_2 = Arc::new(RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed()));
+    let rpc_limit = args
+        .max_rpc_concurrency
+        .map(|max| Arc::new(Semaphore::new(max as usize)));
+    let confirm_service = ConfirmService::start(
+        confirm_rpc_client,
+        args.sig_status_batch_size as usize,
+        rpc_limit.clone(),
+    );
+    let tx_settings = TxSettings {
+        rpc_limit,
+        sim_timeout: Duration::from_secs(args.sim_timeout),
+        confirm_timeout: args.confirm_timeout_secs.map(Duration::from_secs),
+        min_sol_reserve: args.min_sol_reserve,
+        ..TxSettings::new(send_rpc_client, confirm_service)
+    };
+    if let Some(webhook) = args.webhook {
+        Webhook::start(webhook, args.webhook_events, args.command.name());
+    }
 
     let miner = Arc::new(Miner::new(
         Arc::new(rpc_client),
-        args.priority_fee,
+        tx_settings.clone(),
+        priority_fee,
         Some(default_keypair),
+        args.decimals,
+        args.token_program,
     ));
-    let priority_fee = args.priority_fee;
+    let decimals = args.decimals;
+    let output = args.output;
+    let token_program = args.token_program;
+    if args.command.uses_token_accounts() {
+        if let Err(e) = check_token_program(&rpc_client_2, token_program).await {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    let share_stats = args.share_stats;
+    let share_stats_region = args.share_stats_region;
 
     // Execute user command.
+    let mut summary = Summary::new(args.command.name());
+    summary.to_stderr = output != OutputFormat::Text;
+    // A command that stopped early still reports its summary before exiting with the error
+    let mut failure = None;
     match args.command {
         Commands::Balance(args) => {
-            miner.balance(args.address).await;
+            miner.balance(args.address, output, &mut summary).await;
         }
         Commands::Busses(_) => {
-            miner.busses().await;
+            miner.busses(output, &mut summary).await;
         }
         Commands::Rewards(args) => {
-            miner.rewards(args.address).await;
+            miner.rewards(args.address, output, &mut summary).await;
         }
         Commands::Treasury(_) => {
-            miner.treasury().await;
+            miner.treasury(output, &mut summary).await;
         }
         Commands::Mine(args) => {
-            miner.mine(args.threads, args.send_interval).await;
+            miner.mine(args.threads.resolve(), args.send_interval, args.progress, args.max_rounds, &mut summary).await;
         }
         Commands::MineV2(args) => {
-            MinerV2::mine(rpc_client_2.clone(), args.threads, args.send_interval, args.batch_size, args.miner_wallets, priority_fee,args.sim_attempts, args.fee_payer).await;
+            let auto_topup = match (args.auto_topup, args.funding_wallet, args.max_topup_total) {
+                (Some(target), Some(funding_wallet), Some(max_total)) => {
+                    match read_keypair_file(&funding_wallet) {
+                        Ok(funding) => Some(AutoTopup {
+                            funding,
+                            target,
+                            max_total,
+                            sent: 0,
+                        }),
+                        Err(e) => summary.fail(&format!(
+                            "failed to read funding wallet {}: {}",
+                            funding_wallet, e
+                        )),
+                    }
+                }
+                _ => None,
+            };
+            let fee_payer = match (args.fee_payer, args.fee_payer_signer, args.fee_payer_pubkey) {
+                (Some(path), _, _) => match read_keypair_file(&path) {
+                    Ok(keypair) => Some(FeePayer::new(keypair)),
+                    Err(e) => summary.fail(&format!("failed to read fee payer {}: {}", path, e)),
+                },
+                (None, Some(command), Some(pubkey)) => match Pubkey::from_str(&pubkey) {
+                    Ok(pubkey) => Some(FeePayer::new(ExternalSigner::new(pubkey, command))),
+                    Err(e) => {
+                        summary.fail(&format!("invalid --fee-payer-pubkey {}: {}", pubkey, e))
+                    }
+                },
+                _ => None,
+            };
+            let jito = args
+                .jito
+                .then(|| Jito::new(args.jito_url, args.jito_tip_lamports));
+            if let Some(port) = args.metrics_port {
+                if let Err(e) = metrics::serve(args.metrics_bind, port) {
+                    summary.fail(&e);
+                }
+            }
+            let wallet_source = WalletSource::from_args(
+                args.miner_wallets,
+                args.mnemonic_file,
+                args.derivation_count,
+            );
+            let report = args
+                .report
+                .map(|path| compress::output_path(path, args.compress));
+            let stats_file = args
+                .stats_file
+                .map(|path| compress::output_path(path, args.compress));
+            let csv_file = args
+                .csv_file
+                .map(|path| compress::output_path(path, args.compress));
+            let config = MineV2Config {
+                threads: args.threads.resolve(),
+                send_interval: args.send_interval,
+                batch_size: args.batch_size,
+                batch_timeout_secs: args.batch_timeout_secs,
+                wallet_source,
+                priority_fee,
+                sim_attempts: args.sim_attempts,
+                simulate_only: args.simulate_only,
+                jito,
+                fee_payer,
+                decimals,
+                fail_fast: args.fail_fast,
+                progress: args.progress,
+                max_fee_payer_spend: args.max_fee_payer_spend,
+                rounds: if args.once { Some(1) } else { args.rounds },
+                token_program,
+                report,
+                retry_failed: args.retry_failed,
+                min_ore_per_sol: args.min_ore_per_sol,
+                queue_file: args.queue_file,
+                stats_file,
+                csv_file,
+                max_register_attempts: args.max_register_attempts,
+                auto_topup,
+                threads_per_wallet: args.threads_per_wallet,
+                overcommit: args.overcommit,
+                bus_strategy: args.bus_strategy,
+                webhook_failures: args.webhook_failures,
+                claim_threshold: args.claim_threshold_ore,
+                claim_beneficiary: args.claim_beneficiary,
+                claim_check_interval: args.claim_check_interval,
+                heartbeat_interval: args.heartbeat_interval,
+                stall_timeout: args.stall_timeout,
+                exit_on_stall: args.exit_on_stall,
+                tx_settings,
+            };
+            failure = MinerV2::mine(rpc_client_2.clone(), config, &mut summary)
+                .await
+                .err();
         }
         Commands::Claim(args) => {
-            miner.claim(args.beneficiary, args.amount).await;
+            miner.claim(args.beneficiary, args.beneficiary_owner, args.amount, args.wait_for_epoch, args.ledger, &mut summary).await;
         }
         Commands::ClaimV2(args) => {
-            MinerV2::claim(rpc_client_2.clone(), args.send_interval, args.miner_wallets, args.beneficiary, priority_fee).await;
+            let wallet_source = WalletSource::from_args(
+                args.miner_wallets,
+                args.mnemonic_file,
+                args.derivation_count,
+            );
+            let config = ClaimConfig {
+                send_interval: args.send_interval,
+                wallet_source,
+                beneficiary: args.beneficiary,
+                beneficiary_owner: args.beneficiary_owner,
+                transfer_to: args.claim_to_self_then_transfer,
+                amount: args.amount,
+                min_claim_amount: args.min_claim_amount,
+                priority_fee,
+                fail_fast: args.fail_fast,
+                wait_for_epoch: args.wait_for_epoch,
+                token_program,
+                decimals,
+                ledger: args.ledger,
+                aggregate: args.aggregate,
+                tx_settings,
+            };
+            MinerV2::claim(rpc_client_2.clone(), config, &mut summary).await;
         }
         Commands::Wallets(args) => {
-            MinerV2::wallets(rpc_client_2.clone(), args.miner_wallets).await;
+            let wallet_source = WalletSource::from_args(args.miner_wallets, args.mnemonic_file, args.derivation_count);
+            MinerV2::wallets(rpc_client_2.clone(), wallet_source, decimals, args.include_zero, token_program, output, &mut summary).await;
+        }
+        Commands::Pubkeys(args) => {
+            MinerV2::pubkeys(args.miner_wallets, output, &mut summary).await;
         }
         Commands::SendSol(args) => {
-            MinerV2::send_sol(rpc_client_2.clone(), args.sender_wallet, args.receiving_wallets, args.send_interval, args.amount).await;
+            MinerV2::send_sol(rpc_client_2.clone(), tx_settings, args.sender_wallet, args.receiving_wallets, args.send_interval, args.amount, args.top_up, args.only_if_below, args.equal_split, args.concurrency, &mut summary).await;
+        }
+        Commands::CollectSol(args) => {
+            MinerV2::collect_sol(rpc_client_2.clone(), tx_settings, args.destination, args.miner_wallets, args.send_interval, &mut summary).await;
+        }
+        Commands::TuneBatch(args) => {
+            MinerV2::tune_batch(rpc_client_2.clone(), tx_settings, args.miner_wallets, args.threads.resolve(), args.send_interval, priority_fee, args.rounds, args.real, &mut summary).await;
+        }
+        Commands::Snapshot(args) => {
+            MinerV2::snapshot(rpc_client_2.clone(), args.miner_wallets, args.out, token_program, &mut summary).await;
+        }
+        Commands::SnapshotDiff(args) => {
+            MinerV2::snapshot_diff(args.a, args.b, &mut summary).await;
+        }
+        Commands::BenchHash(args) => {
+            MinerV2::bench_hash(&rpc_client_2, args.max_threads, args.samples, args.duration_secs, args.difficulty, args.csv, &mut summary).await;
+        }
+        Commands::Hash(args) => {
+            miner.hash(args.proof_hash, args.pubkey, args.difficulty, args.threads, args.progress, &mut summary).await;
+        }
+        Commands::VerifyTx(args) => {
+            miner.verify_tx(args.signature, args.proof_hash, args.difficulty, &mut summary).await;
         }
         #[cfg(feature = "admin")]
         Commands::Initialize(_) => {
             miner.initialize().await;
+            summary.success += 1;
         }
         #[cfg(feature = "admin")]
         Commands::UpdateAdmin(args) => {
             miner.update_admin(args.new_admin).await;
+            summary.success += 1;
         }
         #[cfg(feature = "admin")]
         Commands::UpdateDifficulty(_) => {
             miner.update_difficulty().await;
+            summary.success += 1;
         }
     }
+    Webhook::finish(&summary).await;
+
+    if let Some(endpoint) = share_stats {
+        let stats = SharedStats::from_summary(
+            &summary,
+            priority_fee.cu_price(cu_limits::CU_LIMIT_MINE),
+            share_stats_region,
+        );
+        if let Err(e) = stats.post(&endpoint).await {
+            warn!("Failed to share stats: {}", e);
+        }
+    }
+
+    if let Some(failure) = failure {
+        summary.fail(&failure);
+    }
+    summary.print();
+
+    if summary.nothing_to_do {
+        std::process::exit(NOTHING_TO_DO_EXIT_CODE);
+    }
+}
+
+fn parse_commitment(s: &str) -> Result<CommitmentLevel, String> {
+    match s {
+        "processed" => Ok(CommitmentLevel::Processed),
+        "confirmed" => Ok(CommitmentLevel::Confirmed),
+        "finalized" => Ok(CommitmentLevel::Finalized),
+        _ => Err("expected processed, confirmed or finalized".to_string()),
+    }
+}
+
+fn parse_log_level(s: &str) -> Result<LevelFilter, String> {
+    match s {
+        "error" => Ok(LevelFilter::Error),
+        "warn" => Ok(LevelFilter::Warn),
+        "info" => Ok(LevelFilter::Info),
+        "debug" => Ok(LevelFilter::Debug),
+        "trace" => Ok(LevelFilter::Trace),
+        _ => Err("expected error, warn, info, debug or trace".to_string()),
+    }
+}
+
+/// Logs to stdout at `level`. Info and above print as plain lines, debug and trace lines are
+/// prefixed with their level. Dependencies only log warnings, their debug output would drown
+/// ours.
+fn init_logger(level: LevelFilter, target: env_logger::Target) {
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module(env!("CARGO_CRATE_NAME"), level)
+        .target(target)
+        .format(|buf, record| match record.level() {
+            Level::Error | Level::Warn | Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level, record.args()),
+        })
+        .init();
+}
+
+/// Asks a yes/no question on stdin, anything but y/yes (including no terminal) is a no.
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
 }
 
 impl Miner {
-    pub fn new(rpc_client: Arc<RpcClient>, priority_fee: u64, keypair_filepath: Option<String>) -> Self {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        tx_settings: TxSettings,
+        priority_fee: PriorityFee,
+        keypair_filepath: Option<String>,
+        decimals: usize,
+        token_program: TokenProgram,
+    ) -> Self {
         Self {
             rpc_client,
+            tx_settings,
             keypair_filepath,
             priority_fee,
+            decimals,
+            token_program,
         }
     }
 
@@ -420,3 +1845,13 @@ impl Miner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::{info, warn};
+
+static HASHES: AtomicU64 = AtomicU64::new(0);
+static CONFIRMED_TXS: AtomicU64 = AtomicU64::new(0);
+static FAILED_TXS: AtomicU64 = AtomicU64::new(0);
+// Summed over confirmed transactions, for the averages
+static HASH_TIME_SECS: AtomicU64 = AtomicU64::new(0);
+static TX_TIME_SECS: AtomicU64 = AtomicU64::new(0);
+static ACTIVE_WALLETS: AtomicU64 = AtomicU64::new(0);
+
+pub fn add_hashes(hashes: u64) {
+    HASHES.fetch_add(hashes, Ordering::Relaxed);
+}
+
+pub fn record_confirmed(hash_time: u64, tx_time: u64) {
+    CONFIRMED_TXS.fetch_add(1, Ordering::Relaxed);
+    HASH_TIME_SECS.fetch_add(hash_time, Ordering::Relaxed);
+    TX_TIME_SECS.fetch_add(tx_time, Ordering::Relaxed);
+}
+
+pub fn record_failed() {
+    FAILED_TXS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn set_active_wallets(wallets: usize) {
+    ACTIVE_WALLETS.store(wallets as u64, Ordering::Relaxed);
+}
+
+/// Serves the mine-v2 metrics in the Prometheus text format on `--metrics-bind` and
+/// `--metrics-port`, from a thread of its own so scrapes never wait on the miner.
+pub fn serve(bind: IpAddr, port: u16) -> Result<(), String> {
+    let addr = SocketAddr::new(bind, port);
+    let server = tiny_http::Server::http(addr)
+        .map_err(|e| format!("Failed to start metrics server on {}: {}", addr, e))?;
+    info!("Serving metrics on http://{}/metrics", addr);
+    std::thread::spawn(move || {
+        let content_type =
+            tiny_http::Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+        for request in server.incoming_requests() {
+            let response =
+                tiny_http::Response::from_string(render()).with_header(content_type.clone());
+            if let Err(e) = request.respond(response) {
+                warn!("Failed to answer metrics request: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn render() -> String {
+    let confirmed = CONFIRMED_TXS.load(Ordering::Relaxed);
+    let average = |total: &AtomicU64| {
+        if confirmed == 0 {
+            0.0
+        } else {
+            total.load(Ordering::Relaxed) as f64 / confirmed as f64
+        }
+    };
+    let metrics = [
+        (
+            "ore_hashes_total",
+            "counter",
+            "Hashes attempted.",
+            HASHES.load(Ordering::Relaxed) as f64,
+        ),
+        (
+            "ore_transactions_confirmed_total",
+            "counter",
+            "Mine transactions that landed.",
+            confirmed as f64,
+        ),
+        (
+            "ore_transactions_failed_total",
+            "counter",
+            "Mine transactions that failed or were dropped.",
+            FAILED_TXS.load(Ordering::Relaxed) as f64,
+        ),
+        (
+            "ore_hash_time_seconds_average",
+            "gauge",
+            "Average seconds spent hashing a batch, over confirmed transactions.",
+            average(&HASH_TIME_SECS),
+        ),
+        (
+            "ore_tx_time_seconds_average",
+            "gauge",
+            "Average seconds from sending a transaction until it landed.",
+            average(&TX_TIME_SECS),
+        ),
+        (
+            "ore_active_wallets",
+            "gauge",
+            "Wallets still being mined.",
+            ACTIVE_WALLETS.load(Ordering::Relaxed) as f64,
+        ),
+    ];
+    let mut body = String::new();
+    for (name, kind, help, value) in metrics {
+        body.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+            name, help, name, kind, name, value
+        ));
+    }
+    body
+}
@@ -1,9 +1,13 @@
 use std::{
     io::{stdout, Write},
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
-use ore::{self, state::Bus, BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION};
+use ore::{self, BUS_ADDRESSES, BUS_COUNT};
 use rand::Rng;
 use solana_program::{keccak::HASH_BYTES, program_memory::sol_memcmp, pubkey::Pubkey};
 use solana_sdk::{
@@ -14,7 +18,13 @@ use solana_sdk::{
 
 use crate::{
     cu_limits::{CU_LIMIT_MINE, CU_LIMIT_RESET},
-    utils::{get_clock_account, get_proof, get_treasury},
+    miner_v2::MinerV2,
+    nonce_hasher::{NonceHasher, NONCE_BATCH},
+    summary::Summary,
+    utils::{
+        difficulty_zero_bits, epoch_end, estimate_tx_fee, format_ore, get_bus, get_clock,
+        get_proof_v2, get_treasury, hash_rate, nonce_range, spawn_hash_progress, BusAccount,
+    },
     Miner,
 };
 
@@ -22,62 +32,105 @@ use crate::{
 const RESET_ODDS: u64 = 20;
 
 impl Miner {
-    pub async fn mine(&self, threads: u64, send_interval: u64) {
+    pub async fn mine(
+        &self,
+        threads: u64,
+        send_interval: u64,
+        progress: bool,
+        max_rounds: Option<u64>,
+        summary: &mut Summary,
+    ) {
         // Register, if needed.
         let signer = self.signer();
         self.register().await;
         let mut rng = rand::thread_rng();
         let mut tx_time_keeper: Vec<u64> = vec![];
+        // Successful submissions, for --max-rounds
+        let mut round = 0;
 
         // Start mining loop
         loop {
             println!("TX TIMES: \n:{:?}", tx_time_keeper);
             // Fetch account state
             let balance = self.get_ore_display_balance().await;
-            let treasury = get_treasury(&self.rpc_client).await;
-            let proof = get_proof(&self.rpc_client, signer.pubkey()).await;
-            let rewards =
-                (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-            let reward_rate =
-                (treasury.reward_rate as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+            let (treasury, proof) = match (
+                get_treasury(&self.rpc_client).await,
+                get_proof_v2(&self.rpc_client, signer.pubkey()).await,
+            ) {
+                (Ok(treasury), Ok(proof)) => (treasury, proof),
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("{}, retrying...", e);
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                    continue;
+                }
+            };
+            let rewards = format_ore(proof.claimable_rewards, self.decimals);
+            let reward_rate = format_ore(treasury.reward_rate, self.decimals);
             println!("Balance: {} ORE", balance);
             println!("Claimable: {} ORE", rewards);
             println!("Reward rate: {} ORE", reward_rate);
 
             // Escape sequence that clears the screen and the scrollback buffer
             println!("\nMining for a valid hash...");
-            let (next_hash, nonce) =
-                self.find_next_hash_par(proof.hash.into(), treasury.difficulty.into(), threads);
+            let hash_timer = Instant::now();
+            let Some((next_hash, nonce)) =
+                self.find_next_hash_par(
+                    proof.hash,
+                    treasury.difficulty,
+                    threads,
+                    progress,
+                )
+            else {
+                println!("\nNo solution found, re-hashing...");
+                continue;
+            };
+            let hash_time_secs = hash_timer.elapsed().as_secs();
 
             // Submit mine tx.
             // Use busses randomly so on each epoch, transactions don't pile on the same busses
             println!("\n\nSubmitting hash for validation...");
             'submit: loop {
                 // Double check we're submitting for the right challenge
-                let proof_ = get_proof(&self.rpc_client, signer.pubkey()).await;
+                let proof_ = match get_proof_v2(&self.rpc_client, signer.pubkey()).await {
+                    Ok(proof) => proof,
+                    Err(e) => {
+                        println!("{}, retrying...", e);
+                        std::thread::sleep(std::time::Duration::from_secs(1));
+                        continue 'submit;
+                    }
+                };
                 if !self.validate_hash(
                     next_hash,
-                    proof_.hash.into(),
+                    proof_.hash,
                     signer.pubkey(),
                     nonce,
-                    treasury.difficulty.into(),
+                    treasury.difficulty,
                 ) {
                     println!("Hash already validated! An earlier transaction must have landed.");
                     break 'submit;
                 }
 
                 // Reset epoch, if needed
-                let treasury = get_treasury(&self.rpc_client).await;
-                let clock = get_clock_account(&self.rpc_client).await;
-                let threshold = treasury.last_reset_at.saturating_add(EPOCH_DURATION);
-                if clock.unix_timestamp.ge(&threshold) {
+                let (treasury, clock) = match (
+                    get_treasury(&self.rpc_client).await,
+                    get_clock(&self.rpc_client).await,
+                ) {
+                    (Ok(treasury), Ok(clock)) => (treasury, clock),
+                    (Err(e), _) | (_, Err(e)) => {
+                        println!("{}, retrying...", e);
+                        std::thread::sleep(std::time::Duration::from_secs(1));
+                        continue 'submit;
+                    }
+                };
+                if clock.unix_timestamp.ge(&epoch_end(&treasury)) {
                     // There are a lot of miners right now, so randomly select into submitting tx
                     if rng.gen_range(0..RESET_ODDS).eq(&0) {
                         println!("Sending epoch reset transaction...");
                         let cu_limit_ix =
                             ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_RESET);
-                        let cu_price_ix =
-                            ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
+                        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                            self.priority_fee.cu_price(CU_LIMIT_RESET),
+                        );
                         let reset_ix = ore::instruction::reset(signer.pubkey());
                         self.send_and_confirm(&[cu_limit_ix, cu_price_ix, reset_ix], false, true)
                             .await
@@ -87,14 +140,15 @@ impl Miner {
 
                 // Submit request.
                 let bus = self.find_bus_id(treasury.reward_rate).await;
-                let bus_rewards = (bus.rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+                let bus_rewards = format_ore(bus.rewards, self.decimals);
                 println!("Sending on bus {} ({} ORE)", bus.id, bus_rewards);
                 let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_MINE);
-                let cu_price_ix =
-                    ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
+                let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                    self.priority_fee.cu_price(CU_LIMIT_MINE),
+                );
                 let ix_mine = ore::instruction::mine(
                     signer.pubkey(),
-                    BUS_ADDRESSES[bus.id as usize],
+                    BUS_ADDRESSES[bus.id],
                     next_hash.into(),
                     nonce,
                 );
@@ -106,21 +160,45 @@ impl Miner {
                         println!("Success: {}", sig);
                         println!("Took: {} seconds", tx_time_secs);
                         tx_time_keeper.push(tx_time_secs);
+                        summary.success += 1;
+                        summary.ore_mined += treasury.reward_rate;
+                        summary.lamports_spent +=
+                            estimate_tx_fee(1, CU_LIMIT_MINE, self.priority_fee);
+                        round += 1;
+                        let claimable = match get_proof_v2(&self.rpc_client, signer.pubkey()).await
+                        {
+                            Ok(proof) => format_ore(proof.claimable_rewards, self.decimals),
+                            Err(_) => "?".to_string(),
+                        };
+                        println!(
+                            "Round {}: hashed in {} seconds, tx took {} seconds. Balance: {} ORE, claimable: {} ORE",
+                            round,
+                            hash_time_secs,
+                            tx_time_secs,
+                            self.get_ore_display_balance().await,
+                            claimable
+                        );
                         break;
                     }
                     Err(_err) => {
-                        // TODO
+                        summary.failed += 1;
                     }
                 }
             }
+
+            if max_rounds == Some(round) {
+                println!("Finished {} rounds.", round);
+                println!("TX TIMES: \n:{:?}", tx_time_keeper);
+                return;
+            }
         }
     }
 
-    async fn find_bus_id(&self, reward_rate: u64) -> Bus {
+    async fn find_bus_id(&self, reward_rate: u64) -> BusAccount {
         let mut rng = rand::thread_rng();
         loop {
             let bus_id = rng.gen_range(0..BUS_COUNT);
-            if let Ok(bus) = self.get_bus(bus_id).await {
+            if let Ok(bus) = get_bus(&self.rpc_client, bus_id).await {
                 if bus.rewards.gt(&reward_rate.saturating_mul(20)) {
                     return bus;
                 }
@@ -153,7 +231,17 @@ impl Miner {
         hash: KeccakHash,
         difficulty: KeccakHash,
         threads: u64,
-    ) -> (KeccakHash, u64) {
+        progress: bool,
+    ) -> Option<(KeccakHash, u64)> {
+        println!(
+            "Difficulty: {} leading zero bits",
+            difficulty_zero_bits(difficulty.as_ref())
+        );
+        let hashes = Arc::new(AtomicU64::new(0));
+        let started_at = Instant::now();
+        let done = Arc::new(AtomicBool::new(false));
+        let progress_handle =
+            progress.then(|| spawn_hash_progress(hashes.clone(), done.clone()));
         let found_solution = Arc::new(AtomicBool::new(false));
         let solution = Arc::new(Mutex::<(KeccakHash, u64)>::new((
             KeccakHash::new_from_array([0; 32]),
@@ -166,38 +254,54 @@ impl Miner {
                 std::thread::spawn({
                     let found_solution = found_solution.clone();
                     let solution = solution.clone();
+                    let hashes = hashes.clone();
                     let mut stdout = stdout();
                     move || {
-                        let n = u64::MAX.saturating_div(threads).saturating_mul(i);
+                        let (n, end) = nonce_range(i, threads);
+                        let hasher = NonceHasher::new(hash, pubkey);
+                        let mut batch = [KeccakHash::default(); NONCE_BATCH];
                         let mut next_hash: KeccakHash;
                         let mut nonce: u64 = n;
                         loop {
-                            next_hash = hashv(&[
-                                hash.to_bytes().as_slice(),
-                                pubkey.to_bytes().as_slice(),
-                                nonce.to_le_bytes().as_slice(),
-                            ]);
+                            let batch_index = ((nonce - n) % NONCE_BATCH as u64) as usize;
+                            if batch_index == 0 {
+                                batch = hasher.hash_batch(nonce);
+                            }
+                            next_hash = batch[batch_index];
                             if nonce % 10_000 == 0 {
                                 if found_solution.load(std::sync::atomic::Ordering::Relaxed) {
                                     return;
                                 }
-                                if n == 0 {
+                                hashes.fetch_add(10_000, std::sync::atomic::Ordering::Relaxed);
+                                if n == 0 && !progress {
+                                    let rate = hash_rate(&hashes, started_at);
                                     stdout
                                         .write_all(
-                                            format!("\r{}", next_hash.to_string()).as_bytes(),
+                                            format!("\r{} {:.0} H/s", next_hash, rate).as_bytes(),
                                         )
                                         .ok();
                                 }
                             }
                             if next_hash.le(&difficulty) {
-                                stdout
-                                    .write_all(format!("\r{}", next_hash.to_string()).as_bytes())
-                                    .ok();
+                                if !progress {
+                                    stdout
+                                        .write_all(
+                                            format!("\r{}", next_hash.to_string()).as_bytes(),
+                                        )
+                                        .ok();
+                                }
                                 found_solution.store(true, std::sync::atomic::Ordering::Relaxed);
+                                hashes.fetch_add(
+                                    nonce % 10_000,
+                                    std::sync::atomic::Ordering::Relaxed,
+                                );
                                 let mut w_solution = solution.lock().expect("failed to lock mutex");
                                 *w_solution = (next_hash, nonce);
                                 return;
                             }
+                            if nonce == end {
+                                return;
+                            }
                             nonce += 1;
                         }
                     }
@@ -208,9 +312,24 @@ impl Miner {
         for thread_handle in thread_handles {
             thread_handle.join().unwrap();
         }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        if let Some(progress_handle) = progress_handle {
+            progress_handle.join().unwrap();
+        }
+        println!(
+            "\nSearched about {} hashes in {:.1} seconds ({:.0} H/s)",
+            hashes.load(std::sync::atomic::Ordering::Relaxed),
+            started_at.elapsed().as_secs_f64(),
+            hash_rate(&hashes, started_at)
+        );
+
+        // Every thread exited without finding a solution, so the placeholder is not valid
+        if !found_solution.load(std::sync::atomic::Ordering::Relaxed) {
+            return None;
+        }
 
         let r_solution = solution.lock().expect("Failed to get lock");
-        *r_solution
+        Some(*r_solution)
     }
 
     pub fn validate_hash(
@@ -221,40 +340,43 @@ impl Miner {
         nonce: u64,
         difficulty: KeccakHash,
     ) -> bool {
-        // Validate hash correctness
-        let hash_ = hashv(&[
-            current_hash.as_ref(),
-            signer.as_ref(),
-            nonce.to_le_bytes().as_slice(),
-        ]);
-        if sol_memcmp(hash.as_ref(), hash_.as_ref(), HASH_BYTES) != 0 {
-            return false;
-        }
-
-        // Validate hash difficulty
-        if hash.gt(&difficulty) {
-            return false;
-        }
-
-        true
+        validate_hash(hash, current_hash, signer, nonce, difficulty)
     }
 
     pub async fn get_ore_display_balance(&self) -> String {
-        let client = self.rpc_client.clone();
         let signer = self.signer();
-        let token_account_address = spl_associated_token_account::get_associated_token_address(
-            &signer.pubkey(),
-            &ore::MINT_ADDRESS,
-        );
-        match client.get_token_account(&token_account_address).await {
-            Ok(token_account) => {
-                if let Some(token_account) = token_account {
-                    token_account.token_amount.ui_amount_string
-                } else {
-                    "0.00".to_string()
-                }
-            }
-            Err(_) => "0.00".to_string(),
-        }
+        MinerV2::get_ore_display_balance(
+            &self.rpc_client,
+            signer.pubkey(),
+            self.decimals,
+            self.token_program,
+        )
+        .await
     }
 }
+
+/// Whether `hash` is the hash of `nonce` for the challenge and meets `difficulty`.
+pub fn validate_hash(
+    hash: KeccakHash,
+    current_hash: KeccakHash,
+    signer: Pubkey,
+    nonce: u64,
+    difficulty: KeccakHash,
+) -> bool {
+    // Validate hash correctness
+    let hash_ = hashv(&[
+        current_hash.as_ref(),
+        signer.as_ref(),
+        nonce.to_le_bytes().as_slice(),
+    ]);
+    if sol_memcmp(hash.as_ref(), hash_.as_ref(), HASH_BYTES) != 0 {
+        return false;
+    }
+
+    // Validate hash difficulty
+    if hash.gt(&difficulty) {
+        return false;
+    }
+
+    true
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::compress::AppendFile;
+
+/// One wallet's transaction result, written as a row of the `--csv` file.
+#[derive(Serialize)]
+pub struct CsvRecord {
+    pub wallet_pubkey: String,
+    pub bus_id: usize,
+    pub nonce: u64,
+    pub hash_time_secs: u64,
+    /// 0 if the transaction didn't land.
+    pub tx_time_secs: u64,
+    pub signature: String,
+    pub landed: bool,
+    pub timestamp_unix: i64,
+}
+
+/// Append-only mine-v2 results for spreadsheets, the header is written when the file is new.
+/// A `.gz` path is written gzip compressed.
+pub struct MineCsv {
+    path: String,
+    writer: csv::Writer<AppendFile>,
+}
+
+impl MineCsv {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let is_new = !Path::new(path).exists();
+        let file = AppendFile::open(path)
+            .map_err(|e| format!("Failed to open csv file {}: {}", path, e))?;
+        let writer = csv::WriterBuilder::new()
+            .has_headers(is_new)
+            .from_writer(file);
+        Ok(Self {
+            path: path.to_string(),
+            writer,
+        })
+    }
+
+    pub fn append(&mut self, record: &CsvRecord) -> Result<(), String> {
+        self.writer
+            .serialize(record)
+            .and_then(|_| self.writer.flush().map_err(csv::Error::from))
+            .map_err(|e| format!("Failed to write to csv file {}: {}", self.path, e))
+    }
+}
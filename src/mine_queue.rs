@@ -0,0 +1,110 @@
+use std::{collections::BTreeSet, path::Path, sync::Mutex};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+/// Where each wallet is in the mine-v2 pipeline, persisted to the `--queue-file` so a
+/// restarted miner picks up roughly where it left off. Only pubkeys are written, never keys.
+#[derive(Default, Deserialize, Serialize)]
+struct QueueState {
+    updated_at: String,
+    /// Waiting to be hashed, in queue order.
+    queued: Vec<String>,
+    /// In a transaction that was built but hasn't landed or failed yet.
+    in_flight: BTreeSet<String>,
+    /// Landed since the current round started, cleared once every wallet has landed.
+    completed: BTreeSet<String>,
+}
+
+pub struct MineQueue {
+    path: String,
+    wallets: usize,
+    state: Mutex<QueueState>,
+}
+
+impl MineQueue {
+    /// Loads the queue file, or starts an empty queue if it doesn't exist yet.
+    pub fn open(path: &str, wallets: usize) -> Result<Self, String> {
+        let state = if Path::new(path).exists() {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read queue file {}: {}", path, e))?;
+            let state: QueueState = serde_json::from_str(&contents)
+                .map_err(|e| format!("Invalid queue file {}: {}", path, e))?;
+            info!(
+                "Resuming queue from {}: {} in flight, {} queued, {} completed this round",
+                path,
+                state.in_flight.len(),
+                state.queued.len(),
+                state.completed.len()
+            );
+            state
+        } else {
+            QueueState::default()
+        };
+        Ok(Self {
+            path: path.to_string(),
+            wallets,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Sort key that resumes the saved order: wallets that were in flight first, then the
+    /// queue in order, then wallets the file doesn't know, then those already done this round.
+    pub fn resume_rank(&self, pubkey: &str) -> (u8, usize) {
+        let state = self.state.lock().unwrap();
+        if state.in_flight.contains(pubkey) {
+            (0, 0)
+        } else if state.completed.contains(pubkey) {
+            (3, 0)
+        } else if let Some(i) = state.queued.iter().position(|queued| queued == pubkey) {
+            (1, i)
+        } else {
+            (2, 0)
+        }
+    }
+
+    pub fn queued(&self, pubkey: String) {
+        self.update(|state| {
+            state.in_flight.remove(&pubkey);
+            if !state.queued.contains(&pubkey) {
+                state.queued.push(pubkey);
+            }
+        });
+    }
+
+    pub fn in_flight(&self, pubkeys: Vec<String>) {
+        self.update(|state| {
+            state.queued.retain(|queued| !pubkeys.contains(queued));
+            state.in_flight.extend(pubkeys);
+        });
+    }
+
+    pub fn landed(&self, pubkey: String) {
+        let wallets = self.wallets;
+        self.update(|state| {
+            state.in_flight.remove(&pubkey);
+            state.completed.insert(pubkey);
+            if state.completed.len() >= wallets {
+                state.completed.clear();
+            }
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut QueueState)) {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state);
+        state.updated_at = chrono::Utc::now().to_rfc3339();
+        // Written to a temporary file first so a restart never reads a truncated queue
+        let tmp = format!("{}.tmp", self.path);
+        let result = serde_json::to_string_pretty(&*state)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                std::fs::write(&tmp, contents)
+                    .and_then(|_| std::fs::rename(&tmp, &self.path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to write queue file {}: {}", self.path, e);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compress;
+
+/// Per-wallet results of a mine-v2 session, rewritten to the `--report` file after every
+/// transaction result. A `.gz` path is read and written gzip compressed.
+#[derive(Default, Deserialize, Serialize)]
+pub struct MineReport {
+    pub updated_at: String,
+    pub wallets: BTreeMap<String, WalletReport>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct WalletReport {
+    pub landed: u64,
+    pub failed: u64,
+    /// Error of the wallet's latest transaction, None if it landed.
+    pub last_error: Option<String>,
+}
+
+impl MineReport {
+    pub fn read(path: &str) -> Result<Self, String> {
+        let contents = compress::read_to_string(path)
+            .map_err(|e| format!("Failed to read report {}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid report {}: {}", path, e))
+    }
+
+    pub fn record(&mut self, pubkey: String, error: Option<String>) {
+        let wallet = self.wallets.entry(pubkey).or_default();
+        match error {
+            Some(error) => {
+                wallet.failed += 1;
+                wallet.last_error = Some(error);
+            }
+            None => {
+                wallet.landed += 1;
+                wallet.last_error = None;
+            }
+        }
+    }
+
+    /// Writes to a temporary file first so an interrupted write never leaves a truncated
+    /// report behind.
+    pub fn write(&mut self, path: &str) -> Result<(), String> {
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = format!("{}.tmp", path);
+        compress::encode(path, contents.as_bytes())
+            .and_then(|contents| std::fs::write(&tmp, contents))
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| format!("Failed to write report {}: {}", path, e))
+    }
+
+    /// Wallets whose latest transaction failed.
+    pub fn failed_wallets(&self) -> HashSet<String> {
+        self.wallets
+            .iter()
+            .filter(|(_, wallet)| wallet.last_error.is_some())
+            .map(|(pubkey, _)| pubkey.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,65 @@
+use std::{io::Write, path::Path};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::compress::{self, AppendFile};
+
+/// One wallet's transaction result, written as a JSON line to the `--stats-file`.
+#[derive(Deserialize, Serialize)]
+pub struct StatsRecord {
+    pub timestamp: String,
+    pub wallet: String,
+    pub sig: String,
+    /// Seconds spent hashing the batch the wallet was in.
+    pub hash_time: u64,
+    /// Seconds from sending the transaction until it landed, 0 if it failed.
+    pub tx_time: u64,
+    pub failed: bool,
+}
+
+/// Append-only mine-v2 stats. Earlier runs' records are loaded on open, so running stats
+/// carry over restarts. A `.gz` path is read and written gzip compressed.
+pub struct StatsFile {
+    path: String,
+    file: AppendFile,
+}
+
+impl StatsFile {
+    /// Opens the stats file for appending, returning the records already in it.
+    pub fn open(path: &str) -> Result<(Self, Vec<StatsRecord>), String> {
+        let mut records = vec![];
+        if Path::new(path).exists() {
+            let contents = compress::read_to_string(path)
+                .map_err(|e| format!("Failed to read stats file {}: {}", path, e))?;
+            for (i, line) in contents.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record = serde_json::from_str(line).map_err(|e| {
+                    format!("Invalid stats file {} at line {}: {}", path, i + 1, e)
+                })?;
+                records.push(record);
+            }
+            info!("Loaded {} earlier results from {}", records.len(), path);
+        }
+        let file = AppendFile::open(path)
+            .map_err(|e| format!("Failed to open stats file {}: {}", path, e))?;
+        Ok((
+            Self {
+                path: path.to_string(),
+                file,
+            },
+            records,
+        ))
+    }
+
+    pub fn append(&mut self, record: &StatsRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.flush())
+            .map_err(|e| format!("Failed to write to stats file {}: {}", self.path, e))
+    }
+}
@@ -1,10 +1,10 @@
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::engine::Engine as _;
-use ore::{state::Bus, utils::AccountDeserialize};
+use clap::ValueEnum;
 use ore::{BUS_ADDRESSES, BUS_COUNT};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::{
-    client_error::Result as ClientResult,
+    client_error::{ClientError, Result as ClientResult},
     rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
 };
 use solana_program::instruction::Instruction;
@@ -13,31 +13,76 @@ use solana_program::system_instruction;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::account::ReadableAccount;
 use solana_sdk::signature::read_keypair_file;
-use solana_sdk::signer::EncodableKey;
 use solana_sdk::{
     commitment_config::CommitmentLevel,
     compute_budget::ComputeBudgetInstruction,
-    keccak::{hashv, Hash as KeccakHash},
+    hash::Hash,
+    packet::PACKET_DATA_SIZE,
     signature::{Keypair, Signature, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
-use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
-use std::path::PathBuf;
+use solana_transaction_status::UiTransactionEncoding;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use log::{debug, error, info, log_enabled, warn, Level};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::str::FromStr;
 use std::{
-    io::{stdout, Write},
-    sync::{atomic::AtomicBool, Arc, Mutex},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     sync::mpsc::{self, Receiver, Sender},
+    task::JoinSet,
     time::sleep,
 };
 
-use crate::cu_limits::{CU_LIMIT_CLAIM, CU_LIMIT_MINE};
-use crate::utils::{get_proof, get_proof_v2, get_treasury, proof_pubkey};
+use crate::blockhash_cache::BlockhashCache;
+use crate::confirm_service::ConfirmService;
+use crate::cu_limits::{CU_LIMIT_CLAIM, CU_LIMIT_MINE, CU_LIMIT_TOKEN_TRANSFER};
+use crate::fee_payer::FeePayer;
+use crate::hash_pool::{HashOutput, HashPool};
+use crate::jito::Jito;
+use crate::ledger::{ClaimLedger, ClaimRecord};
+use crate::metrics;
+use crate::mine::validate_hash;
+use crate::mine_csv::{CsvRecord, MineCsv};
+use crate::mine_queue::MineQueue;
+use crate::mine_report::MineReport;
+use crate::mine_stats::{StatsFile, StatsRecord};
+use crate::output::{Output, OutputFormat};
+use crate::profitability::ProfitabilityGate;
+use crate::webhook::{Webhook, WebhookEvent};
+use crate::summary::Summary;
+use crate::utils::{
+    difficulty_zero_bits, estimate_tx_fee, format_ore, get_ore_balance, get_proof_v2, get_proofs,
+    get_treasury, load_keypairs, proof_pubkey, refresh_priority_fee, rpc_permit,
+    wait_for_next_epoch, BusAccount, LoadKeypairsOptions, PriorityFee, TokenProgram, TxSettings,
+    WalletSource, CONFIRM_TIMEOUT_ERROR, TX_FAILED_ERROR,
+};
 
 const SIMULATION_RETRIES: usize = 4;
+// Most claims `claim --aggregate` packs into one transaction, like the mine batches
+const CLAIM_BATCH_SIZE: usize = 5;
+const WALLET_QUEUE_SIZE: usize = 100;
+// The wallet feeder tops the queue back up to the high-water mark once it drains below the
+// low-water mark, so only a few batches worth of wallets are ever queued at once
+const WALLET_QUEUE_LOW_WATER: usize = 10;
+const WALLET_QUEUE_HIGH_WATER: usize = 50;
+// Wallets skipped for not having enough SOL to pay their fee are requeued after this long
+const LOW_SOL_REQUEUE_DELAY: Duration = Duration::from_secs(60);
+// Backoff between failed register attempts, doubling from the first delay up to the max
+const REGISTER_RETRY_DELAY: Duration = Duration::from_millis(500);
+const REGISTER_MAX_RETRY_DELAY: Duration = Duration::from_secs(4);
+// Wallets that failed to register are dropped from their batch and requeued after this long
+const REGISTER_REQUEUE_DELAY: Duration = Duration::from_secs(30);
 // Odds of being selected to submit a reset tx
 // const RESET_ODDS: u64 = 20;
 
@@ -47,787 +92,3194 @@ pub struct WalletQueueMessage {
 
 pub struct TransactionQueueMessage {
     pub wallets: Vec<String>,
+    /// Bus and nonce each wallet mined with, in `wallets` order.
+    pub bus_ids: Vec<usize>,
+    pub nonces: Vec<u64>,
     pub encoded_unsigned_tx: String,
     pub hash_time_elapsed: u64,
+    pub expected_rewards: u64,
+    /// Priority fee the tx was built with.
+    pub priority_fee: PriorityFee,
 }
 
 pub struct TransactionResultMessage {
     pub wallets: Vec<String>,
+    /// Bus and nonce each wallet mined with, in `wallets` order.
+    pub bus_ids: Vec<usize>,
+    pub nonces: Vec<u64>,
     pub sig: String,
     pub tx_time_elapsed: u64,
     pub hash_time_elapsed: u64,
+    pub expected_rewards: u64,
     pub failed: bool,
+    pub error: Option<String>,
+    /// Lamports the fee payer paid for the tx, 0 when it never landed.
+    pub fee_paid: u64,
 }
 
-pub struct MinerV2;
+impl TransactionResultMessage {
+    /// Result for a queued tx that was dropped before it was sent, requeueing its wallets.
+    fn dropped(mssg: &TransactionQueueMessage, error: String) -> Self {
+        Self {
+            wallets: mssg.wallets.clone(),
+            bus_ids: mssg.bus_ids.clone(),
+            nonces: mssg.nonces.clone(),
+            sig: "failed".to_string(),
+            tx_time_elapsed: 0,
+            hash_time_elapsed: mssg.hash_time_elapsed,
+            expected_rewards: mssg.expected_rewards,
+            failed: true,
+            error: Some(error),
+            fee_paid: 0,
+        }
+    }
+}
 
-impl MinerV2 {
-    pub async fn claim(
-        rpc_client: Arc<RpcClient>,
-        send_interval: u64,
-        wallets_directory_string: Option<String>,
-        beneficiary: Option<String>,
-        priority_fee: u64,
-    ) {
-        println!("MinerV2 claiming rewards.");
-        let mut key_paths = vec![];
+/// The unsigned tx of a `TransactionQueueMessage`, base64 encoded bincode.
+fn decode_tx(encoded: &str) -> Result<Transaction, String> {
+    let bytes = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+    bincode::deserialize(&bytes).map_err(|e| e.to_string())
+}
 
-        if let Some(wallets_dir) = wallets_directory_string {
-            let dir_reader = tokio::fs::read_dir(wallets_dir.clone()).await;
-            if let Ok(mut dir_reader) = dir_reader {
-                loop {
-                    if let Ok(Some(next_entry)) = dir_reader.next_entry().await {
-                        key_paths.push(next_entry.path());
-                    } else {
-                        break;
-                    }
-                }
-            } else {
-                println!("Failed to read miner wallets directory: {}", wallets_dir);
-                return;
+/// What a claim-v2 run claimed, for its closing line.
+struct ClaimTotals {
+    /// Decimals of the ORE amounts printed.
+    decimals: usize,
+    with_rewards: u64,
+    claimed: u64,
+    /// Rewards left in wallets skipped for being under `--min-claim-amount`.
+    below_minimum: u64,
+}
+
+impl ClaimTotals {
+    fn new(decimals: usize) -> Self {
+        Self {
+            decimals,
+            with_rewards: 0,
+            claimed: 0,
+            below_minimum: 0,
+        }
+    }
+
+    /// Whether `rewards` is under `--min-claim-amount`, counting it as left unclaimed if so.
+    fn below_minimum(&mut self, rewards: u64, min_claim_amount: Option<u64>) -> bool {
+        match min_claim_amount {
+            Some(min_claim_amount) if rewards < min_claim_amount => {
+                info!(
+                    "Skipping {} ORE, below the --min-claim-amount of {} ORE.",
+                    format_ore(rewards, self.decimals),
+                    format_ore(min_claim_amount, self.decimals)
+                );
+                self.below_minimum += rewards;
+                true
             }
+            _ => false,
         }
-        let beneficiary = match beneficiary {
-            Some(beneficiary) => {
-                println!("Claim beneficiary supplied: {}", beneficiary);
-                Some(Pubkey::from_str(&beneficiary).expect("Failed to parse beneficiary address"))
+    }
+
+    fn print(&self, wallets: usize, summary: &mut Summary) {
+        info!(
+            "\nChecked {} wallets, {} with rewards, claimed {} ORE.",
+            wallets,
+            self.with_rewards,
+            format_ore(self.claimed, self.decimals)
+        );
+        if self.below_minimum > 0 {
+            info!(
+                "Left {} ORE unclaimed below --min-claim-amount.",
+                format_ore(self.below_minimum, self.decimals)
+            );
+        }
+        if self.with_rewards == 0 && summary.failed == 0 {
+            info!("Nothing to claim.");
+            summary.nothing_to_do = true;
+        }
+    }
+}
+
+/// Stops new mine-v2 wallets from hashing and tells `mine` to drain and finish, with the
+/// failure if there is one. Only the first stop is sent.
+fn stop_mining(
+    stopping: &AtomicBool,
+    stop_sender: &mut Option<tokio::sync::oneshot::Sender<Option<String>>>,
+    failure: Option<String>,
+) {
+    stopping.store(true, Ordering::Relaxed);
+    if let Some(sender) = stop_sender.take() {
+        let _ = sender.send(failure);
+    }
+}
+
+/// Waits for the mine-v2 transactions already sent to land or fail, a Ctrl-C aborts them.
+async fn drain_in_flight(in_flight_txs: &AtomicUsize, tx_times: &Mutex<TxTimes>) {
+    info!(
+        "\nStopping, waiting for {} in-flight transactions. Press Ctrl-C again to abort.",
+        in_flight_txs.load(Ordering::Relaxed)
+    );
+    while in_flight_txs.load(Ordering::Relaxed) > 0 {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                error!("Aborted with transactions still in flight.");
+                break;
             }
-            None => None,
-        };
+            _ = sleep(Duration::from_millis(500)) => {}
+        }
+    }
+    tx_times.lock().unwrap().print();
+}
 
-        println!("Found {} wallets", key_paths.len());
+/// Timings of the landed mine-v2 transactions, in seconds.
+#[derive(Default)]
+struct TxTimes {
+    tx: Vec<u64>,
+    hash: Vec<u64>,
+    total: Vec<u64>,
+}
 
-        for key_path in key_paths.clone() {
-            if let Ok(signer) = read_keypair_file(key_path.clone()) {
-                println!("Starting claim for \n{}", signer.pubkey().to_string());
-                println!("Key path: {}", key_path.to_str().unwrap());
+impl TxTimes {
+    /// Starts from the landed transactions of an earlier `--stats-file`, which has a record
+    /// per wallet rather than per transaction.
+    fn resume(records: &[StatsRecord]) -> Self {
+        let mut times = Self::default();
+        let mut sigs = HashSet::new();
+        for record in records.iter().filter(|record| !record.failed) {
+            if sigs.insert(record.sig.as_str()) {
+                times.push(record.tx_time, record.hash_time);
+            }
+        }
+        times
+    }
 
-                let proof = get_proof(&rpc_client, signer.pubkey()).await;
-                let rewards = proof.claimable_rewards;
-                let amount = rewards;
+    fn push(&mut self, tx_time: u64, hash_time: u64) {
+        self.tx.push(tx_time);
+        self.hash.push(hash_time);
+        self.total.push(tx_time + hash_time);
+    }
 
-                if amount == 0 {
-                    println!("No rewards to claim in this wallet.");
-                    continue;
-                }
+    fn print(&self) {
+        debug!("TX TIMES COUNT: {:?}", self.tx.len());
+        debug!("TX TIMES: \n{:?}", self.tx);
+        debug!("HASH TIMES: \n{:?}", self.hash);
+        debug!("TOTAL TIMES: \n{:?}", self.total);
+        if !self.tx.is_empty() {
+            let average = |times: &[u64]| times.iter().sum::<u64>() as f64 / times.len() as f64;
+            debug!(
+                "AVERAGE TIMES: tx {:.1}s, hash {:.1}s, total {:.1}s",
+                average(&self.tx),
+                average(&self.hash),
+                average(&self.total)
+            );
+        }
+    }
+}
 
-                let token_account = if let Some(beneficiary) = beneficiary {
-                    beneficiary
-                } else {
-                    MinerV2::initialize_ata(
-                        rpc_client.clone(),
-                        &signer,
-                        priority_fee,
-                        send_interval,
-                    )
-                    .await
-                };
-                println!("Proof: {:?}", proof);
-                let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM);
-                let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
-                let ix = ore::instruction::claim(signer.pubkey(), token_account, amount);
-
-                println!("Building tx...");
-                let mut tx = Transaction::new_with_payer(
-                    &[cu_limit_ix, cu_price_ix, ix],
-                    Some(&signer.pubkey()),
-                );
+/// One wallet of the `wallets` command. Amounts are raw token units, `None` when they
+/// couldn't be read, see `error`.
+#[derive(Serialize)]
+struct WalletOutput {
+    pubkey: String,
+    keypair_path: String,
+    balance: Option<u64>,
+    claimable_rewards: Option<u64>,
+    /// Whether the wallet's ORE token account exists.
+    ata_exists: Option<bool>,
+    error: Option<String>,
+}
 
-                let (hash, last_valid_blockheight) = rpc_client
-                    .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                    .await
-                    .unwrap();
-
-                println!("Signing tx...");
-                tx.sign(&[&signer], hash);
-
-                println!("Submitting claim transaction...");
-                let send_cfg = RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    preflight_commitment: Some(CommitmentLevel::Confirmed),
-                    encoding: Some(UiTransactionEncoding::Base64),
-                    max_retries: None,
-                    min_context_slot: None,
-                };
-                let result = MinerV2::send_and_confirm_transaction(
-                    rpc_client.clone(),
-                    tx,
-                    last_valid_blockheight,
-                    send_interval,
-                    send_cfg,
-                )
-                .await;
+#[derive(Serialize)]
+struct WalletsTotals {
+    wallets: usize,
+    balance: u64,
+    claimable_rewards: u64,
+    /// Wallets left out for having no claimable rewards, see `--include-zero`.
+    skipped: u64,
+}
 
-                match result {
-                    Ok((sig, tx_time_elapsed)) => {
-                        println!("Success: {}", sig);
-                        println!("Took: {} seconds", tx_time_elapsed);
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
+#[derive(Serialize)]
+struct WalletsOutput {
+    wallets: Vec<WalletOutput>,
+    totals: WalletsTotals,
+    #[serde(skip)]
+    decimals: usize,
+}
+
+impl Output for WalletsOutput {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "pubkey",
+        "keypair_path",
+        "balance",
+        "claimable_rewards",
+        "ata_exists",
+        "error",
+    ];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        self.wallets
+            .iter()
+            .map(|wallet| {
+                vec![
+                    wallet.pubkey.clone(),
+                    wallet.keypair_path.clone(),
+                    wallet.balance.map(|b| b.to_string()).unwrap_or_default(),
+                    wallet.claimable_rewards.map(|r| r.to_string()).unwrap_or_default(),
+                    wallet.ata_exists.map(|e| e.to_string()).unwrap_or_default(),
+                    wallet.error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn text(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for wallet in self.wallets.iter() {
+            lines.push(format!("\nLoaded wallet pubkey: \n{}", wallet.pubkey));
+            lines.push(format!("Wallet Path: {}", wallet.keypair_path));
+            match (wallet.claimable_rewards, &wallet.error) {
+                (Some(rewards), error) => {
+                    match wallet.balance {
+                        Some(balance) => {
+                            lines.push(format!("Balance: {} ORE", format_ore(balance, self.decimals)))
+                        }
+                        None => lines.push(format!(
+                            "Balance: ? ORE ({})",
+                            error.as_deref().unwrap_or_default()
+                        )),
                     }
+                    lines.push(format!("Claimable: {} ORE", format_ore(rewards, self.decimals)));
+                }
+                (None, error) => {
+                    lines.push(format!("Error: {}", error.as_deref().unwrap_or_default()))
                 }
-            } else {
-                println!(
-                    "Failed to read keypair file: {}",
-                    key_path.to_str().unwrap()
-                );
             }
         }
+        lines.push(format!(
+            "\nTotal over {} wallets: {} ORE balance, {} ORE claimable, {} skipped with no rewards",
+            self.totals.wallets,
+            format_ore(self.totals.balance, self.decimals),
+            format_ore(self.totals.claimable_rewards, self.decimals),
+            self.totals.skipped
+        ));
+        lines
     }
+}
 
-    pub async fn mine(
-        rpc_client: Arc<RpcClient>,
-        threads: u64,
-        send_interval: u64,
-        batch_size: u64,
-        wallets_directory_string: Option<String>,
-        priority_fee: u64,
-        sim_attempts: Option<u64>,
-        fee_payer: Option<String>,
-    ) {
-        println!("MinerV2 Running...");
-        let (wallet_queue_sender, mut wallet_queue_reader): (
-            mpsc::Sender<WalletQueueMessage>,
-            mpsc::Receiver<WalletQueueMessage>,
-        ) = tokio::sync::mpsc::channel(100);
-        let (tx_queue_sender, mut tx_queue_reader): (
-            mpsc::Sender<TransactionQueueMessage>,
-            mpsc::Receiver<TransactionQueueMessage>,
-        ) = tokio::sync::mpsc::channel(100);
-        let (tx_results_sender, mut tx_results_reader): (
-            mpsc::Sender<TransactionResultMessage>,
-            mpsc::Receiver<TransactionResultMessage>,
-        ) = tokio::sync::mpsc::channel(100);
+/// How mine-v2 spreads a batch's wallets over the busses, picked with `--bus-strategy`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusStrategy {
+    /// Rotate through every bus that can still pay out a reward.
+    #[value(name = "roundrobin")]
+    RoundRobin,
+    /// The bus with the most rewards left, minus what the batch's earlier wallets take from it.
+    Max,
+    /// A random bus with rewards left for at least 20 more wallets, like `mine`.
+    Random,
+}
 
-        let mut has_fee_payer = false;
-        let fee_payer_string = if let Some(fee_payer) = fee_payer {
-            has_fee_payer = true;
-            fee_payer
-        } else {
-            "".to_string()
+impl BusStrategy {
+    /// Picks a bus for each of `wallets` wallets. Falls back to the richest bus when none
+    /// meets the strategy's threshold, and to the `rotation` bus when none could be loaded.
+    fn assign(
+        &self,
+        busses: &[BusAccount],
+        reward_rate: u64,
+        wallets: usize,
+        rotation: usize,
+    ) -> Vec<usize> {
+        let Some(richest) = busses.iter().max_by_key(|bus| bus.rewards) else {
+            return vec![rotation % BUS_COUNT; wallets];
         };
-
-        if let Some(wallets_dir) = wallets_directory_string {
-            let mut key_strings = vec![];
-            println!("Reading wallet directory");
-            let dir_reader = tokio::fs::read_dir(wallets_dir.clone()).await;
-            if let Ok(mut dir_reader) = dir_reader {
-                loop {
-                    if let Ok(Some(next_entry)) = dir_reader.next_entry().await {
-                        if let Ok(signer) = read_keypair_file(next_entry.path().clone()) {
-                            let key_string = signer.to_base58_string();
-                            key_strings.push(key_string);
-                        } else {
-                            println!(
-                                "Failed to read keypair file: {}",
-                                next_entry.path().to_str().unwrap()
-                            );
-                        }
-                    } else {
-                        break;
-                    }
+        match self {
+            BusStrategy::RoundRobin => {
+                let funded: Vec<usize> = busses
+                    .iter()
+                    .filter(|bus| bus.rewards >= reward_rate)
+                    .map(|bus| bus.id)
+                    .collect();
+                if funded.is_empty() {
+                    return vec![richest.id; wallets];
                 }
-            } else {
-                println!("Failed to read miner wallets directory: {}", wallets_dir);
+                (0..wallets)
+                    .map(|i| funded[(rotation + i) % funded.len()])
+                    .collect()
+            }
+            BusStrategy::Max => {
+                let mut rewards: Vec<(usize, u64)> =
+                    busses.iter().map(|bus| (bus.id, bus.rewards)).collect();
+                (0..wallets)
+                    .map(|_| {
+                        let bus = rewards.iter_mut().max_by_key(|(_, rewards)| *rewards).unwrap();
+                        bus.1 = bus.1.saturating_sub(reward_rate);
+                        bus.0
+                    })
+                    .collect()
+            }
+            BusStrategy::Random => {
+                let funded: Vec<usize> = busses
+                    .iter()
+                    .filter(|bus| bus.rewards > reward_rate.saturating_mul(20))
+                    .map(|bus| bus.id)
+                    .collect();
+                if funded.is_empty() {
+                    return vec![richest.id; wallets];
+                }
+                let mut rng = rand::thread_rng();
+                (0..wallets)
+                    .map(|_| funded[rng.gen_range(0..funded.len())])
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Refills wallets from a funding wallet during mining, for `--auto-topup`.
+pub struct AutoTopup {
+    pub funding: Keypair,
+    /// Balance a wallet is topped back up to once it drops below half of it.
+    pub target: u64,
+    /// Most lamports sent in total this session.
+    pub max_total: u64,
+    pub sent: u64,
+}
+
+impl AutoTopup {
+    /// Tops `wallet` up to the target if it's running low and the session cap allows it.
+    async fn check(
+        &mut self,
+        rpc_client: &Arc<RpcClient>,
+        tx_settings: &TxSettings,
+        wallet: Pubkey,
+        send_interval: u64,
+    ) {
+        let balance = match rpc_client.get_balance(&wallet).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                warn!("Failed to load balance for {}, skipping top-up: {}", wallet, e);
                 return;
             }
+        };
+        if balance >= self.target / 2 {
+            return;
+        }
+        let amount = self.target - balance;
+        if self.sent + amount > self.max_total {
+            info!(
+                "Wallet {} is low on SOL ({} SOL) but topping it up would exceed the --max-topup-total of {} SOL.",
+                wallet,
+                lamports_to_sol(balance),
+                lamports_to_sol(self.max_total)
+            );
+            return;
+        }
+        let fee = estimate_tx_fee(1, 0, PriorityFee::PerCu(0));
+        if let Err(e) = tx_settings
+            .check_sol_reserve(rpc_client, &self.funding.pubkey(), amount + fee)
+            .await
+        {
+            info!("Skipping top-up of {}, funding wallet: {}", wallet, e);
+            return;
+        }
+        let (hash, last_valid_blockheight) =
+            match BlockhashCache::latest_blockhash(rpc_client).await {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!("{}, skipping top-up of {}", e, wallet);
+                    return;
+                }
+            };
+        info!("Topping up {} with {} SOL...", wallet, lamports_to_sol(amount));
+        let tx = sol_transfer_tx(&self.funding, &wallet, amount, hash);
+        let send_cfg = RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: None,
+            min_context_slot: None,
+        };
+        match MinerV2::send_and_confirm_transaction(
+            tx_settings,
+            tx,
+            last_valid_blockheight,
+            send_interval,
+            send_cfg,
+        )
+        .await
+        {
+            Ok((sig, _tx_time_elapsed)) => {
+                self.sent += amount;
+                info!(
+                    "Topped up {}: {} ({} of {} SOL top-up allowance used)",
+                    wallet,
+                    sig,
+                    lamports_to_sol(self.sent),
+                    lamports_to_sol(self.max_total)
+                );
+            }
+            Err(e) => warn!("Failed to top up {}: {}", wallet, e),
+        }
+    }
+}
 
+/// Settings of a `claim-v2` run, also used by the mine-v2 claim monitor.
+pub struct ClaimConfig {
+    pub send_interval: u64,
+    pub wallet_source: Option<WalletSource>,
+    pub beneficiary: Option<String>,
+    pub beneficiary_owner: Option<String>,
+    /// Claim to each wallet's own token account, then transfer to this owner.
+    pub transfer_to: Option<String>,
+    pub amount: Option<u64>,
+    pub min_claim_amount: Option<u64>,
+    pub priority_fee: PriorityFee,
+    pub fail_fast: bool,
+    pub wait_for_epoch: bool,
+    pub token_program: TokenProgram,
+    pub decimals: usize,
+    pub ledger: Option<String>,
+    pub aggregate: bool,
+    pub tx_settings: TxSettings,
+}
 
-            // tokio spawn threads
-            // wallet queue reader thread
-            let mut handles = vec![];
-            let rpc_client_0 = rpc_client.clone();
-            let fee_payer_string_1 = fee_payer_string.clone();
-            let thread_handle = tokio::spawn(async move {
-                let rpc_client = rpc_client_0.clone();
-                let mut wallet_batch = vec![];
-                let batch_size = if batch_size > 5 { 5 } else { batch_size };
+/// Settings of a `mine-v2` run.
+pub struct MineV2Config {
+    /// Hashing threads shared by every wallet.
+    pub threads: u64,
+    pub send_interval: u64,
+    pub batch_size: u64,
+    pub batch_timeout_secs: u64,
+    pub wallet_source: Option<WalletSource>,
+    pub priority_fee: PriorityFee,
+    pub sim_attempts: Option<u64>,
+    pub simulate_only: bool,
+    pub jito: Option<Jito>,
+    pub fee_payer: Option<FeePayer>,
+    pub decimals: usize,
+    pub fail_fast: bool,
+    pub progress: bool,
+    pub max_fee_payer_spend: Option<u64>,
+    /// Successful rounds per wallet, unlimited when None.
+    pub rounds: Option<u64>,
+    pub token_program: TokenProgram,
+    pub report: Option<String>,
+    pub retry_failed: Option<String>,
+    pub min_ore_per_sol: Option<f64>,
+    pub queue_file: Option<String>,
+    pub stats_file: Option<String>,
+    pub csv_file: Option<String>,
+    pub max_register_attempts: u32,
+    pub auto_topup: Option<AutoTopup>,
+    pub threads_per_wallet: Option<u64>,
+    pub overcommit: bool,
+    pub bus_strategy: BusStrategy,
+    pub webhook_failures: u64,
+    pub claim_threshold: Option<u64>,
+    pub claim_beneficiary: Option<String>,
+    pub claim_check_interval: u64,
+    pub heartbeat_interval: u64,
+    pub stall_timeout: Option<u64>,
+    pub exit_on_stall: bool,
+    pub tx_settings: TxSettings,
+}
 
-                let mut bus = 0;
-                loop {
-                    if let Some(mssg) = wallet_queue_reader.recv().await {
-                        wallet_batch.push(mssg.wallet);
-                    }
-                    // TODO: start processing hash here, so when 5th wallet
-                    // comes in and hash finishes it can be sent off right away.
-                    if wallet_batch.len() as u64 == batch_size {
-                        let mut keys_bytes_with_hashes = Vec::new();
-                        println!("Got {} wallets, hashing...", batch_size);
-                        let hash_timer = SystemTime::now();
-                        let treasury = get_treasury(&rpc_client).await;
-
-                        for wallet in wallet_batch.clone() {
-                            let signer = Keypair::from_base58_string(&wallet);
-                            //let balance = MinerV2::get_ore_display_balance(&rpc_client, signer.pubkey()).await;
-                            MinerV2::register(
-                                rpc_client.clone(),
-                                &signer,
-                                send_interval,
-                                priority_fee,
-                            )
-                            .await;
-                            let proof = get_proof(&rpc_client, signer.pubkey()).await;
-                            //let rewards =
-                            //    (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-
-                            println!("\nStarting hash for wallet {}", signer.pubkey());
-                            let st = wallet.clone();
-                            let th = tokio::task::spawn_blocking(move || {
-                                let handle = std::thread::spawn(move || {
-                                    let signer = Keypair::from_base58_string(&st);
-                                    let (next_hash, nonce) = MinerV2::find_next_hash_par(
-                                        &signer,
-                                        proof.hash.into(),
-                                        treasury.difficulty.into(),
-                                        threads,
-                                    );
-                                    return (wallet.clone(), next_hash, nonce);
-                                });
+pub struct MinerV2;
 
-                                return handle.join().unwrap();
-                            });
-                            let data = th.await.unwrap();
-                            keys_bytes_with_hashes.push(data);
-                        }
-                        let hash_time = hash_timer.elapsed().unwrap().as_secs();
-
-                        println!("\nHashing complete.");
-                        println!("Building transaction...");
-                        // Reset epoch, if needed
-                        //let treasury = get_treasury(&rpc_client).await;
-                        //let clock = get_clock_account(&rpc_client).await;
-                        //let threshold = treasury.last_reset_at.saturating_add(EPOCH_DURATION);
-                        // can't use thread_rng() across thread safetly
-                        //let mut rng = rand::thread_rng();
-
-                        //if clock.unix_timestamp.ge(&threshold) {
-                        //    // There are a lot of miners right now, so randomly select into submitting tx
-                        //    if rng.gen_range(0..RESET_ODDS).eq(&0) {
-                        //        println!("Sending epoch reset transaction...");
-                        //        let signer =
-                        //            Keypair::from_base58_string(&keys_bytes_with_hashes[0].0);
-                        //        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-                        //            CU_LIMIT_RESET,
-                        //        );
-                        //        let cu_price_ix =
-                        //            ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
-                        //        let reset_ix = ore::instruction::reset(signer.pubkey());
-                        //        MinerV2::send_and_confirm(
-                        //            &signer,
-                        //            rpc_client.clone(),
-                        //            &[cu_limit_ix, cu_price_ix, reset_ix],
-                        //            false,
-                        //            send_interval,
-                        //            priority_fee,
-                        //        )
-                        //        .await
-                        //        .ok();
-                        //    }
-                        //}
-                        let wallet_count = keys_bytes_with_hashes.len();
-                        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-                            CU_LIMIT_MINE * wallet_count as u32,
-                        );
-                        let cu_price_ix =
-                            ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
-
-                        let mut ixs = vec![];
-                        ixs.push(cu_limit_ix);
-                        ixs.push(cu_price_ix);
-                        let bus =
-                            MinerV2::get_bus(&rpc_client, bus).await.expect("Should successfully get bus.");
-                        let bus_rewards =
-                            (bus.rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-                        println!("Will be sending on bus {} ({} ORE)", bus.id, bus_rewards);
-
-                        let mut keypairs = vec![];
-                        for (key_bytes, next_hash, nonce) in keys_bytes_with_hashes.clone() {
-                            let signer = Keypair::from_base58_string(&key_bytes);
-                            keypairs.push(Keypair::from_base58_string(&key_bytes));
-                            let ix_mine = ore::instruction::mine(
-                                signer.pubkey(),
-                                BUS_ADDRESSES[bus.id as usize],
-                                next_hash.into(),
-                                nonce,
-                            );
-                            ixs.push(ix_mine);
-                        }
+impl MinerV2 {
+    pub async fn claim(rpc_client: Arc<RpcClient>, config: ClaimConfig, summary: &mut Summary) {
+        let ClaimConfig {
+            send_interval,
+            wallet_source,
+            beneficiary,
+            beneficiary_owner,
+            transfer_to,
+            amount,
+            min_claim_amount,
+            mut priority_fee,
+            fail_fast,
+            wait_for_epoch,
+            token_program,
+            decimals,
+            ledger,
+            aggregate,
+            tx_settings,
+        } = config;
+        info!("MinerV2 claiming rewards.");
+        let mut ledger = match ledger.as_deref().map(ClaimLedger::open) {
+            Some(Ok(ledger)) => Some(ledger),
+            Some(Err(e)) => {
+                error!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+            None => None,
+        };
+        let mut keypairs = vec![];
+
+        if let Some(wallet_source) = wallet_source {
+            let opts = LoadKeypairsOptions { strict: fail_fast };
+            match wallet_source.load(&opts).await {
+                Ok(k) => keypairs = k,
+                Err(e) => {
+                    error!("{}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            }
+        }
+        let beneficiary = match beneficiary {
+            Some(beneficiary) => {
+                info!("Claim beneficiary supplied: {}", beneficiary);
+                Some(Pubkey::from_str(&beneficiary).expect("Failed to parse beneficiary address"))
+            }
+            None => None,
+        };
+        let beneficiary_owner = match beneficiary_owner.map(|owner| Pubkey::from_str(&owner)) {
+            Some(Ok(owner)) => {
+                info!(
+                    "Claiming to the token account of {}: {}",
+                    owner,
+                    token_program.ore_token_account(&owner)
+                );
+                Some(owner)
+            }
+            Some(Err(e)) => {
+                warn!("Invalid beneficiary owner: {}", e);
+                summary.failed += 1;
+                return;
+            }
+            None => None,
+        };
+        let transfer_to = match transfer_to.map(|address| Pubkey::from_str(&address)) {
+            Some(Ok(transfer_to)) => {
+                info!("Claimed ORE will be transferred to {}", transfer_to);
+                Some(transfer_to)
+            }
+            Some(Err(e)) => {
+                warn!("Invalid transfer token account: {}", e);
+                summary.failed += 1;
+                return;
+            }
+            None => None,
+        };
 
-                        let fee_payer = if has_fee_payer {
-                            let file_path = PathBuf::from_str(&fee_payer_string_1);
-                            println!("File path: {:?}", file_path);
-                            match file_path {
-                                Ok(f) => {
-                                    let key = Keypair::read_from_file(f).unwrap();
-                                    println!("Found fee_payer: {}", key.pubkey().to_string());
-                                    key
+        if aggregate && beneficiary.is_none() && beneficiary_owner.is_none() {
+            summary.fail("--aggregate needs a beneficiary token account or --beneficiary-owner");
+        }
 
-                                },
-                                Err(_) => Keypair::from_base58_string(&keys_bytes_with_hashes[0].0),
-                            }
-                        } else {
-                            Keypair::from_base58_string(&keys_bytes_with_hashes[0].0)
-                        };
+        info!("Found {} wallets", keypairs.len());
 
-                        let tx =
-                            Transaction::new_with_payer(ixs.as_slice(), Some(&fee_payer.pubkey()));
+        if wait_for_epoch {
+            if let Err(e) = wait_for_next_epoch(&rpc_client).await {
+                error!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        }
 
-                        println!("Sending unsigned tx to queue...");
-                        let serialized_tx = bincode::serialize(&tx).unwrap();
-                        let encoded_unsigned_tx = BASE64.encode(serialized_tx);
-                        let tqm = TransactionQueueMessage {
-                            wallets: wallet_batch.clone(),
-                            encoded_unsigned_tx,
-                            hash_time_elapsed: hash_time,
-                        };
-                        if let Ok(_) = tx_queue_sender.send(tqm).await {
-                            println!("Sent tx to be processed.");
-                        } else {
-                            println!(
-                                "Failed to send tx to be processed. Tx Queue full? Dev help pls."
-                            );
+        if aggregate {
+            let token_account = match (beneficiary, beneficiary_owner, keypairs.first()) {
+                (Some(beneficiary), _, _) => beneficiary,
+                // Created once up front, paid by the first wallet
+                (None, Some(owner), Some((_, payer))) => {
+                    let created = MinerV2::initialize_ata(
+                        rpc_client.clone(),
+                        &tx_settings,
+                        payer,
+                        owner,
+                        priority_fee,
+                        send_interval,
+                        token_program,
+                    )
+                    .await;
+                    match created {
+                        Ok(token_account) => token_account,
+                        Err(e) => {
+                            error!("{}", e);
+                            summary.failed += 1;
+                            return;
                         }
-                        wallet_batch = vec![];
-                    } else {
                     }
+                }
+                _ => return ClaimTotals::new(decimals).print(0, summary),
+            };
+            let wallets: Vec<&Keypair> = keypairs.iter().map(|(_, signer)| signer).collect();
+            let totals = MinerV2::claim_aggregate(
+                &rpc_client,
+                &tx_settings,
+                &wallets,
+                token_account,
+                amount,
+                min_claim_amount,
+                priority_fee,
+                send_interval,
+                fail_fast,
+                decimals,
+                ledger.as_mut(),
+                summary,
+            )
+            .await;
+            totals.print(keypairs.len(), summary);
+            return;
+        }
 
-                    bus += 1;
+        let mut totals = ClaimTotals::new(decimals);
+        for (key_path, signer) in keypairs.iter() {
+            info!("Starting claim for \n{}", signer.pubkey().to_string());
+            debug!("Key path: {}", key_path.display());
 
-                    if bus >= BUS_COUNT {
-                        bus = 0;
+            let proof = match get_proof_v2(&rpc_client, signer.pubkey()).await {
+                Ok(proof) => proof,
+                Err(e) => {
+                    warn!("Error: {}", e);
+                    summary.failed += 1;
+                    if fail_fast {
+                        summary.fail(&format!("claim failed for {}: {}", signer.pubkey(), e));
                     }
-                    sleep(Duration::from_millis(100)).await;
+                    continue;
                 }
-            });
+            };
+            let rewards = proof.claimable_rewards;
 
-            handles.push(thread_handle);
+            if rewards == 0 {
+                info!("No rewards to claim in this wallet.");
+                summary.skipped += 1;
+                continue;
+            }
+            if totals.below_minimum(rewards, min_claim_amount) {
+                summary.skipped += 1;
+                continue;
+            }
+            totals.with_rewards += 1;
+
+            let amount = amount.unwrap_or(rewards);
+            if amount > rewards {
+                info!(
+                    "Requested {} ORE but only {} ORE is claimable.",
+                    format_ore(amount, decimals),
+                    format_ore(rewards, decimals)
+                );
+                summary.failed += 1;
+                if fail_fast {
+                    summary.fail(&format!("claim amount exceeds rewards for {}", signer.pubkey()));
+                }
+                continue;
+            }
 
-            // tx queue processor thread
-            let rpc_client_1 = rpc_client.clone();
-            let fee_payer_string_2 = fee_payer_string.clone();
-            let thread_handle = tokio::spawn(async move {
-                let rpc_client = rpc_client_1.clone();
-                loop {
-                    if let Some(mssg) = tx_queue_reader.recv().await {
-                        let serialized_tx =
-                            BASE64.decode(mssg.encoded_unsigned_tx.clone()).unwrap();
-                        let mut tx: Transaction = bincode::deserialize(&serialized_tx).unwrap();
-
-                        if let Some(sim_attempts) = sim_attempts {
-                            for _i in 0..sim_attempts {
-                                let (_hash, last_valid_blockheight) = rpc_client
-                                    .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                                    .await
-                                    .unwrap();
-
-                                let sim_res = rpc_client
-                                    .simulate_transaction_with_config(
-                                        &tx,
-                                        RpcSimulateTransactionConfig {
-                                            sig_verify: false,
-                                            replace_recent_blockhash: true,
-                                            commitment: Some(rpc_client.commitment()),
-                                            encoding: Some(UiTransactionEncoding::Base64),
-                                            accounts: None,
-                                            min_context_slot: Some(last_valid_blockheight),
-                                            inner_instructions: false,
-                                        },
-                                    )
-                                    .await;
-                                match sim_res {
-                                    Ok(sim_res) => {
-                                        if let Some(err) = sim_res.value.err {
-                                            println!("Simulaton error: {:?}", err);
-                                        } else {
-                                            println!("Simulaton successful.");
-                                        }
-                                    }
-                                    Err(err) => {
-                                        println!("Simulaton error: {:?}", err);
-                                    }
-                                }
-                            }
-                        }
+            priority_fee = refresh_priority_fee(&rpc_client, priority_fee).await;
+            let mut fee = estimate_tx_fee(1, CU_LIMIT_CLAIM, priority_fee);
+            if transfer_to.is_some() {
+                fee += estimate_tx_fee(1, CU_LIMIT_TOKEN_TRANSFER, priority_fee);
+            }
+            if let Err(e) = tx_settings
+                .check_sol_reserve(&rpc_client, &signer.pubkey(), fee)
+                .await
+            {
+                info!("Skipping claim: {}", e);
+                summary.skipped += 1;
+                continue;
+            }
 
-                        let (hash, last_valid_blockheight) = rpc_client
-                            .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                            .await
-                            .unwrap();
-
-                        let wallets = mssg.wallets.clone();
-                        let fee_payer = if has_fee_payer {
-                            let file_path = PathBuf::from_str(&fee_payer_string_2);
-                            match file_path {
-                                Ok(f) => Keypair::read_from_file(f).unwrap(),
-                                Err(_) => Keypair::from_base58_string(&mssg.wallets[0]),
-                            }
+            let token_account = if let Some(beneficiary) = beneficiary {
+                beneficiary
+            } else {
+                let created = MinerV2::initialize_ata(
+                    rpc_client.clone(),
+                    &tx_settings,
+                    &signer,
+                    beneficiary_owner.unwrap_or(signer.pubkey()),
+                    priority_fee,
+                    send_interval,
+                    token_program,
+                )
+                .await;
+                match created {
+                    Ok(token_account) => token_account,
+                    Err(e) => {
+                        warn!("Error: {}", e);
+                        summary.failed += 1;
+                        if fail_fast {
+                            summary.fail(&format!("claim failed for {}: {}", signer.pubkey(), e));
+                        }
+                        continue;
+                    }
+                }
+            };
+            debug!("Proof: {:?}", proof);
+            let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM);
+            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                priority_fee.cu_price(CU_LIMIT_CLAIM),
+            );
+            let ix = ore::instruction::claim(signer.pubkey(), token_account, amount);
+
+            debug!("Building tx...");
+            let mut tx = Transaction::new_with_payer(
+                &[cu_limit_ix, cu_price_ix, ix],
+                Some(&signer.pubkey()),
+            );
+
+            let (hash, last_valid_blockheight) =
+                match BlockhashCache::latest_blockhash(&rpc_client).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        warn!("{}", e);
+                        summary.failed += 1;
+                        if fail_fast {
+                            summary.fail(&format!(
+                                "claim failed for {}: {}",
+                                signer.pubkey(),
+                                e
+                            ));
+                        }
+                        continue;
+                    }
+                };
+
+            debug!("Signing tx...");
+            tx.sign(&[&signer], hash);
+
+            info!("Submitting claim transaction...");
+            let send_cfg = RpcSendTransactionConfig {
+                skip_preflight: true,
+                preflight_commitment: Some(CommitmentLevel::Confirmed),
+                encoding: Some(UiTransactionEncoding::Base64),
+                max_retries: None,
+                min_context_slot: None,
+            };
+            let result = MinerV2::send_and_confirm_transaction(
+                &tx_settings,
+                tx,
+                last_valid_blockheight,
+                send_interval,
+                send_cfg,
+            )
+            .await;
+
+            match result {
+                Ok((sig, tx_time_elapsed)) => {
+                    info!("Success: {}", sig);
+                    info!("Took: {} seconds", tx_time_elapsed);
+                    summary.success += 1;
+                    summary.ore_claimed += amount;
+                    summary.lamports_spent += estimate_tx_fee(1, CU_LIMIT_CLAIM, priority_fee);
+                    summary.tx_seconds += tx_time_elapsed;
+                    totals.claimed += amount;
+
+                    if let Some(ledger) = ledger.as_mut() {
+                        let record = ClaimRecord::new(
+                            &rpc_client,
+                            signer.pubkey(),
+                            amount,
+                            token_account,
+                            sig,
+                        )
+                        .await;
+                        if let Err(e) = ledger.append(&record) {
+                            warn!("{}", e);
+                            if fail_fast {
+                                summary.fail(&e);
+                            }
+                        }
+                    }
+
+                    if let Some(transfer_to) = transfer_to {
+                        if let Err(e) = MinerV2::transfer_ore(
+                            rpc_client.clone(),
+                            &tx_settings,
+                            &signer,
+                            token_account,
+                            transfer_to,
+                            amount,
+                            send_interval,
+                            priority_fee,
+                            token_program,
+                            decimals,
+                        )
+                        .await
+                        {
+                            warn!("Transfer failed: {}", e);
+                            summary.failed += 1;
+                            if fail_fast {
+                                summary.fail(&format!(
+                                    "transfer failed for {}: {}",
+                                    signer.pubkey(),
+                                    e
+                                ));
+                            }
+                        } else {
+                            summary.lamports_spent += estimate_tx_fee(
+                                1,
+                                CU_LIMIT_TOKEN_TRANSFER,
+                                priority_fee,
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("Error: {}", e);
+                    summary.failed += 1;
+                    if fail_fast {
+                        summary.fail(&format!("claim failed for {}: {}", signer.pubkey(), e));
+                    }
+                }
+            }
+        }
+
+        totals.print(keypairs.len(), summary);
+    }
+
+    /// `claim-v2 --aggregate`: claims every wallet's rewards to `token_account` with up to
+    /// `CLAIM_BATCH_SIZE` claims per transaction. Each wallet signs its own claim and the
+    /// first wallet of a transaction pays for it.
+    async fn claim_aggregate(
+        rpc_client: &Arc<RpcClient>,
+        tx_settings: &TxSettings,
+        wallets: &[&Keypair],
+        token_account: Pubkey,
+        amount: Option<u64>,
+        min_claim_amount: Option<u64>,
+        mut priority_fee: PriorityFee,
+        send_interval: u64,
+        fail_fast: bool,
+        decimals: usize,
+        mut ledger: Option<&mut ClaimLedger>,
+        summary: &mut Summary,
+    ) -> ClaimTotals {
+        let mut totals = ClaimTotals::new(decimals);
+        let mut claims = vec![];
+        for signer in wallets.iter().copied() {
+            let proof = match get_proof_v2(rpc_client, signer.pubkey()).await {
+                Ok(proof) => proof,
+                Err(e) => {
+                    warn!("Error for {}: {}", signer.pubkey(), e);
+                    summary.failed += 1;
+                    if fail_fast {
+                        summary.fail(&format!("claim failed for {}: {}", signer.pubkey(), e));
+                    }
+                    continue;
+                }
+            };
+            let rewards = proof.claimable_rewards;
+            if rewards == 0 || totals.below_minimum(rewards, min_claim_amount) {
+                summary.skipped += 1;
+                continue;
+            }
+            let amount = amount.unwrap_or(rewards);
+            if amount > rewards {
+                info!(
+                    "Requested {} ORE but only {} ORE is claimable by {}.",
+                    format_ore(amount, decimals),
+                    format_ore(rewards, decimals),
+                    signer.pubkey()
+                );
+                summary.failed += 1;
+                if fail_fast {
+                    summary.fail(&format!("claim amount exceeds rewards for {}", signer.pubkey()));
+                }
+                continue;
+            }
+            claims.push((signer, amount));
+        }
+        info!(
+            "Claiming for {} wallets in {} transactions",
+            claims.len(),
+            claims.len().div_ceil(CLAIM_BATCH_SIZE)
+        );
+
+        totals.with_rewards = claims.len() as u64;
+        for batch in claims.chunks(CLAIM_BATCH_SIZE) {
+            let payer = batch[0].0;
+            let signers: Vec<&Keypair> = batch.iter().map(|(signer, _)| *signer).collect();
+            let cu_limit = CU_LIMIT_CLAIM * batch.len() as u32;
+            priority_fee = refresh_priority_fee(rpc_client, priority_fee).await;
+            let fee = estimate_tx_fee(batch.len() as u64, cu_limit, priority_fee);
+            if let Err(e) = tx_settings
+                .check_sol_reserve(rpc_client, &payer.pubkey(), fee)
+                .await
+            {
+                info!("Skipping {} claims paid by {}: {}", batch.len(), payer.pubkey(), e);
+                summary.skipped += batch.len() as u64;
+                continue;
+            }
+
+            let mut ixs = vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(cu_limit),
+                ComputeBudgetInstruction::set_compute_unit_price(priority_fee.cu_price(cu_limit)),
+            ];
+            ixs.extend(batch.iter().map(|(signer, amount)| {
+                ore::instruction::claim(signer.pubkey(), token_account, *amount)
+            }));
+            let mut tx = Transaction::new_with_payer(&ixs, Some(&payer.pubkey()));
+            let (hash, last_valid_blockheight) =
+                match BlockhashCache::latest_blockhash(rpc_client).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        warn!("{}", e);
+                        summary.failed += batch.len() as u64;
+                        if fail_fast {
+                            summary.fail(&format!(
+                                "claim failed for {}: {}",
+                                payer.pubkey(),
+                                e
+                            ));
+                        }
+                        continue;
+                    }
+                };
+            tx.sign(&signers, hash);
+
+            info!("Submitting {} claims paid by {}...", batch.len(), payer.pubkey());
+            let send_cfg = RpcSendTransactionConfig {
+                skip_preflight: true,
+                preflight_commitment: Some(CommitmentLevel::Confirmed),
+                encoding: Some(UiTransactionEncoding::Base64),
+                max_retries: None,
+                min_context_slot: None,
+            };
+            let result = MinerV2::send_and_confirm_transaction(
+                tx_settings,
+                tx,
+                last_valid_blockheight,
+                send_interval,
+                send_cfg,
+            )
+            .await;
+            match result {
+                Ok((sig, tx_time_elapsed)) => {
+                    info!("Success: {}", sig);
+                    info!("Took: {} seconds", tx_time_elapsed);
+                    summary.success += batch.len() as u64;
+                    summary.lamports_spent += fee;
+                    summary.tx_seconds += tx_time_elapsed;
+                    for (signer, amount) in batch {
+                        summary.ore_claimed += amount;
+                        totals.claimed += amount;
+                        if let Some(ledger) = ledger.as_deref_mut() {
+                            let record = ClaimRecord::new(
+                                rpc_client,
+                                signer.pubkey(),
+                                *amount,
+                                token_account,
+                                sig,
+                            )
+                            .await;
+                            if let Err(e) = ledger.append(&record) {
+                                warn!("{}", e);
+                                if fail_fast {
+                                    summary.fail(&e);
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("Error: {}", e);
+                    summary.failed += batch.len() as u64;
+                    if fail_fast {
+                        summary.fail(&format!("claim failed for {}: {}", payer.pubkey(), e));
+                    }
+                }
+            }
+        }
+        totals
+    }
+
+    /// Errors with why mining stopped early, e.g. `--fail-fast` or a failed pipeline task,
+    /// once the pipeline has shut down and the summary holds the session's results.
+    pub async fn mine(
+        rpc_client: Arc<RpcClient>,
+        config: MineV2Config,
+        summary: &mut Summary,
+    ) -> Result<(), String> {
+        let MineV2Config {
+            threads,
+            send_interval,
+            batch_size,
+            batch_timeout_secs,
+            wallet_source,
+            priority_fee,
+            sim_attempts,
+            simulate_only,
+            jito,
+            fee_payer,
+            decimals,
+            fail_fast,
+            progress,
+            max_fee_payer_spend,
+            rounds,
+            token_program,
+            report,
+            retry_failed,
+            min_ore_per_sol,
+            queue_file,
+            stats_file,
+            csv_file,
+            max_register_attempts,
+            auto_topup,
+            threads_per_wallet,
+            overcommit,
+            bus_strategy,
+            webhook_failures,
+            claim_threshold,
+            claim_beneficiary,
+            claim_check_interval,
+            heartbeat_interval,
+            stall_timeout,
+            exit_on_stall,
+            tx_settings,
+        } = config;
+        info!("MinerV2 Running...");
+        BlockhashCache::start(rpc_client.clone(), tx_settings.rpc_limit.clone());
+        let (wallet_queue_sender, mut wallet_queue_reader): (
+            mpsc::Sender<WalletQueueMessage>,
+            mpsc::Receiver<WalletQueueMessage>,
+        ) = tokio::sync::mpsc::channel(WALLET_QUEUE_SIZE);
+        let (tx_queue_sender, mut tx_queue_reader): (
+            mpsc::Sender<TransactionQueueMessage>,
+            mpsc::Receiver<TransactionQueueMessage>,
+        ) = tokio::sync::mpsc::channel(100);
+        let (tx_results_sender, mut tx_results_reader): (
+            mpsc::Sender<TransactionResultMessage>,
+            mpsc::Receiver<TransactionResultMessage>,
+        ) = tokio::sync::mpsc::channel(100);
+
+        if let Some(fee_payer) = &fee_payer {
+            info!("Found fee_payer: {}", fee_payer.pubkey());
+        }
+
+        let Some(wallet_source) = wallet_source else {
+            summary.fail("no miner wallets supplied. Pass a directory of keypair files with `--miner-wallets <DIR>`, keys can be created with `solana-keygen new -o <DIR>/id.json`, or a seed phrase with `--mnemonic-file <FILE> --derivation-count <N>`");
+        };
+        info!("Reading wallets");
+        let opts = LoadKeypairsOptions { strict: fail_fast };
+        let mut key_strings: Vec<String> = match wallet_source.load(&opts).await {
+            Ok(keypairs) => keypairs
+                .iter()
+                .map(|(_, signer)| signer.to_base58_string())
+                .collect(),
+            Err(e) => {
+                error!("{}", e);
+                summary.failed += 1;
+                return Ok(());
+            }
+        };
+        if let Some(retry_failed) = retry_failed {
+            let failed_wallets = match MineReport::read(&retry_failed) {
+                Ok(report) => report.failed_wallets(),
+                Err(e) => {
+                    error!("{}", e);
+                    summary.failed += 1;
+                    return Ok(());
+                }
+            };
+            key_strings.retain(|key| {
+                failed_wallets.contains(&Keypair::from_base58_string(key).pubkey().to_string())
+            });
+            info!(
+                "Retrying {} failed wallets from {}",
+                key_strings.len(),
+                retry_failed
+            );
+            if key_strings.is_empty() {
+                info!("No failed wallets to retry.");
+                summary.nothing_to_do = true;
+                return Ok(());
+            }
+        }
+        let claim_beneficiary = match claim_beneficiary.map(|address| Pubkey::from_str(&address)) {
+            Some(Ok(beneficiary)) => Some(beneficiary),
+            Some(Err(e)) => summary.fail(&format!("invalid --claim-beneficiary: {}", e)),
+            None => None,
+        };
+        let mine_queue = match queue_file {
+            Some(path) => match MineQueue::open(&path, key_strings.len()) {
+                Ok(queue) => {
+                    key_strings.sort_by_cached_key(|key| {
+                        queue.resume_rank(&Keypair::from_base58_string(key).pubkey().to_string())
+                    });
+                    Some(Arc::new(queue))
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    summary.failed += 1;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        let (mut stats_file, prior_stats) = match stats_file.as_deref().map(StatsFile::open) {
+            Some(Ok((stats_file, records))) => (Some(stats_file), records),
+            Some(Err(e)) => {
+                error!("{}", e);
+                summary.failed += 1;
+                return Ok(());
+            }
+            None => (None, vec![]),
+        };
+        let mut csv_file = match csv_file.as_deref().map(MineCsv::open) {
+            Some(Ok(csv_file)) => Some(csv_file),
+            Some(Err(e)) => {
+                error!("{}", e);
+                summary.failed += 1;
+                return Ok(());
+            }
+            None => None,
+        };
+
+
+        // tokio spawn threads
+        // wallet queue reader thread
+        // Every pipeline task runs until aborted, so the first one to finish has failed
+        let mut tasks = JoinSet::new();
+        let rpc_client_0 = rpc_client.clone();
+        let batch_fee_payer = fee_payer.as_ref().map(|fee_payer| fee_payer.pubkey());
+        let batch_jito = jito.clone();
+        // The thread budget is split between the wallets of a batch below
+        if threads == 0 {
+            summary.fail("--threads must be at least 1");
+        }
+        if let Some(threads_per_wallet) = threads_per_wallet {
+            if threads_per_wallet == 0 || threads_per_wallet > threads {
+                summary.fail(&format!(
+                    "--threads-per-wallet {} must be between 1 and the --threads budget of {}",
+                    threads_per_wallet, threads
+                ));
+            }
+        }
+        let batch_size = if batch_size > 5 { 5 } else { batch_size };
+        // With --overcommit every wallet in a batch gets all of --threads, so the pool needs
+        // enough workers to run the whole batch at once
+        let pool_threads = if overcommit {
+            threads * batch_size.max(1)
+        } else {
+            threads
+        };
+        let hash_pool = HashPool::new(pool_threads);
+        // Wallets still being mined, drops as wallets finish their --rounds
+        let active_wallets = Arc::new(AtomicUsize::new(key_strings.len()));
+        metrics::set_active_wallets(key_strings.len());
+        let batch_active_wallets = active_wallets.clone();
+        let batch_mine_queue = mine_queue.clone();
+        // Set by the first Ctrl-C, stops new wallets from being queued or hashed
+        let stopping = Arc::new(AtomicBool::new(false));
+        let batch_stopping = stopping.clone();
+        // Transactions sent to the tx processor that haven't produced a result yet
+        let in_flight_txs = Arc::new(AtomicUsize::new(0));
+        let batch_in_flight_txs = in_flight_txs.clone();
+        let batch_wallet_queue_sender = wallet_queue_sender.clone();
+        let mut auto_topup = auto_topup;
+        let batch_tx_settings = tx_settings.clone();
+        tasks.spawn(pipeline_task("batch collector", async move {
+            let rpc_client = rpc_client_0.clone();
+            let mut priority_fee = priority_fee;
+            let mut wallet_batch = vec![];
+            // When the last wallet joined the batch, a partial batch goes out once no other
+            // wallet joins it within --batch-timeout-secs
+            let mut last_arrival = Instant::now();
+
+            let mut bus = 0;
+            loop {
+                if batch_stopping.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(500)).await;
+                    continue;
+                }
+                // Time out so a partial batch still goes out once fewer wallets are active
+                // than the batch size
+                if let Ok(Some(mssg)) =
+                    tokio::time::timeout(Duration::from_millis(500), wallet_queue_reader.recv())
+                        .await
+                {
+                    wallet_batch.push(mssg.wallet);
+                    last_arrival = Instant::now();
+                }
+                let active = batch_active_wallets.load(Ordering::Relaxed) as u64;
+                let batch_full = wallet_batch.len() as u64 >= batch_size.min(active);
+                let batch_timed_out =
+                    last_arrival.elapsed() >= Duration::from_secs(batch_timeout_secs);
+                // TODO: start processing hash here, so when 5th wallet
+                // comes in and hash finishes it can be sent off right away.
+                if !wallet_batch.is_empty() && (batch_full || batch_timed_out) {
+                    // Wallets left over from this batch wait a full timeout for new company
+                    last_arrival = Instant::now();
+                    let mut keys_bytes_with_hashes = Vec::new();
+                    let mut unsolved_wallets = vec![];
+                    info!("Got {} wallets, hashing...", wallet_batch.len());
+                    let hash_timer = SystemTime::now();
+                    let treasury = match get_treasury(&rpc_client).await {
+                        Ok(treasury) => treasury,
+                        Err(e) => {
+                            warn!("{}, retrying...", e);
+                            sleep(Duration::from_millis(1000)).await;
+                            continue;
+                        }
+                    };
+
+                    let mut challenges = vec![];
+                    for wallet in wallet_batch.clone() {
+                        let signer = Keypair::from_base58_string(&wallet);
+                        // Don't hash for a wallet that can't pay for its own transaction.
+                        // Nothing to check when a --fee-payer pays or --auto-topup refills
+                        // wallets before sending.
+                        if batch_fee_payer.is_none() && auto_topup.is_none() {
+                            let fee = estimate_tx_fee(1, CU_LIMIT_MINE, priority_fee);
+                            let balance = match rpc_client.get_balance(&signer.pubkey()).await {
+                                Ok(balance) => balance,
+                                Err(e) => {
+                                    warn!(
+                                        "Failed to load SOL balance for wallet {}: {}, will retry in the next batch.",
+                                        signer.pubkey(),
+                                        e
+                                    );
+                                    unsolved_wallets.push(wallet);
+                                    continue;
+                                }
+                            };
+                            let low_sol = if balance < fee {
+                                Some(format!(
+                                    "it has {} SOL, less than the {} SOL fee",
+                                    lamports_to_sol(balance),
+                                    lamports_to_sol(fee)
+                                ))
+                            } else {
+                                batch_tx_settings.keeps_sol_reserve(balance, fee).err()
+                            };
+                            if let Some(reason) = low_sol {
+                                warn!(
+                                    "WARNING: skipping wallet {}, {}. Requeueing it in {} seconds.",
+                                    signer.pubkey(),
+                                    reason,
+                                    LOW_SOL_REQUEUE_DELAY.as_secs()
+                                );
+                                let wallet_queue = batch_wallet_queue_sender.clone();
+                                tokio::spawn(async move {
+                                    sleep(LOW_SOL_REQUEUE_DELAY).await;
+                                    let _ = wallet_queue.send(WalletQueueMessage { wallet }).await;
+                                });
+                                continue;
+                            }
+                        }
+                        // Registering costs SOL, which --simulate-only never spends
+                        let registered = if simulate_only {
+                            Ok(())
                         } else {
-                            Keypair::from_base58_string(&mssg.wallets[0])
+                            MinerV2::register(
+                                rpc_client.clone(),
+                                &batch_tx_settings,
+                                &signer,
+                                send_interval,
+                                priority_fee,
+                                max_register_attempts,
+                            )
+                            .await
                         };
-                        let balance = rpc_client.get_balance(&fee_payer.pubkey()).await;
-
-                        if let Ok(balance) = balance {
-                            println!("Fee Payer: {}", fee_payer.pubkey());
-                            println!("Fee Payer balance: {}", (balance as f64) / (LAMPORTS_PER_SOL as f64));
-                            if balance < 300_000 {
-                                println!("Fee bayer balance is too low. Transaction may fail.");
-                                println!("Please fund fee payer.");
-                                println!("Fee Payer: {}", fee_payer.pubkey());
-                                println!("Fee Payer balance: {}", (balance as f64) / (LAMPORTS_PER_SOL as f64));
+                        if let Err(e) = registered {
+                            warn!(
+                                "WARNING: failed to register wallet {}: {}. Requeueing it in {} seconds.",
+                                signer.pubkey(),
+                                e,
+                                REGISTER_REQUEUE_DELAY.as_secs()
+                            );
+                            let wallet_queue = batch_wallet_queue_sender.clone();
+                            tokio::spawn(async move {
+                                sleep(REGISTER_REQUEUE_DELAY).await;
+                                let _ = wallet_queue.send(WalletQueueMessage { wallet }).await;
+                            });
+                            continue;
+                        }
+                        let proof = match get_proof_v2(&rpc_client, signer.pubkey()).await {
+                            Ok(proof) => proof,
+                            Err(e) if simulate_only => {
+                                info!(
+                                    "{} for wallet {}, dropping it. --simulate-only doesn't register wallets.",
+                                    e,
+                                    signer.pubkey()
+                                );
+                                continue;
+                            }
+                            Err(e) => {
+                                info!("{} for wallet {}, will retry in the next batch.", e, signer.pubkey());
+                                unsolved_wallets.push(wallet);
+                                continue;
                             }
+                        };
+                        //let rewards =
+                        //    (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+                        let proof_hash = proof.hash;
+                        challenges.push((wallet, signer.pubkey(), proof_hash));
+                    }
+
+                    // Hash the whole batch at once, splitting --threads between the wallets
+                    // unless --threads-per-wallet or --overcommit say otherwise
+                    let batch_len = challenges.len().max(1) as u64;
+                    let (threads_per_wallet, hash_concurrency) = match threads_per_wallet {
+                        Some(threads_per_wallet) => {
+                            (threads_per_wallet, threads / threads_per_wallet)
+                        }
+                        None if overcommit => (threads, batch_len),
+                        None => {
+                            let concurrency = batch_len.min(threads);
+                            (threads / concurrency, concurrency)
+                        }
+                    };
+                    let hash_concurrency = hash_concurrency as usize;
+                    info!(
+                        "Hashing up to {} wallets at once with {} threads each",
+                        hash_concurrency, threads_per_wallet
+                    );
+                    let difficulty = treasury.difficulty;
+                    info!(
+                        "Difficulty: {} leading zero bits",
+                        difficulty_zero_bits(difficulty.as_ref())
+                    );
+                    let output = if hash_concurrency > 1 {
+                        // Concurrent searches would write over each other's output
+                        HashOutput::Quiet
+                    } else if progress {
+                        HashOutput::Progress
+                    } else if log_enabled!(Level::Trace) {
+                        // Scrolling every hash is only shown at `--log-level trace`
+                        HashOutput::Scroll
+                    } else {
+                        HashOutput::Quiet
+                    };
+                    let hash_pool = &hash_pool;
+                    let solutions: Vec<_> = futures::stream::iter(challenges.into_iter().map(
+                        |(wallet, pubkey, proof_hash)| async move {
+                            info!("\nStarting hash for wallet {}", pubkey);
+                            let (solution, hashes) = hash_pool
+                                .search_split(
+                                    pubkey,
+                                    proof_hash,
+                                    difficulty,
+                                    output,
+                                    threads_per_wallet,
+                                )
+                                .await;
+                            metrics::add_hashes(hashes);
+                            (wallet, pubkey, proof_hash, solution)
+                        },
+                    ))
+                    .buffered(hash_concurrency)
+                    .collect()
+                    .await;
+                    for (wallet, pubkey, proof_hash, solution) in solutions {
+                        match solution {
+                            // Never spend a tx on a solution the program would reject
+                            Some((next_hash, nonce)) => {
+                                if validate_hash(next_hash, proof_hash, pubkey, nonce, difficulty) {
+                                    keys_bytes_with_hashes.push((wallet, next_hash, nonce));
+                                } else {
+                                    warn!(
+                                        "\nInvalid solution for wallet {}, will re-hash in the next batch.",
+                                        pubkey
+                                    );
+                                    unsolved_wallets.push(wallet);
+                                }
+                            }
+                            None => {
+                                info!(
+                                    "\nNo solution found for wallet {}, will re-hash in the next batch.",
+                                    pubkey
+                                );
+                                unsolved_wallets.push(wallet);
+                            }
+                        }
+                    }
+                    let hash_time = hash_timer.elapsed().unwrap().as_secs();
+
+                    if keys_bytes_with_hashes.is_empty() {
+                        wallet_batch = unsolved_wallets;
+                        sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                    info!("\nHashing complete.");
+                    debug!("Building transaction...");
+                    // Reset epoch, if needed
+                    //let treasury = get_treasury(&rpc_client).await;
+                    //let clock = get_clock(&rpc_client).await;
+                    // can't use thread_rng() across thread safetly
+                    //let mut rng = rand::thread_rng();
+
+                    //if clock.unix_timestamp.ge(&epoch_end(&treasury)) {
+                    //    // There are a lot of miners right now, so randomly select into submitting tx
+                    //    if rng.gen_range(0..RESET_ODDS).eq(&0) {
+                    //        info!("Sending epoch reset transaction...");
+                    //        let signer =
+                    //            Keypair::from_base58_string(&keys_bytes_with_hashes[0].0);
+                    //        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(
+                    //            CU_LIMIT_RESET,
+                    //        );
+                    //        let cu_price_ix =
+                    //            ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
+                    //        let reset_ix = ore::instruction::reset(signer.pubkey());
+                    //        MinerV2::send_and_confirm(
+                    //            &signer,
+                    //            rpc_client.clone(),
+                    //            &[cu_limit_ix, cu_price_ix, reset_ix],
+                    //            false,
+                    //            send_interval,
+                    //            priority_fee,
+                    //        )
+                    //        .await
+                    //        .ok();
+                    //    }
+                    //}
+                    // Spread the wallets across the busses with --bus-strategy, re-checked
+                    // each batch
+                    let busses = match MinerV2::get_busses(&rpc_client).await {
+                        Ok(busses) => busses,
+                        Err(e) => {
+                            warn!("Failed to load busses: {}", e);
+                            vec![]
+                        }
+                    };
+                    let bus_ids = bus_strategy.assign(
+                        &busses,
+                        treasury.reward_rate,
+                        keys_bytes_with_hashes.len(),
+                        bus,
+                    );
+                    let mut bus_distribution = [0usize; BUS_COUNT];
+                    let mut mine_ixs = vec![];
+                    for ((key_bytes, next_hash, nonce), bus_id) in
+                        keys_bytes_with_hashes.iter().zip(bus_ids.iter().copied())
+                    {
+                        let signer = Keypair::from_base58_string(key_bytes);
+                        bus_distribution[bus_id] += 1;
+                        mine_ixs.push(ore::instruction::mine(
+                            signer.pubkey(),
+                            BUS_ADDRESSES[bus_id],
+                            (*next_hash).into(),
+                            *nonce,
+                        ));
+                    }
+                    let distribution: Vec<String> = bus_distribution
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, count)| **count > 0)
+                        .map(|(id, count)| format!("bus {}: {}", id, count))
+                        .collect();
+                    info!(
+                        "Bus distribution ({:?}): {}",
+                        bus_strategy,
+                        distribution.join(", ")
+                    );
+
+                    // Refill whoever pays for this batch before sending it. Nothing is sent
+                    // with --simulate-only.
+                    if let Some(auto_topup) = auto_topup.as_mut().filter(|_| !simulate_only) {
+                        let payers: Vec<Pubkey> = match batch_fee_payer {
+                            Some(fee_payer) => vec![fee_payer],
+                            None => keys_bytes_with_hashes
+                                .iter()
+                                .map(|(wallet, _, _)| Keypair::from_base58_string(wallet).pubkey())
+                                .collect(),
+                        };
+                        for payer in payers {
+                            auto_topup
+                                .check(&rpc_client, &batch_tx_settings, payer, send_interval)
+                                .await;
+                        }
+                    }
+
+                    priority_fee = refresh_priority_fee(&rpc_client, priority_fee).await;
+
+                    // Build one tx for the whole batch, halving it until every tx fits in a
+                    // packet once signed. The unsigned tx already holds a placeholder for
+                    // each required signature, so its serialized size is the signed size.
+                    let mut groups = vec![(0, keys_bytes_with_hashes.len())];
+                    let mut txs = vec![];
+                    while let Some((start, end)) = groups.pop() {
+                        let group = &keys_bytes_with_hashes[start..end];
+                        let fee_payer = match batch_fee_payer {
+                            Some(fee_payer) => fee_payer,
+                            None => Keypair::from_base58_string(&group[0].0).pubkey(),
+                        };
+                        let mut ixs = vec![
+                            ComputeBudgetInstruction::set_compute_unit_limit(
+                                CU_LIMIT_MINE * group.len() as u32,
+                            ),
+                            ComputeBudgetInstruction::set_compute_unit_price(
+                                priority_fee.cu_price(CU_LIMIT_MINE * group.len() as u32),
+                            ),
+                        ];
+                        ixs.extend_from_slice(&mine_ixs[start..end]);
+                        if let Some(jito) = batch_jito.as_ref() {
+                            ixs.push(jito.tip_ix(&fee_payer));
+                        }
+                        let tx = Transaction::new_with_payer(ixs.as_slice(), Some(&fee_payer));
+                        let serialized_tx = bincode::serialize(&tx).unwrap();
+                        if serialized_tx.len() > PACKET_DATA_SIZE && group.len() > 1 {
+                            info!(
+                                "Transaction for {} wallets is {} bytes, over the {} byte limit. Splitting batch.",
+                                group.len(),
+                                serialized_tx.len(),
+                                PACKET_DATA_SIZE
+                            );
+                            let mid = start + group.len() / 2;
+                            groups.push((mid, end));
+                            groups.push((start, mid));
+                            continue;
+                        }
+                        txs.push((group, &bus_ids[start..end], serialized_tx));
+                    }
+
+                    // --simulate-only reports what each tx would do instead of sending it,
+                    // then puts its wallets back on the queue for the next batch
+                    if simulate_only {
+                        for (group, _, serialized_tx) in txs {
+                            let tx: Transaction = bincode::deserialize(&serialized_tx).unwrap();
+                            let sim_res = batch_tx_settings
+                                .simulate_transaction(
+                                    &rpc_client,
+                                    &tx,
+                                    RpcSimulateTransactionConfig {
+                                        sig_verify: false,
+                                        replace_recent_blockhash: true,
+                                        commitment: Some(rpc_client.commitment()),
+                                        encoding: Some(UiTransactionEncoding::Base64),
+                                        accounts: None,
+                                        min_context_slot: None,
+                                        inner_instructions: false,
+                                    },
+                                )
+                                .await;
+                            match sim_res {
+                                Ok(sim_res) => {
+                                    info!(
+                                        "Simulated tx for {} wallets: {} of {} CUs used",
+                                        group.len(),
+                                        sim_res.value.units_consumed.unwrap_or(0),
+                                        CU_LIMIT_MINE * group.len() as u32
+                                    );
+                                    if let Some(err) = sim_res.value.err {
+                                        warn!("Simulaton error: {:?}", err);
+                                        for log in sim_res.value.logs.unwrap_or_default() {
+                                            warn!("  {}", log);
+                                        }
+                                    } else {
+                                        debug!("Simulaton successful.");
+                                    }
+                                }
+                                Err(err) => {
+                                    warn!("Simulaton error: {:?}", err);
+                                }
+                            }
+                            for (wallet, _, _) in group {
+                                let wallet_queue = batch_wallet_queue_sender.clone();
+                                let wallet = wallet.clone();
+                                tokio::spawn(async move {
+                                    let _ = wallet_queue.send(WalletQueueMessage { wallet }).await;
+                                });
+                            }
+                        }
+                        wallet_batch = unsolved_wallets;
+                        sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+
+                    for (group, group_bus_ids, serialized_tx) in txs {
+                        debug!("Sending unsigned tx to queue...");
+                        if let Some(queue) = batch_mine_queue.as_ref() {
+                            queue.in_flight(
+                                group
+                                    .iter()
+                                    .map(|(wallet, _, _)| {
+                                        Keypair::from_base58_string(wallet).pubkey().to_string()
+                                    })
+                                    .collect(),
+                            );
+                        }
+                        let encoded_unsigned_tx = BASE64.encode(serialized_tx);
+                        let tqm = TransactionQueueMessage {
+                            wallets: group.iter().map(|(wallet, _, _)| wallet.clone()).collect(),
+                            bus_ids: group_bus_ids.to_vec(),
+                            nonces: group.iter().map(|(_, _, nonce)| *nonce).collect(),
+                            encoded_unsigned_tx,
+                            hash_time_elapsed: hash_time,
+                            expected_rewards: treasury.reward_rate * group.len() as u64,
+                            priority_fee,
+                        };
+                        batch_in_flight_txs.fetch_add(1, Ordering::Relaxed);
+                        if let Ok(_) = tx_queue_sender.send(tqm).await {
+                            debug!("Sent tx to be processed.");
                         } else {
-                            println!("Failed to load fee payer balance, transaction may fail.");
+                            batch_in_flight_txs.fetch_sub(1, Ordering::Relaxed);
+                            error!(
+                                "Failed to send tx to be processed. Tx Queue full? Dev help pls."
+                            );
                         }
+                    }
+                    wallet_batch = unsolved_wallets;
+                } else {
+                }
 
-                        println!("Signing tx...");
+                bus += 1;
 
+                if bus >= BUS_COUNT {
+                    bus = 0;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        }));
+
+        let shared_summary = Arc::new(Mutex::new(*summary));
+        // Fires through `stop_mining` once every wallet finished its --rounds or mining became
+        // unprofitable, or with the failure once --max-fee-payer-spend is reached or a tx fails
+        // with --fail-fast
+        let (stop_sender, stop_receiver) = tokio::sync::oneshot::channel::<Option<String>>();
+
+        // tx queue processor thread
+        let rpc_client_1 = rpc_client.clone();
+        let fee_payer_2 = fee_payer.clone();
+        let processor_jito = jito.clone();
+        let processor_tx_settings = tx_settings.clone();
+        tasks.spawn(pipeline_task("tx processor", async move {
+            let rpc_client = rpc_client_1.clone();
+            // fee payers the low-balance webhook already went out for
+            let mut low_balance_notified = HashSet::new();
+            loop {
+                if let Some(mssg) = tx_queue_reader.recv().await {
+                    // A bad tx must not take down the processor, its wallets would never be
+                    // requeued
+                    let mut tx = match decode_tx(&mssg.encoded_unsigned_tx) {
+                        Ok(tx) => tx,
+                        Err(e) => {
+                            warn!("Failed to decode tx: {}, requeueing wallets.", e);
+                            if tx_results_sender
+                                .send(TransactionResultMessage::dropped(
+                                    &mssg,
+                                    format!("failed to decode tx: {}", e),
+                                ))
+                                .await
+                                .is_err()
+                            {
+                                error!(
+                                    "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                );
+                            }
+                            continue;
+                        }
+                    };
 
-                        tx.partial_sign(&[&fee_payer], hash);
+                    // Simulate before sending with --sim-attempts, dropping the batch if it
+                    // never simulates cleanly. Without it the tx is sent with skip_preflight.
+                    if let Some(sim_attempts) = sim_attempts {
+                        let mut sim_error = None;
+                        for attempt in 1..=sim_attempts {
+                            let sim_res = processor_tx_settings
+                                .simulate_transaction(
+                                    &rpc_client,
+                                    &tx,
+                                    RpcSimulateTransactionConfig {
+                                        sig_verify: false,
+                                        replace_recent_blockhash: true,
+                                        commitment: Some(rpc_client.commitment()),
+                                        encoding: Some(UiTransactionEncoding::Base64),
+                                        accounts: None,
+                                        min_context_slot: None,
+                                        inner_instructions: false,
+                                    },
+                                )
+                                .await;
+                            let err = match sim_res {
+                                Ok(sim_res) => match sim_res.value.err {
+                                    Some(err) => format!("{:?}", err),
+                                    None => {
+                                        debug!("Simulaton successful.");
+                                        sim_error = None;
+                                        break;
+                                    }
+                                },
+                                Err(err) => err.to_string(),
+                            };
+                            warn!(
+                                "Simulaton error (attempt {} of {}): {}",
+                                attempt, sim_attempts, err
+                            );
+                            sim_error = Some(err);
+                        }
+                        if let Some(err) = sim_error {
+                            info!("Simulation kept failing, dropping the batch.");
+                            if tx_results_sender
+                                .send(TransactionResultMessage::dropped(
+                                    &mssg,
+                                    format!("simulation failed: {}", err),
+                                ))
+                                .await
+                                .is_err()
+                            {
+                                error!(
+                                    "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                );
+                            }
+                            continue;
+                        }
+                    }
 
-                        for wallet in &wallets {
-                            let keypair = Keypair::from_base58_string(wallet);
+                    let (hash, last_valid_blockheight) =
+                        match BlockhashCache::latest_blockhash(&rpc_client).await {
+                            Ok(res) => res,
+                            Err(e) => {
+                                warn!("{}, requeueing wallets.", e);
+                                if tx_results_sender
+                                    .send(TransactionResultMessage::dropped(&mssg, e.to_string()))
+                                    .await
+                                    .is_err()
+                                {
+                                    error!(
+                                        "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                    );
+                                }
+                                continue;
+                            }
+                        };
+
+                    let wallets = mssg.wallets.clone();
+                    let fee_payer = match &fee_payer_2 {
+                        Some(fee_payer) => fee_payer.clone(),
+                        None => FeePayer::new(Keypair::from_base58_string(&mssg.wallets[0])),
+                    };
+                    let balance = rpc_client.get_balance(&fee_payer.pubkey()).await;
+
+                    if let Ok(balance) = balance {
+                        debug!("Fee Payer: {}", fee_payer.pubkey());
+                        debug!("Fee Payer balance: {}", (balance as f64) / (LAMPORTS_PER_SOL as f64));
+                        if balance < 300_000 {
+                            if low_balance_notified.insert(fee_payer.pubkey()) {
+                                Webhook::notify(
+                                    WebhookEvent::LowBalance,
+                                    json!({
+                                        "fee_payer": fee_payer.pubkey().to_string(),
+                                        "lamports": balance,
+                                    }),
+                                );
+                            }
+                            warn!("Fee bayer balance is too low. Transaction may fail.");
+                            info!("Please fund fee payer.");
+                            debug!("Fee Payer: {}", fee_payer.pubkey());
+                            debug!("Fee Payer balance: {}", (balance as f64) / (LAMPORTS_PER_SOL as f64));
+                        }
+                    } else {
+                        warn!("Failed to load fee payer balance, transaction may fail.");
+                    }
+
+                    // Paid once the tx lands, the tip only if it also succeeds
+                    let fee = estimate_tx_fee(
+                        wallets.len() as u64,
+                        CU_LIMIT_MINE * wallets.len() as u32,
+                        mssg.priority_fee,
+                    );
+                    let tip = processor_jito.as_ref().map_or(0, |jito| jito.tip_lamports());
+                    if let Ok(balance) = balance {
+                        if let Err(e) = processor_tx_settings.keeps_sol_reserve(balance, fee + tip) {
+                            info!("Skipping tx, fee payer {}: {}", fee_payer.pubkey(), e);
+                            if tx_results_sender
+                                .send(TransactionResultMessage::dropped(&mssg, e))
+                                .await
+                                .is_err()
+                            {
+                                error!(
+                                    "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                );
+                            }
+                            continue;
+                        }
+                    }
+
+                    debug!("Signing tx...");
+
+
+                    let mut sign_errors = vec![];
+                    if let Err(e) = tx.try_partial_sign(&[&fee_payer], hash) {
+                        sign_errors.push(format!("fee payer {}: {}", fee_payer.pubkey(), e));
+                    }
+
+                    // Wallets that decoded and signed, these are requeued if the tx is dropped
+                    let mut signed_wallets = vec![];
+                    let mut signed_bus_ids = vec![];
+                    let mut signed_nonces = vec![];
+                    for (i, wallet) in wallets.iter().enumerate() {
+                        let keypair = match bs58::decode(wallet)
+                            .into_vec()
+                            .ok()
+                            .and_then(|bytes| Keypair::from_bytes(&bytes).ok())
+                        {
+                            Some(keypair) => keypair,
+                            None => {
+                                sign_errors.push("a wallet key could not be decoded".to_string());
+                                continue;
+                            }
+                        };
 
-                            if keypair.pubkey() == fee_payer.pubkey() {
+                        if keypair.pubkey() != fee_payer.pubkey() {
+                            if let Err(e) = tx.try_partial_sign(&[&keypair], hash) {
+                                sign_errors.push(format!("{}: {}", keypair.pubkey(), e));
                                 continue;
+                            }
+                        }
+                        signed_wallets.push(wallet.clone());
+                        signed_bus_ids.push(mssg.bus_ids[i]);
+                        signed_nonces.push(mssg.nonces[i]);
+                    }
+
+                    // Never send a tx that is missing a required signature
+                    let num_signers = tx.message.header.num_required_signatures as usize;
+                    let missing_signers: Vec<Pubkey> = tx.message.account_keys[..num_signers]
+                        .iter()
+                        .zip(tx.signatures.iter())
+                        .filter(|(_, sig)| **sig == Signature::default())
+                        .map(|(pubkey, _)| *pubkey)
+                        .collect();
+                    if !missing_signers.is_empty() || !sign_errors.is_empty() {
+                        for e in sign_errors.iter() {
+                            warn!("Signing error: {}", e);
+                        }
+                        for pubkey in missing_signers.iter() {
+                            info!("Missing signature for signer {}", pubkey);
+                        }
+                        warn!("Skipping transaction, requeueing the wallets that signed.");
+                        let error = format!(
+                            "missing signers: {}",
+                            missing_signers
+                                .iter()
+                                .map(|pubkey| pubkey.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                        if tx_results_sender
+                            .send(TransactionResultMessage {
+                                wallets: signed_wallets,
+                                bus_ids: signed_bus_ids,
+                                nonces: signed_nonces,
+                                sig: "failed".to_string(),
+                                tx_time_elapsed: 0,
+                                hash_time_elapsed: mssg.hash_time_elapsed,
+                                expected_rewards: mssg.expected_rewards,
+                                failed: true,
+                                error: Some(error),
+                                fee_paid: 0,
+                            })
+                            .await
+                            .is_err()
+                        {
+                            error!(
+                                "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                            );
+                        }
+                        continue;
+                    }
+
+                    let result = match processor_jito.as_ref() {
+                        Some(jito) => {
+                            info!("Sending tx as a Jito bundle until it lands or blockhash expires.");
+                            jito.send_and_confirm(
+                                &rpc_client,
+                                &tx,
+                                last_valid_blockheight,
+                                processor_tx_settings.confirm_timeout,
+                            )
+                            .await
+                        }
+                        None => {
+                            info!("Sending tx every {} milliseconds until confirmation or blockhash expires.", send_interval);
+                            let send_cfg = RpcSendTransactionConfig {
+                                skip_preflight: true,
+                                preflight_commitment: Some(CommitmentLevel::Confirmed),
+                                encoding: Some(UiTransactionEncoding::Base64),
+                                max_retries: None,
+                                min_context_slot: None,
+                            };
+                            MinerV2::send_and_confirm_transaction(
+                                &processor_tx_settings,
+                                tx,
+                                last_valid_blockheight,
+                                send_interval,
+                                send_cfg,
+                            )
+                            .await
+                        }
+                    };
+
+                    match result {
+                        Ok((sig, tx_time_elapsed)) => {
+                            info!("Transaction Confirmed!");
+                            if let Ok(_) = tx_results_sender
+                                .send(TransactionResultMessage {
+                                    wallets: mssg.wallets.clone(),
+                                    bus_ids: mssg.bus_ids.clone(),
+                                    nonces: mssg.nonces.clone(),
+                                    sig: sig.to_string(),
+                                    tx_time_elapsed,
+                                    hash_time_elapsed: mssg.hash_time_elapsed,
+                                    expected_rewards: mssg.expected_rewards,
+                                    failed: false,
+                                    error: None,
+                                    fee_paid: fee + tip,
+                                })
+                                .await
+                            {
+                            } else {
+                                error!(
+                                    "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Error: {}", e);
+                            // A tx that landed and failed still paid its fee
+                            let fee_paid = if e == TX_FAILED_ERROR { fee } else { 0 };
+                            if let Ok(_) = tx_results_sender
+                                .send(TransactionResultMessage {
+                                    wallets: mssg.wallets.clone(),
+                                    bus_ids: mssg.bus_ids.clone(),
+                                    nonces: mssg.nonces.clone(),
+                                    sig: "failed".to_string(),
+                                    tx_time_elapsed: 0,
+                                    hash_time_elapsed: mssg.hash_time_elapsed,
+                                    expected_rewards: mssg.expected_rewards,
+                                    failed: true,
+                                    error: Some(e),
+                                    fee_paid,
+                                })
+                                .await
+                            {
+                                debug!("Sent tx result.");
                             } else {
-                                tx.partial_sign(&[&keypair], hash);
+                                error!(
+                                    "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                );
+                            }
+                        }
+                    }
+                }
+                sleep(Duration::from_millis(500)).await;
+            }
+        }));
+
+        // tx results thread
+        let wallet_queue_sender_1 = wallet_queue_sender.clone();
+
+        let results_pubkeys: Vec<Pubkey> = key_strings.iter().map(|key_str| {
+            let key = Keypair::from_base58_string(key_str);
+            key.pubkey()
+        }).collect();
+
+        // wallet feeder thread, started before the balance summary below so the first
+        // wallets hash right away
+        let feeder_mine_queue = mine_queue.clone();
+        let feeder_stopping = stopping.clone();
+        tasks.spawn(pipeline_task("wallet feeder", async move {
+            debug!("Sending wallets to queue");
+            let mut wallets = key_strings.into_iter().peekable();
+            while wallets.peek().is_some() {
+                if feeder_stopping.load(Ordering::Relaxed) {
+                    std::future::pending::<()>().await;
+                }
+                let queued = WALLET_QUEUE_SIZE - wallet_queue_sender.capacity();
+                if queued < WALLET_QUEUE_LOW_WATER {
+                    for wallet in wallets.by_ref().take(WALLET_QUEUE_HIGH_WATER - queued) {
+                        if let Some(queue) = feeder_mine_queue.as_ref() {
+                            queue.queued(Keypair::from_base58_string(&wallet).pubkey().to_string());
+                        }
+                        let w = WalletQueueMessage { wallet };
+                        if let Err(_) = wallet_queue_sender.send(w).await {
+                            warn!("Failed to send wallet to queue.");
+                        }
+                    }
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+            info!("All wallets queued.");
+            // Every pipeline task runs until aborted
+            std::future::pending().await
+        }));
+
+        // claimable rewards per wallet, to measure what each landed tx actually mined
+        let claimable_rewards: Arc<Mutex<HashMap<Pubkey, u64>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+
+        // claim monitor thread, claims every wallet to one beneficiary once the fleet's
+        // claimable rewards cross --claim-threshold-ore
+        if let (Some(threshold), Some(beneficiary)) = (claim_threshold, claim_beneficiary) {
+            let rpc_client = rpc_client.clone();
+            let wallets = results_pubkeys.clone();
+            let wallet_source = wallet_source.clone();
+            let claim_summary = shared_summary.clone();
+            let monitor_claimable_rewards = claimable_rewards.clone();
+            let monitor_tx_settings = tx_settings.clone();
+            tasks.spawn(pipeline_task("claim monitor", async move {
+                loop {
+                    sleep(Duration::from_secs(claim_check_interval)).await;
+                    let claimable: u64 = match get_proofs(&rpc_client, &wallets).await {
+                        Ok(proofs) => proofs.iter().flatten().map(|p| p.claimable_rewards).sum(),
+                        Err(e) => {
+                            info!("Claim monitor: {}", e);
+                            continue;
+                        }
+                    };
+                    if claimable < threshold {
+                        continue;
+                    }
+                    info!(
+                        "Claimable rewards of {} ORE crossed the {} ORE threshold, claiming to {}",
+                        format_ore(claimable, decimals),
+                        format_ore(threshold, decimals),
+                        beneficiary
+                    );
+                    let mut claimed = Summary::new("claim");
+                    let config = ClaimConfig {
+                        send_interval,
+                        wallet_source: Some(wallet_source.clone()),
+                        beneficiary: Some(beneficiary.to_string()),
+                        beneficiary_owner: None,
+                        transfer_to: None,
+                        amount: None,
+                        min_claim_amount: None,
+                        priority_fee,
+                        fail_fast: false,
+                        wait_for_epoch: false,
+                        token_program,
+                        decimals,
+                        ledger: None,
+                        aggregate: false,
+                        tx_settings: monitor_tx_settings.clone(),
+                    };
+                    MinerV2::claim(rpc_client.clone(), config, &mut claimed).await;
+                    info!(
+                        "Claimed {} ORE, {} wallets failed. Resuming mining.",
+                        format_ore(claimed.ore_claimed, decimals),
+                        claimed.failed
+                    );
+                    {
+                        let mut summary = claim_summary.lock().unwrap();
+                        summary.ore_claimed += claimed.ore_claimed;
+                        summary.lamports_spent += claimed.lamports_spent;
+                    }
+                    // Claiming lowered the claimable rewards, restart the baseline the mined
+                    // amounts are measured from so the claim doesn't read as nothing mined
+                    let proofs = get_proofs(&rpc_client, &wallets).await;
+                    let mut baseline = monitor_claimable_rewards.lock().unwrap();
+                    match proofs {
+                        Ok(proofs) => {
+                            for (pubkey, proof) in wallets.iter().zip(proofs) {
+                                match proof {
+                                    Some(proof) => {
+                                        baseline.insert(*pubkey, proof.claimable_rewards);
+                                    }
+                                    None => {
+                                        baseline.remove(pubkey);
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            info!("Claim monitor: {}", e);
+                            baseline.clear();
+                        }
+                    }
+                }
+            }));
+        }
+
+        info!("Loading All wallet balances");
+
+        let response = rpc_client.get_multiple_accounts(&results_pubkeys).await;
+
+        let mut total_lamports = 0;
+        if let Ok(results) = response {
+            for res in results {
+                if let Some(acc) = res {
+                    total_lamports += acc.lamports();
+                }
+            }
+        }
+
+        info!("Total Sol: {}", lamports_to_sol(total_lamports));
+
+        info!("Loading ore balances and rewards...");
+        let mut total_ore_balance = 0;
+        let mut unknown_ore_balances = 0;
+        let mut total_ore_rewards_claimable = 0;
+        for pubkey in &results_pubkeys {
+            let pubkey = pubkey.to_owned();
+
+            sleep(Duration::from_millis(200)).await;
+            match get_ore_balance(&rpc_client, pubkey, token_program).await {
+                Ok(ore_balance) => total_ore_balance += ore_balance.unwrap_or(0),
+                Err(e) => {
+                    info!("{} for wallet {}", e, pubkey);
+                    unknown_ore_balances += 1;
+                }
+            }
+
+            sleep(Duration::from_millis(200)).await;
+            match get_proof_v2(&rpc_client, pubkey).await {
+                Ok(proof) => {
+                    total_ore_rewards_claimable += proof.claimable_rewards;
+                    claimable_rewards
+                        .lock()
+                        .unwrap()
+                        .insert(pubkey, proof.claimable_rewards);
+                }
+                Err(e) => info!("{} for wallet {}", e, pubkey),
+            }
+        }
+        if unknown_ore_balances > 0 {
+            info!(
+                "Total Ore Balance: {} (excluding {} wallets that failed to load)",
+                format_ore(total_ore_balance, decimals),
+                unknown_ore_balances
+            );
+        } else {
+            info!("Total Ore Balance: {}", format_ore(total_ore_balance, decimals));
+        }
+        info!("Total Ore Claimable: {}", format_ore(total_ore_rewards_claimable, decimals));
+
+        let results_summary = shared_summary.clone();
+        let rpc_client_2 = rpc_client.clone();
+        let results_mine_queue = mine_queue.clone();
+        // Wallets that made it through the pipeline, landed or failed, for the heartbeat
+        let wallets_processed = Arc::new(AtomicU64::new(0));
+        let results_wallets_processed = wallets_processed.clone();
+        let results_in_flight_txs = in_flight_txs.clone();
+        let results_stopping = stopping.clone();
+        let tx_times = Arc::new(Mutex::new(TxTimes::resume(&prior_stats)));
+        let results_tx_times = tx_times.clone();
+        tasks.spawn(pipeline_task("tx results", async move {
+            let rpc_client = rpc_client_2.clone();
+            let wallet_queue = wallet_queue_sender_1.clone();
+            // successful rounds per wallet, only tracked with --rounds
+            let mut wallet_rounds: HashMap<String, u64> = HashMap::new();
+            let mut stop_sender = Some(stop_sender);
+            let mut profitability = min_ore_per_sol.map(ProfitabilityGate::new);
+            let mut landed_any = false;
+            let mut consecutive_failures = 0;
+            let mut mine_report = MineReport::default();
+            // Fees paid by the landed and failed txs of this session, for --max-fee-payer-spend
+            let mut fees_paid = 0;
+
+            let current_time = SystemTime::now();
+
+            loop {
+                if let Some(mssg) = tx_results_reader.recv().await {
+                    results_in_flight_txs.fetch_sub(1, Ordering::Relaxed);
+                    results_wallets_processed
+                        .fetch_add(mssg.wallets.len() as u64, Ordering::Relaxed);
+                    fees_paid += mssg.fee_paid;
+                    results_summary.lock().unwrap().lamports_spent += mssg.fee_paid;
+                    if let Some(max_spend) = max_fee_payer_spend {
+                        if fees_paid >= max_spend && !results_stopping.load(Ordering::Relaxed) {
+                            warn!(
+                                "Fee payer spend limit reached: paid {} SOL in fees of the {} SOL allowed. Stopping.",
+                                lamports_to_sol(fees_paid),
+                                lamports_to_sol(max_spend)
+                            );
+                            stop_mining(
+                                &results_stopping,
+                                &mut stop_sender,
+                                Some(format!(
+                                    "fee payer spend limit of {} SOL reached",
+                                    lamports_to_sol(max_spend)
+                                )),
+                            );
+                        }
+                    }
+                    if let Some(stats_file) = stats_file.as_mut() {
+                        for wallet in mssg.wallets.iter() {
+                            let record = StatsRecord {
+                                timestamp: chrono::Utc::now().to_rfc3339(),
+                                wallet: Keypair::from_base58_string(wallet).pubkey().to_string(),
+                                sig: mssg.sig.clone(),
+                                hash_time: mssg.hash_time_elapsed,
+                                tx_time: mssg.tx_time_elapsed,
+                                failed: mssg.failed,
+                            };
+                            if let Err(e) = stats_file.append(&record) {
+                                warn!("{}", e);
+                            }
+                        }
+                    }
+                    if let Some(csv_file) = csv_file.as_mut() {
+                        let timestamp_unix = chrono::Utc::now().timestamp();
+                        for (i, wallet) in mssg.wallets.iter().enumerate() {
+                            let record = CsvRecord {
+                                wallet_pubkey: Keypair::from_base58_string(wallet)
+                                    .pubkey()
+                                    .to_string(),
+                                bus_id: mssg.bus_ids[i],
+                                nonce: mssg.nonces[i],
+                                hash_time_secs: mssg.hash_time_elapsed,
+                                tx_time_secs: mssg.tx_time_elapsed,
+                                signature: mssg.sig.clone(),
+                                landed: !mssg.failed,
+                                timestamp_unix,
+                            };
+                            if let Err(e) = csv_file.append(&record) {
+                                warn!("{}", e);
+                            }
+                        }
+                    }
+                    if let Some(report) = report.as_deref() {
+                        let error = mssg
+                            .failed
+                            .then(|| mssg.error.clone().unwrap_or_else(|| "failed".to_string()));
+                        for wallet in mssg.wallets.iter() {
+                            mine_report.record(
+                                Keypair::from_base58_string(wallet).pubkey().to_string(),
+                                error.clone(),
+                            );
+                        }
+                        if let Err(e) = mine_report.write(report) {
+                            warn!("{}", e);
+                        }
+                    }
+                    if mssg.failed {
+                        metrics::record_failed();
+                        consecutive_failures += 1;
+                        if consecutive_failures == webhook_failures {
+                            Webhook::notify(
+                                WebhookEvent::Failures,
+                                json!({
+                                    "consecutive_failures": consecutive_failures,
+                                    "wallets": mssg.wallets.len(),
+                                    "error": mssg.error.clone(),
+                                }),
+                            );
+                        }
+                        results_summary.lock().unwrap().failed += 1;
+                        if fail_fast && !results_stopping.load(Ordering::Relaxed) {
+                            let pubkeys: Vec<String> = mssg
+                                .wallets
+                                .iter()
+                                .map(|w| Keypair::from_base58_string(w).pubkey().to_string())
+                                .collect();
+                            warn!("Transaction failed with --fail-fast. Stopping.");
+                            stop_mining(
+                                &results_stopping,
+                                &mut stop_sender,
+                                Some(format!(
+                                    "transaction failed for {}: {}",
+                                    pubkeys.join(", "),
+                                    mssg.error.as_deref().unwrap_or_default()
+                                )),
+                            );
+                        }
+                        warn!("Transaction failed, adding wallets back into queue.");
+                    } else {
+                        consecutive_failures = 0;
+                        if !landed_any {
+                            landed_any = true;
+                            Webhook::notify(
+                                WebhookEvent::FirstSuccess,
+                                json!({
+                                    "signature": mssg.sig,
+                                    "wallets": mssg.wallets.len(),
+                                    "tx_seconds": mssg.tx_time_elapsed,
+                                }),
+                            );
+                        }
+                        {
+                            let mut summary = results_summary.lock().unwrap();
+                            summary.success += 1;
+                            summary.ore_mined += mssg.expected_rewards;
+                            summary.tx_seconds += mssg.tx_time_elapsed;
+                        }
+                        if let Some(gate) = profitability.as_mut() {
+                            // Rewards actually mined, from each wallet's proof delta
+                            let pubkeys: Vec<Pubkey> = mssg
+                                .wallets
+                                .iter()
+                                .map(|wallet| Keypair::from_base58_string(wallet).pubkey())
+                                .collect();
+                            let mut mined = 0;
+                            match get_proofs(&rpc_client, &pubkeys).await {
+                                Ok(proofs) => {
+                                    for (pubkey, proof) in pubkeys.iter().zip(proofs) {
+                                        let Some(proof) = proof else {
+                                            info!("Failed to load proof for wallet {}", pubkey);
+                                            continue;
+                                        };
+                                        let last = claimable_rewards
+                                            .lock()
+                                            .unwrap()
+                                            .insert(*pubkey, proof.claimable_rewards)
+                                            .unwrap_or(proof.claimable_rewards);
+                                        mined += proof.claimable_rewards.saturating_sub(last);
+                                    }
+                                }
+                                Err(e) => info!("{}", e),
                             }
-                        }
-
-                        println!("Sending tx every {} milliseconds until confirmation or blockhash expires.", send_interval);
-                        let send_cfg = RpcSendTransactionConfig {
-                            skip_preflight: true,
-                            preflight_commitment: Some(CommitmentLevel::Confirmed),
-                            encoding: Some(UiTransactionEncoding::Base64),
-                            max_retries: None,
-                            min_context_slot: None,
-                        };
-                        let result = MinerV2::send_and_confirm_transaction(
-                            rpc_client.clone(),
-                            tx,
-                            last_valid_blockheight,
-                            send_interval,
-                            send_cfg,
-                        )
-                        .await;
-
-                        match result {
-                            Ok((sig, tx_time_elapsed)) => {
-                                println!("Transaction Confirmed!");
-                                if let Ok(_) = tx_results_sender
-                                    .send(TransactionResultMessage {
-                                        wallets: mssg.wallets.clone(),
-                                        sig: sig.to_string(),
-                                        tx_time_elapsed,
-                                        hash_time_elapsed: mssg.hash_time_elapsed,
-                                        failed: false,
-                                    })
-                                    .await
-                                {
-                                } else {
-                                    println!(
-                                        "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                            gate.record(mined, mssg.fee_paid);
+                            if let Some(ratio) = gate.ratio() {
+                                if gate.should_log() {
+                                    info!(
+                                        "Profitability: {:.4} ORE per SOL over the last transactions (minimum {})",
+                                        ratio,
+                                        gate.min_ore_per_sol()
                                     );
                                 }
-                            }
-                            Err(e) => {
-                                println!("Error: {}", e);
-                                if let Ok(_) = tx_results_sender
-                                    .send(TransactionResultMessage {
-                                        wallets: mssg.wallets.clone(),
-                                        sig: "failed".to_string(),
-                                        tx_time_elapsed: 0,
-                                        hash_time_elapsed: mssg.hash_time_elapsed,
-                                        failed: true,
-                                    })
-                                    .await
-                                {
-                                    println!("Sent tx result.");
-                                } else {
-                                    println!(
-                                        "Failed to send tx result. Tx Result Queue full? Dev help pls."
+                                if gate.is_unprofitable() {
+                                    warn!(
+                                        "Mining is unprofitable: {:.4} ORE per SOL is below the --min-ore-per-sol of {}. Stopping.",
+                                        ratio,
+                                        gate.min_ore_per_sol()
                                     );
+                                    stop_mining(&results_stopping, &mut stop_sender, None);
                                 }
                             }
                         }
+                        info!("Transaction was Successfull!");
+                        info!("Sig: {}", mssg.sig);
+                        info!("Took {} seconds", mssg.tx_time_elapsed);
+                        // append running results stats
+                        results_tx_times
+                            .lock()
+                            .unwrap()
+                            .push(mssg.tx_time_elapsed, mssg.hash_time_elapsed);
+                        metrics::record_confirmed(mssg.hash_time_elapsed, mssg.tx_time_elapsed);
+                        // log data
+                        info!(
+                            "Miner run time: {} seconds",
+                            current_time.elapsed().unwrap().as_secs()
+                        );
+                        results_tx_times.lock().unwrap().print();
+                    }
+                    for wallet in mssg.wallets {
+                        if let (Some(queue), false) = (results_mine_queue.as_ref(), mssg.failed) {
+                            queue.landed(Keypair::from_base58_string(&wallet).pubkey().to_string());
+                        }
+                        if let (Some(rounds), false) = (rounds, mssg.failed) {
+                            let done = wallet_rounds.entry(wallet.clone()).or_insert(0);
+                            *done += 1;
+                            if *done >= rounds {
+                                info!("Wallet finished {} rounds.", rounds);
+                                let left = active_wallets.fetch_sub(1, Ordering::Relaxed) - 1;
+                                metrics::set_active_wallets(left);
+                                if left == 0 {
+                                    info!("All wallets finished {} rounds.", rounds);
+                                    stop_mining(&results_stopping, &mut stop_sender, None);
+                                }
+                                continue;
+                            }
+                        }
+                        if let Some(queue) = results_mine_queue.as_ref() {
+                            queue.queued(Keypair::from_base58_string(&wallet).pubkey().to_string());
+                        }
+                        let w = WalletQueueMessage { wallet };
+                        if let Ok(_) = wallet_queue.send(w).await {
+                            debug!("Successfully sent wallet to queue.");
+                        } else {
+                            warn!("Failed to send wallet to queue.");
+                        }
                     }
-                    sleep(Duration::from_millis(500)).await;
                 }
-            });
-            handles.push(thread_handle);
-
-            // tx results thread
-            let wallet_queue_sender_1 = wallet_queue_sender.clone();
-
-            let results_pubkeys: Vec<Pubkey> = key_strings.iter().map(|key_str| {
-                let key = Keypair::from_base58_string(key_str);
-                key.pubkey()
-            }).collect();
-
-            println!("Loading All wallet balances");
-
-            let response = rpc_client.get_multiple_accounts(&results_pubkeys).await;
-
-            let mut total_lamports = 0;
-            if let Ok(results) = response {
-                for res in results {
-                    if let Some(acc) = res {
-                        total_lamports += acc.lamports();
+                sleep(Duration::from_millis(500)).await;
+            }
+        }));
+
+        // heartbeat thread, logs progress and catches a pipeline that stopped moving, e.g. a
+        // wedged rpc or stuck hashing threads
+        tasks.spawn(pipeline_task("heartbeat", async move {
+            let mut last_heartbeat = Instant::now();
+            let mut last_heartbeat_count = 0;
+            let mut last_progress = Instant::now();
+            let mut last_progress_count = 0;
+            let mut stall_reported = false;
+            loop {
+                sleep(Duration::from_secs(1)).await;
+                let count = wallets_processed.load(Ordering::Relaxed);
+                if count > last_progress_count {
+                    last_progress = Instant::now();
+                    last_progress_count = count;
+                    stall_reported = false;
+                }
+                if last_heartbeat.elapsed().as_secs() >= heartbeat_interval {
+                    info!(
+                        "alive: {} wallets processed in last {} seconds",
+                        count - last_heartbeat_count,
+                        last_heartbeat.elapsed().as_secs()
+                    );
+                    last_heartbeat = Instant::now();
+                    last_heartbeat_count = count;
+                }
+                let Some(stall_timeout) = stall_timeout else {
+                    continue;
+                };
+                let stalled_for = last_progress.elapsed().as_secs();
+                if stalled_for >= stall_timeout && !stall_reported {
+                    error!(
+                        "\n!!! STALLED: no wallet has progressed through the pipeline in {} seconds !!!\n",
+                        stalled_for
+                    );
+                    if exit_on_stall {
+                        return Err(format!(
+                            "stalled, no wallet progressed in {} seconds",
+                            stalled_for
+                        ));
                     }
+                    stall_reported = true;
                 }
             }
+        }));
+
+        let stopped = async {
+            match stop_receiver.await {
+                // New wallets already stopped, let the transactions in flight land so their
+                // results make it into the summary, stats and csv
+                Ok(failure) => {
+                    drain_in_flight(&in_flight_txs, &tx_times).await;
+                    failure
+                }
+                // The results task ended, `tasks.join_next` reports why
+                Err(_) => std::future::pending().await,
+            }
+        };
+        // The first Ctrl-C stops hashing new wallets and drains, a second one aborts
+        let interrupted = async {
+            if tokio::signal::ctrl_c().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+            stopping.store(true, Ordering::Relaxed);
+            drain_in_flight(&in_flight_txs, &tx_times).await;
+        };
+        let failure = tokio::select! {
+            failure = stopped => failure,
+            _ = interrupted => None,
+            Some(result) = tasks.join_next() => Some(match result {
+                Ok(Err(e)) => e,
+                Ok(Ok(())) => "mining task stopped unexpectedly".to_string(),
+                Err(e) => format!("mining task failed: {}", e),
+            }),
+        };
+        tasks.shutdown().await;
 
-            println!("Total Sol: {}", lamports_to_sol(total_lamports));
-
-            println!("Loading ore balances and rewards...");
-            let mut total_ore_balance = 0.0;
-            let mut total_ore_rewards_claimable = 0.0;
-            for pubkey in &results_pubkeys {
-                let pubkey = pubkey.to_owned();
-
-                sleep(Duration::from_millis(200)).await;
-                let ore_balance = MinerV2::get_ore_display_balance_v2(&rpc_client, pubkey).await;
-                total_ore_balance += ore_balance;
-
-                sleep(Duration::from_millis(200)).await;
-                let proof = get_proof(&rpc_client, pubkey).await;
-                let rewards = proof.claimable_rewards;
+        *summary = *shared_summary.lock().unwrap();
+        match failure {
+            Some(failure) => Err(failure),
+            None => Ok(()),
+        }
+    }
 
-                total_ore_rewards_claimable += (rewards as f64) / 1000000000.0;
+    pub async fn tune_batch(
+        rpc_client: Arc<RpcClient>,
+        tx_settings: TxSettings,
+        wallets_directory_string: Option<String>,
+        threads: u64,
+        send_interval: u64,
+        priority_fee: PriorityFee,
+        rounds: u64,
+        real: bool,
+        summary: &mut Summary,
+    ) {
+        let mut signers = vec![];
+        if let Some(wallets_dir) = wallets_directory_string {
+            match load_keypairs(&wallets_dir, &LoadKeypairsOptions::default()).await {
+                Ok(keypairs) => {
+                    signers = keypairs.into_iter().map(|(_, signer)| signer).collect();
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
             }
-            println!("Total Ore Balance: {}", total_ore_balance);
-            println!("Total Ore Claimable: {}", total_ore_rewards_claimable);
+        }
 
+        if signers.is_empty() {
+            info!("No wallets found. Supply a directory of keypairs with --miner-wallets.");
+            return;
+        }
 
-            let thread_handle = tokio::spawn(async move {
-                let wallet_queue = wallet_queue_sender_1.clone();
-                let mut tx_times = vec![];
-                let mut hash_times = vec![];
-                let mut total_times = vec![];
+        if real {
+            info!("Running tune with real transactions. This will spend SOL.");
+        } else {
+            info!("Running tune with simulated transactions. Use --real to send.");
+        }
 
-                let current_time = SystemTime::now();
+        // (batch_size, landed, attempts, lamports_spent)
+        let mut results: Vec<(usize, u64, u64, u64)> = vec![];
+        let hash_pool = HashPool::new(threads);
+        let max_batch_size = signers.len().min(5);
+        for batch_size in 1..=max_batch_size {
+            let batch = &signers[..batch_size];
+            let cu_limit = CU_LIMIT_MINE * batch_size as u32;
+            let mut landed = 0;
+            let mut lamports_spent = 0;
+
+            'rounds: for round in 0..rounds {
+                info!("\nBatch size {} round {}/{}", batch_size, round + 1, rounds);
+                let treasury = match get_treasury(&rpc_client).await {
+                    Ok(treasury) => treasury,
+                    Err(e) => {
+                        warn!("{}, skipping round.", e);
+                        continue 'rounds;
+                    }
+                };
+                let bus = BUS_ADDRESSES[round as usize % BUS_COUNT];
+
+                let mut ixs = vec![
+                    ComputeBudgetInstruction::set_compute_unit_limit(cu_limit),
+                    ComputeBudgetInstruction::set_compute_unit_price(priority_fee.cu_price(cu_limit)),
+                ];
+                for signer in batch {
+                    let proof = match get_proof_v2(&rpc_client, signer.pubkey()).await {
+                        Ok(proof) => proof,
+                        Err(e) => {
+                            warn!("{} for wallet {}, skipping round.", e, signer.pubkey());
+                            continue 'rounds;
+                        }
+                    };
+                    let solution = hash_pool
+                        .find_next_hash(
+                            signer.pubkey(),
+                            proof.hash,
+                            treasury.difficulty,
+                            false,
+                        )
+                        .await;
+                    let Some((next_hash, nonce)) = solution else {
+                        warn!("\nNo solution found for wallet {}, skipping round.", signer.pubkey());
+                        continue 'rounds;
+                    };
+                    ixs.push(ore::instruction::mine(
+                        signer.pubkey(),
+                        bus,
+                        next_hash.into(),
+                        nonce,
+                    ));
+                }
+                info!("");
 
-                loop {
-                    if let Some(mssg) = tx_results_reader.recv().await {
-                        if mssg.failed {
-                            println!("Transaction failed, adding wallets back into queue.");
-                        } else {
-                            println!("Transaction was Successfull!");
-                            println!("Sig: {}", mssg.sig);
-                            println!("Took {} seconds", mssg.tx_time_elapsed);
-                            // append running results stats
-                            tx_times.push(mssg.tx_time_elapsed);
-                            hash_times.push(mssg.hash_time_elapsed);
-                            total_times.push(mssg.tx_time_elapsed + mssg.hash_time_elapsed);
-                            // log data
-                            println!(
-                                "Miner run time: {} seconds",
-                                current_time.elapsed().unwrap().as_secs()
-                            );
-                            println!("TX TIMES COUNT: {:?}", tx_times.len());
-                            println!("TX TIMES: \n{:?}", tx_times);
-                            println!("HASH TIMES: \n{:?}", hash_times);
-                            println!("TOTAL TIMES: \n{:?}", total_times);
-                        }
-                        for wallet in mssg.wallets {
-                            let w = WalletQueueMessage { wallet };
-                            if let Ok(_) = wallet_queue.send(w).await {
-                                println!("Successfully sent wallet to queue.");
+                let fee_payer = &batch[0];
+                let mut tx = Transaction::new_with_payer(&ixs, Some(&fee_payer.pubkey()));
+
+                if real {
+                    let balance_before = rpc_client.get_balance(&fee_payer.pubkey()).await;
+                    let (hash, last_valid_blockheight) =
+                        match BlockhashCache::latest_blockhash(&rpc_client).await {
+                            Ok(res) => res,
+                            Err(e) => {
+                                warn!("{}, skipping round.", e);
+                                continue 'rounds;
+                            }
+                        };
+                    let signer_refs: Vec<&Keypair> = batch.iter().collect();
+                    tx.sign(&signer_refs, hash);
+                    let send_cfg = RpcSendTransactionConfig {
+                        skip_preflight: true,
+                        preflight_commitment: Some(CommitmentLevel::Confirmed),
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        max_retries: None,
+                        min_context_slot: None,
+                    };
+                    match MinerV2::send_and_confirm_transaction(
+                        &tx_settings,
+                        tx,
+                        last_valid_blockheight,
+                        send_interval,
+                        send_cfg,
+                    )
+                    .await
+                    {
+                        Ok((sig, tx_time_elapsed)) => {
+                            info!("Landed: {} ({} seconds)", sig, tx_time_elapsed);
+                            landed += 1;
+                        }
+                        Err(e) => {
+                            warn!("Failed: {}", e);
+                        }
+                    }
+                    let balance_after = rpc_client.get_balance(&fee_payer.pubkey()).await;
+                    if let (Ok(before), Ok(after)) = (balance_before, balance_after) {
+                        lamports_spent += before.saturating_sub(after);
+                    }
+                } else {
+                    let sim_res = tx_settings
+                        .simulate_transaction(
+                            &rpc_client,
+                            &tx,
+                            RpcSimulateTransactionConfig {
+                                sig_verify: false,
+                                replace_recent_blockhash: true,
+                                commitment: Some(rpc_client.commitment()),
+                                encoding: Some(UiTransactionEncoding::Base64),
+                                accounts: None,
+                                min_context_slot: None,
+                                inner_instructions: false,
+                            },
+                        )
+                        .await;
+                    match sim_res {
+                        Ok(sim_res) => {
+                            if let Some(err) = sim_res.value.err {
+                                warn!("Simulaton error: {:?}", err);
                             } else {
-                                println!("Failed to send wallet to queue.");
+                                debug!("Simulaton successful.");
+                                landed += 1;
                             }
                         }
+                        Err(err) => {
+                            warn!("Simulaton error: {:?}", err);
+                        }
                     }
-                    sleep(Duration::from_millis(500)).await;
-                }
-            });
-
-            println!("Sending wallets to queue");
-            for wallet in key_strings {
-                let w = WalletQueueMessage {
-                    wallet,
-                };
-                if let Err(_) = wallet_queue_sender.send(w).await {
-                    println!("Failed to send wallet to queue.");
+                    lamports_spent += estimate_tx_fee(batch_size as u64, cu_limit, priority_fee);
                 }
-
             }
+            results.push((batch_size, landed, rounds, lamports_spent));
+            summary.success += landed;
+            summary.failed += rounds - landed;
+            if real {
+                summary.lamports_spent += lamports_spent;
+            }
+        }
 
-            handles.push(thread_handle);
-            for handle in handles {
-                handle.await.unwrap();
+        info!("\nbatch_size | landed | landing_rate | sol_per_mine");
+        let mut best: Option<(usize, f64)> = None;
+        for (batch_size, landed, attempts, lamports_spent) in results {
+            let landing_rate = landed as f64 / attempts.max(1) as f64;
+            let mines = landed * batch_size as u64;
+            if mines == 0 {
+                info!(
+                    "{:>10} | {:>6} | {:>11.0}% | -",
+                    batch_size,
+                    landed,
+                    landing_rate * 100.0
+                );
+                continue;
+            }
+            let sol_per_mine = lamports_to_sol(lamports_spent) / mines as f64;
+            info!(
+                "{:>10} | {:>6} | {:>11.0}% | {:.9}",
+                batch_size,
+                landed,
+                landing_rate * 100.0,
+                sol_per_mine
+            );
+            if best.map_or(true, |(_, best_cost)| sol_per_mine < best_cost) {
+                best = Some((batch_size, sol_per_mine));
             }
+        }
 
-            return;
+        match best {
+            Some((batch_size, _)) => info!("\nRecommended batch size: {}", batch_size),
+            None => info!("\nNo batch size landed a mine. Try more rounds or a higher priority fee."),
         }
     }
 
     pub async fn send_sol(
         rpc_client: Arc<RpcClient>,
+        tx_settings: TxSettings,
         sender_wallet: String,
         wallets_directory_string: Option<String>,
         send_interval: u64,
         amount: Option<u64>,
+        top_up: Option<u64>,
+        only_if_below: Option<u64>,
+        equal_split: bool,
+        concurrency: usize,
+        summary: &mut Summary,
     ) {
-        let amount = if let Some(a) = amount {
-            a
-        } else {
-            println!("Please provide lamports amount to send `--amount 1_000_000`.");
+        let modes = [amount.is_some(), top_up.is_some(), equal_split];
+        if modes.iter().filter(|set| **set).count() != 1 {
+            info!("Please provide either a lamports amount to send `--amount 1_000_000`, a target balance `--top-up 1_000_000` or `--equal-split`.");
+            summary.failed += 1;
             return;
-        };
+        }
+        let mut amount = amount.unwrap_or(0);
 
-        println!("Wallet Path: {}", sender_wallet);
+        info!("Wallet Path: {}", sender_wallet);
         let sender;
         if let Ok(signer) = read_keypair_file(sender_wallet.clone()) {
-            println!(
+            info!(
                 "\nLoaded Sender wallet pubkey: \n{}",
                 signer.pubkey().to_string()
             );
             sender = signer;
         } else {
-            println!("Sender wallet required.");
+            info!("Sender wallet required.");
+            summary.failed += 1;
             return;
         }
 
-        let mut key_paths = vec![];
+        let mut receivers = vec![];
         if let Some(wallets_dir) = wallets_directory_string {
-            let dir_reader = tokio::fs::read_dir(wallets_dir.clone()).await;
-            if let Ok(mut dir_reader) = dir_reader {
-                loop {
-                    if let Ok(Some(next_entry)) = dir_reader.next_entry().await {
-                        key_paths.push(next_entry.path());
-                    } else {
-                        break;
-                    }
+            match load_keypairs(&wallets_dir, &LoadKeypairsOptions::default()).await {
+                Ok(keypairs) => receivers = keypairs,
+                Err(e) => {
+                    error!("{}", e);
+                    summary.failed += 1;
+                    return;
                 }
-            } else {
-                println!(
-                    "Failed to read receiving wallets directory: {}",
-                    wallets_dir
-                );
-                return;
             }
         }
 
-        println!("Found {} wallets", key_paths.len());
+        info!("Found {} wallets", receivers.len());
 
-        for key_path in key_paths.clone() {
-            println!("Wallet Path: {}", key_path.to_str().unwrap());
-            if let Ok(signer) = read_keypair_file(key_path.clone()) {
-                println!("\nLoaded wallet pubkey: \n{}", signer.pubkey().to_string());
+        if equal_split && !receivers.is_empty() {
+            // Split what's left after the sender's own fees and --min-sol-reserve
+            let sender_balance = match rpc_client.get_balance(&sender.pubkey()).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    warn!("Failed to load sender balance: {}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            };
+            let fees = estimate_tx_fee(1, 0, PriorityFee::PerCu(0)) * receivers.len() as u64;
+            let available = sender_balance.saturating_sub(fees + tx_settings.min_sol_reserve);
+            amount = available / receivers.len() as u64;
+            info!(
+                "Splitting {} SOL of the sender's {} SOL between {} wallets: {} lamports ({} SOL) each",
+                lamports_to_sol(available),
+                lamports_to_sol(sender_balance),
+                receivers.len(),
+                amount,
+                lamports_to_sol(amount)
+            );
+            let rent_exempt_minimum =
+                match rpc_client.get_minimum_balance_for_rent_exemption(0).await {
+                    Ok(minimum) => minimum,
+                    Err(e) => {
+                        warn!("Failed to load the rent-exempt minimum: {}", e);
+                        summary.failed += 1;
+                        return;
+                    }
+                };
+            if amount < rent_exempt_minimum {
+                warn!(
+                    "{} lamports per wallet is below the rent-exempt minimum of {} lamports. Aborting.",
+                    amount, rent_exempt_minimum
+                );
+                summary.failed += 1;
+                return;
+            }
+        }
 
-                println!("Send Sol");
-                println!("Building Transaction...");
+        // Work out how much each wallet needs, skipping wallets already at the target or at
+        // --only-if-below
+        let mut transfers = vec![];
+        for (key_path, signer) in receivers {
+            let balance = match rpc_client.get_balance(&signer.pubkey()).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    warn!("Failed to load balance for {}: {}", signer.pubkey(), e);
+                    summary.failed += 1;
+                    return;
+                }
+            };
+            let transfer_amount = match top_up {
+                Some(target) => target.saturating_sub(balance),
+                None => amount,
+            };
+            if transfer_amount == 0 || only_if_below.is_some_and(|threshold| balance >= threshold) {
+                info!(
+                    "Skipping {}, already has {} SOL.",
+                    signer.pubkey(),
+                    lamports_to_sol(balance)
+                );
+                summary.skipped += 1;
+                continue;
+            }
+            transfers.push((key_path, signer, balance, transfer_amount));
+        }
 
-                let ix = system_instruction::transfer(&sender.pubkey(), &signer.pubkey(), amount);
-                println!("Signing Transaction...");
-                let mut tx = Transaction::new_with_payer(&[ix], Some(&sender.pubkey()));
+        // Make sure the sender can fund every wallet before sending anything
+        let transfer_fee = estimate_tx_fee(1, 0, PriorityFee::PerCu(0));
+        let required: u64 = transfers
+            .iter()
+            .map(|(_, _, _, transfer_amount)| transfer_amount + transfer_fee)
+            .sum();
+        let sender_balance = match rpc_client.get_balance(&sender.pubkey()).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                warn!("Failed to load sender balance: {}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        info!(
+            "Funding {} wallets, requires {} SOL. Sender balance: {} SOL",
+            transfers.len(),
+            lamports_to_sol(required),
+            lamports_to_sol(sender_balance)
+        );
+        if sender_balance < required {
+            warn!("Sender balance is too low to fund all wallets. Aborting.");
+            summary.failed += 1;
+            return;
+        }
+        if let Err(e) = tx_settings.keeps_sol_reserve(sender_balance, required) {
+            warn!("Funding all wallets would break the sender's reserve: {}. Aborting.", e);
+            summary.failed += 1;
+            return;
+        }
+        let rent_exempt_minimum = rpc_client
+            .get_minimum_balance_for_rent_exemption(0)
+            .await
+            .unwrap_or(0);
 
-                let (hash, last_valid_blockheight) = rpc_client
-                    .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                    .await
-                    .unwrap();
-
-                println!("Signing tx...");
-                tx.sign(&[&sender], hash);
-
-                println!("Sending Transaction...");
-                let send_cfg = RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    preflight_commitment: Some(CommitmentLevel::Confirmed),
-                    encoding: Some(UiTransactionEncoding::Base64),
-                    max_retries: None,
-                    min_context_slot: None,
+        let send_cfg = RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: None,
+            min_context_slot: None,
+        };
+        let concurrency = concurrency.max(1);
+        let sender = Arc::new(sender);
+        let transfer_count = transfers.len();
+        let mut transfers = transfers.into_iter();
+        let mut sends = JoinSet::new();
+        let mut funded = vec![];
+        let mut failed = vec![];
+        let mut sender_too_low = false;
+        // Keep up to --concurrency transfers in flight, starting the next as soon as one ends
+        loop {
+            while sends.len() < concurrency && !sender_too_low {
+                let Some((key_path, signer, balance, transfer_amount)) = transfers.next() else {
+                    break;
                 };
-                let result = MinerV2::send_and_confirm_transaction(
-                    rpc_client.clone(),
-                    tx,
-                    last_valid_blockheight,
-                    send_interval,
-                    send_cfg,
-                )
-                .await;
-
-                match result {
-                    Ok((_sig, _tx_time_elapsed)) => {
-                        println!("Transaction Confirmed!");
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
+                info!("Wallet Path: {}", key_path.display());
+                info!("\nLoaded wallet pubkey: \n{}", signer.pubkey().to_string());
+                if balance + transfer_amount < rent_exempt_minimum {
+                    warn!(
+                        "Warning: wallet will still be below the rent-exempt minimum of {} SOL after transfer.",
+                        lamports_to_sol(rent_exempt_minimum)
+                    );
+                }
+                info!("Send {} Sol", lamports_to_sol(transfer_amount));
+
+                let rpc_client = rpc_client.clone();
+                let tx_settings = tx_settings.clone();
+                let sender = sender.clone();
+                let recipient = signer.pubkey();
+                sends.spawn(async move {
+                    let result = match BlockhashCache::latest_blockhash(&rpc_client).await {
+                        Ok((hash, last_valid_blockheight)) => {
+                            let tx = sol_transfer_tx(&sender, &recipient, transfer_amount, hash);
+                            MinerV2::send_and_confirm_transaction(
+                                &tx_settings,
+                                tx,
+                                last_valid_blockheight,
+                                send_interval,
+                                send_cfg,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e.to_string()),
+                    };
+                    (recipient, transfer_amount, result)
+                });
+            }
+            let Some(joined) = sends.join_next().await else {
+                break;
+            };
+            let (recipient, transfer_amount, result) = match joined {
+                Ok(joined) => joined,
+                Err(e) => {
+                    warn!("Transfer task failed: {}", e);
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+            match result {
+                Ok((sig, _tx_time_elapsed)) => {
+                    info!("Funded {}: {}", recipient, sig);
+                    funded.push(recipient);
+                    summary.success += 1;
+                    summary.lamports_spent += transfer_fee;
+                }
+                Err(e) => {
+                    warn!("Failed to fund {}: {}", recipient, e);
+                    failed.push(recipient);
+                    summary.failed += 1;
+                    // Stop starting transfers if the sender can no longer cover one
+                    let balance = rpc_client.get_balance(&sender.pubkey()).await.unwrap_or(0);
+                    if !sender_too_low && balance < transfer_amount + transfer_fee {
+                        warn!("Sender balance is too low to continue. Aborting.");
+                        sender_too_low = true;
                     }
                 }
+            }
+        }
 
-                println!("Checking for next wallet.");
-            } else {
-                println!(
-                    "Failed to read keypair file: {}",
-                    key_path.to_str().unwrap()
+        info!("Funded {} of {} wallets:", funded.len(), transfer_count);
+        for pubkey in funded.iter() {
+            info!("{}", pubkey);
+        }
+        if !failed.is_empty() {
+            warn!("Failed to fund {} wallets:", failed.len());
+            for pubkey in failed.iter() {
+                warn!("{}", pubkey);
+            }
+        }
+    }
+
+    pub async fn collect_sol(
+        rpc_client: Arc<RpcClient>,
+        tx_settings: TxSettings,
+        destination: String,
+        wallets_directory_string: Option<String>,
+        send_interval: u64,
+        summary: &mut Summary,
+    ) {
+        let destination = match Pubkey::from_str(&destination) {
+            Ok(destination) => destination,
+            Err(e) => summary.fail(&format!("invalid --destination: {}", e)),
+        };
+        let Some(wallets_dir) = wallets_directory_string else {
+            summary.fail("--miner-wallets is required");
+        };
+        let wallets = match load_keypairs(&wallets_dir, &LoadKeypairsOptions::default()).await {
+            Ok(keypairs) => keypairs,
+            Err(e) => summary.fail(&e),
+        };
+        info!("Found {} wallets", wallets.len());
+
+        let transfer_fee = estimate_tx_fee(1, 0, PriorityFee::PerCu(0));
+        // A wallet can only be left non-empty if it stays rent-exempt
+        let reserve = match tx_settings.min_sol_reserve {
+            0 => 0,
+            reserve => {
+                let rent_exempt_minimum = rpc_client
+                    .get_minimum_balance_for_rent_exemption(0)
+                    .await
+                    .unwrap_or(0);
+                reserve.max(rent_exempt_minimum)
+            }
+        };
+        let send_cfg = RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: None,
+            min_context_slot: None,
+        };
+        let mut swept = 0;
+        for (key_path, signer) in wallets {
+            if signer.pubkey() == destination {
+                info!("Skipping {}, it is the destination.", signer.pubkey());
+                summary.skipped += 1;
+                continue;
+            }
+            let balance = match rpc_client.get_balance(&signer.pubkey()).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    warn!("Failed to load balance for {}: {}", signer.pubkey(), e);
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+            let lamports = balance.saturating_sub(transfer_fee + reserve);
+            if lamports == 0 {
+                info!(
+                    "Skipping {}, {} SOL doesn't cover the transfer fee.",
+                    signer.pubkey(),
+                    lamports_to_sol(balance)
                 );
+                summary.skipped += 1;
+                continue;
+            }
+            info!("Wallet Path: {}", key_path.display());
+            info!("Collect {} Sol from {}", lamports_to_sol(lamports), signer.pubkey());
+            let result = match BlockhashCache::latest_blockhash(&rpc_client).await {
+                Ok((hash, last_valid_blockheight)) => {
+                    let tx = sol_transfer_tx(&signer, &destination, lamports, hash);
+                    MinerV2::send_and_confirm_transaction(
+                        &tx_settings,
+                        tx,
+                        last_valid_blockheight,
+                        send_interval,
+                        send_cfg,
+                    )
+                    .await
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            match result {
+                Ok((sig, _tx_time_elapsed)) => {
+                    info!("Collected from {}: {}", signer.pubkey(), sig);
+                    swept += lamports;
+                    summary.success += 1;
+                    summary.lamports_spent += transfer_fee;
+                }
+                Err(e) => {
+                    warn!("Failed to collect from {}: {}", signer.pubkey(), e);
+                    summary.failed += 1;
+                }
             }
         }
 
-        println!("Wallets funded!");
+        info!(
+            "Collected {} SOL into {} from {} wallets.",
+            lamports_to_sol(swept),
+            destination,
+            summary.success
+        );
     }
 
-    pub async fn wallets(rpc_client: Arc<RpcClient>, wallets_directory_string: Option<String>) {
-        let mut key_paths = vec![];
-        if let Some(wallets_dir) = wallets_directory_string {
-            let dir_reader = tokio::fs::read_dir(wallets_dir.clone()).await;
-            if let Ok(mut dir_reader) = dir_reader {
-                loop {
-                    if let Ok(Some(next_entry)) = dir_reader.next_entry().await {
-                        key_paths.push(next_entry.path());
-                    } else {
-                        break;
-                    }
+    pub async fn wallets(
+        rpc_client: Arc<RpcClient>,
+        wallet_source: Option<WalletSource>,
+        decimals: usize,
+        include_zero: bool,
+        token_program: TokenProgram,
+        output: OutputFormat,
+        summary: &mut Summary,
+    ) {
+        let mut keypairs = vec![];
+        if let Some(wallet_source) = wallet_source {
+            match wallet_source.load(&LoadKeypairsOptions::default()).await {
+                Ok(k) => keypairs = k,
+                Err(e) => {
+                    error!("{}", e);
+                    summary.failed += 1;
+                    return;
                 }
-            } else {
-                println!("Failed to read miner wallets directory: {}", wallets_dir);
-                return;
             }
         }
 
-        println!("Found {} wallets", key_paths.len());
+        if output == OutputFormat::Text {
+            info!("Found {} wallets", keypairs.len());
+        }
 
-        for key_path in key_paths.clone() {
-            if let Ok(signer) = read_keypair_file(key_path.clone()) {
-                println!("\nLoaded wallet pubkey: \n{}", signer.pubkey().to_string());
-                println!("Wallet Path: {}", key_path.to_str().unwrap());
+        let mut wallets = vec![];
+        let mut totals = WalletsTotals {
+            wallets: 0,
+            balance: 0,
+            claimable_rewards: 0,
+            skipped: 0,
+        };
+        for (key_path, signer) in keypairs.iter() {
+            let mut wallet = WalletOutput {
+                pubkey: signer.pubkey().to_string(),
+                keypair_path: key_path.display().to_string(),
+                balance: None,
+                claimable_rewards: None,
+                ata_exists: None,
+                error: None,
+            };
+            match get_proof_v2(&rpc_client, signer.pubkey()).await {
+                Ok(proof) => {
+                    if proof.claimable_rewards == 0 && !include_zero {
+                        summary.skipped += 1;
+                        totals.skipped += 1;
+                        continue;
+                    }
+                    wallet.claimable_rewards = Some(proof.claimable_rewards);
+                    totals.claimable_rewards += proof.claimable_rewards;
+                    match get_ore_balance(&rpc_client, signer.pubkey(), token_program).await {
+                        Ok(balance) => {
+                            wallet.ata_exists = Some(balance.is_some());
+                            wallet.balance = Some(balance.unwrap_or(0));
+                            totals.balance += balance.unwrap_or(0);
+                            summary.success += 1;
+                        }
+                        Err(e) => {
+                            wallet.error = Some(e.to_string());
+                            summary.failed += 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    wallet.error = Some(e.to_string());
+                    summary.failed += 1;
+                }
+            }
+            totals.wallets += 1;
+            wallets.push(wallet);
+        }
+        output.print(&WalletsOutput {
+            wallets,
+            totals,
+            decimals,
+        });
+    }
 
-                let proof = get_proof_v2(&rpc_client, signer.pubkey()).await;
-                match proof {
-                    Ok(proof) => {
-                        let rewards = proof.claimable_rewards;
-                        let amount = rewards;
+    /// Waits on `confirm_service` for any signature the sender reports, until one confirms or
+    /// the blockhash expires, and sends the outcome to `tx_result_sender`.
+    async fn confirm_sent_signatures(
+        confirm_service: ConfirmService,
+        mut sig_checks_receiver: Receiver<Result<Signature, String>>,
+        tx_result_sender: Sender<Result<Signature, String>>,
+        last_valid_blockheight: u64,
+    ) {
+        let client = confirm_service.client();
+        let mut sigs: Vec<Signature> = vec![];
+        let mut statuses = FuturesUnordered::new();
+        loop {
+            while let Ok(new_sig) = sig_checks_receiver.try_recv() {
+                if let Ok(new_sig) = new_sig {
+                    if !sigs.contains(&new_sig) {
+                        sigs.push(new_sig);
+                        let status = confirm_service.watch(new_sig);
+                        statuses.push(async move { (new_sig, status.await) });
+                    }
+                }
+            }
 
-                        if amount == 0 {
-                            println!("No rewards to claim in this wallet.");
-                            continue;
+            tokio::select! {
+                Some((sig, Ok(status))) = statuses.next() => {
+                    info!("Transaction landed!");
+                    let result = match status {
+                        Ok(()) => Ok(sig),
+                        Err(e) => {
+                            info!("STATUS: {:?}", e);
+                            Err(TX_FAILED_ERROR.to_string())
                         }
+                    };
+                    let _ = tx_result_sender.send(result).await;
+                    return;
+                }
+                // sleep 500ms to allow confirmations to potentially land
+                _ = sleep(Duration::from_millis(500)) => {}
+            }
 
-                        let balance =
-                            MinerV2::get_ore_display_balance(&rpc_client, signer.pubkey()).await;
-                        let rewards = (proof.claimable_rewards as f64)
-                            / (10f64.powf(ore::TOKEN_DECIMALS as f64));
-                        println!("Balance: {} ORE", balance);
-                        println!("Claimable: {} ORE", rewards);
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
-                    }
+            // hash expiration checks
+            if let Ok(current_blockheight) = BlockhashCache::block_height(&client).await {
+                if current_blockheight > last_valid_blockheight {
+                    let err = Err("Last valid blockheight exceeded!".to_string());
+                    let _ = tx_result_sender.send(err).await;
+                    return;
                 }
-            } else {
-                println!(
-                    "Failed to read keypair file: {}",
-                    key_path.to_str().unwrap()
-                );
             }
         }
     }
 
+    /// Waits for the outcome `confirm_sent_signatures` reports, giving up with
+    /// `CONFIRM_TIMEOUT_ERROR` after `--confirm-timeout-secs`.
+    async fn wait_for_tx_result(
+        tx_result_receiver: &mut Receiver<Result<Signature, String>>,
+        confirm_timeout: Option<Duration>,
+    ) -> Result<Signature, String> {
+        let result = match confirm_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, tx_result_receiver.recv())
+                .await
+                .map_err(|_| CONFIRM_TIMEOUT_ERROR.to_string())?,
+            None => tx_result_receiver.recv().await,
+        };
+        result.unwrap_or_else(|| Err("Confirmation stopped without a result".to_string()))
+    }
+
     pub async fn send_and_confirm_transaction(
-        rpc_client: Arc<RpcClient>,
+        tx_settings: &TxSettings,
+        tx: Transaction,
+        last_valid_blockheight: u64,
+        send_interval: u64,
+        send_cfg: RpcSendTransactionConfig,
+    ) -> Result<(Signature, u64), String> {
+        MinerV2::confirm_loop(
+            tx_settings,
+            tx,
+            last_valid_blockheight,
+            send_interval,
+            send_cfg,
+        )
+        .await
+    }
+
+    /// Resends the signed `tx` through the send client every `send_interval` ms until it
+    /// confirms or its blockhash expires, returning the signature and the seconds it took to
+    /// land. The core of both `send_and_confirm_transaction` and `send_and_confirm`.
+    async fn confirm_loop(
+        tx_settings: &TxSettings,
         tx: Transaction,
         last_valid_blockheight: u64,
         send_interval: u64,
@@ -841,172 +3293,113 @@ impl MinerV2 {
         ) = mpsc::channel(100);
 
         // creates channel for getting sigs to confirm
-        let (sig_checks_sender, mut sig_checks_receiver): (
+        let (sig_checks_sender, sig_checks_receiver): (
             Sender<Result<Signature, String>>,
             Receiver<Result<Signature, String>>,
         ) = mpsc::channel(100);
 
         // confirmation checks thread
-        let c_client = rpc_client.clone();
-        let confirms_thread_handle = tokio::spawn(async move {
-            let client = c_client;
-            let mut sigs: Vec<Signature> = vec![];
-            // receive sig_checks and add them to hashmap if new
-            loop {
-                if let Some(new_sig) = sig_checks_receiver.recv().await {
-                    if let Ok(new_sig) = new_sig {
-                        let mut is_new = true;
-                        for sig in sigs.iter() {
-                            if sig.to_string() == new_sig.to_string() {
-                                is_new = false;
-                            }
-                        }
-
-                        if is_new {
-                            sigs.push(new_sig);
-                        }
-                    }
-                }
-                // really should only have one sig here though
-                //for sig in sigs.iter {}
-                // confirmation checks
-                match client.get_signature_statuses(&sigs).await {
-                    Ok(signature_statuses) => {
-                        for signature_status in signature_statuses.value {
-                            if let Some(signature_status) = signature_status.as_ref() {
-                                if signature_status.confirmation_status.is_some() {
-                                    let current_commitment =
-                                        signature_status.confirmation_status.as_ref().unwrap();
-                                    match current_commitment {
-                                        TransactionConfirmationStatus::Processed => {}
-                                        TransactionConfirmationStatus::Confirmed
-                                        | TransactionConfirmationStatus::Finalized => {
-                                            println!("Transaction landed!");
-                                            println!("STATUS: {:?}", signature_status);
-                                            match signature_status.status {
-                                                Ok(_) => {
-                                                    let _ =
-                                                        tx_result_sender.send(Ok(sigs[0])).await;
-                                                    return;
-                                                }
-                                                Err(_) => {
-                                                    let _ = tx_result_sender
-                                                        .send(
-                                                            Err("Transaction Failed.".to_string()),
-                                                        )
-                                                        .await;
-                                                    return;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Handle confirmation errors
-                    Err(err) => {
-                        println!("{:?}", err.kind().to_string());
-                    }
-                }
-
-                // hash expiration checks
-                let current_blockheight = client.get_block_height().await.unwrap();
-                //println!("Last valid blockheight: {}", last_valid_blockheight);
-                //println!("Current blockheight: {}", current_blockheight);
-
-                if current_blockheight > last_valid_blockheight {
-                    let err = Err("Last valid blockheight exceeded!".to_string());
-                    let _ = tx_result_sender.send(err).await;
-                    return;
-                }
-                // sleep 500ms to allow confirmations to potentially land
-                sleep(Duration::from_millis(500)).await;
-            }
-        });
-
-        let client = rpc_client.clone();
+        let confirms_thread_handle = tokio::spawn(MinerV2::confirm_sent_signatures(
+            tx_settings.confirm_service.clone(),
+            sig_checks_receiver,
+            tx_result_sender,
+            last_valid_blockheight,
+        ));
+
+        let client = tx_settings.send_client.clone();
+        let rpc_limit = tx_settings.rpc_limit.clone();
         let sender_thread_handle = tokio::spawn(async move {
             let sig_checks_sender = sig_checks_sender.clone();
+            // Set once the rpc reports the tx as already processed, resending can only duplicate it
+            let in_flight = Arc::new(AtomicBool::new(false));
             loop {
                 let sig_checks_sender = sig_checks_sender.clone();
                 let tx = tx.clone();
                 let client = client.clone();
+                let in_flight_1 = in_flight.clone();
+                // Wait for a slot rather than fail when --max-rpc-concurrency is saturated
+                let permit = rpc_permit(rpc_limit.as_ref()).await;
                 tokio::spawn(async move {
+                    let _permit = permit;
                     // send off tx and get sig
                     let sig_checks_sender = sig_checks_sender.clone();
 
-                    if let Ok(sig) = client.send_transaction_with_config(&tx, send_cfg).await {
-                        match sig_checks_sender.send(Ok(sig)).await {
-                            Ok(_) => {}
-                            Err(_) => {
+                    match client.send_transaction_with_config(&tx, send_cfg).await {
+                        Ok(sig) => {
+                            if sig_checks_sender.send(Ok(sig)).await.is_err() {
                                 return;
                             }
                         }
-                    } else {
-                        // Program will still keep trying until last_valid_blockheight expires
-                        // Transactions that get Err from RPC can still land.
-                        // TODO: log errors to see what they are and if any other handling needs to
-                        // be done.
+                        Err(e) if is_already_processed(&e) => {
+                            in_flight_1.store(true, Ordering::Relaxed);
+                            let _ = sig_checks_sender.send(Ok(tx.signatures[0])).await;
+                        }
+                        Err(_) => {
+                            // Program will still keep trying until last_valid_blockheight expires
+                            // Transactions that get Err from RPC can still land.
+                            // TODO: log errors to see what they are and if any other handling needs to
+                            // be done.
+                        }
                     };
                 });
                 // sleep 100ms (allowing 10 sends per second)
                 sleep(Duration::from_millis(send_interval)).await;
+                if in_flight.load(Ordering::Relaxed) {
+                    debug!("Transaction already processed, waiting for confirmation.");
+                    return;
+                }
             }
         });
 
         // wait for a tx result to come through
-        let res = tx_result_receiver.recv().await.unwrap();
+        let res =
+            MinerV2::wait_for_tx_result(&mut tx_result_receiver, tx_settings.confirm_timeout).await;
         confirms_thread_handle.abort();
         sender_thread_handle.abort();
         let tx_time_elapsed = tx_sent_at.elapsed().unwrap().as_secs();
-
-        match res {
-            Ok(res) => {
-                return Ok((res, tx_time_elapsed));
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        res.map(|sig| (sig, tx_time_elapsed))
     }
 
+    /// Registers the wallet's proof account if it doesn't have one yet, backing off between
+    /// failed attempts and giving up after `max_attempts`.
     pub async fn register(
         rpc_client: Arc<RpcClient>,
+        tx_settings: &TxSettings,
         signer: &Keypair,
         send_interval: u64,
-        _priority_fee: u64,
-    ) {
+        _priority_fee: PriorityFee,
+        max_attempts: u32,
+    ) -> Result<(), String> {
         // Return early if miner is already registered
         let proof_address = proof_pubkey(signer.pubkey());
         let client = rpc_client.clone();
         if client.get_account(&proof_address).await.is_ok() {
-            return;
+            return Ok(());
         }
 
         // Sign and send transaction.
-        println!("Generating challenge...");
+        debug!("Generating challenge...");
+        let mut delay = REGISTER_RETRY_DELAY;
+        let mut attempt = 0;
         loop {
+            attempt += 1;
             let ix = ore::instruction::register(signer.pubkey());
             let mut tx = Transaction::new_with_payer(&[ix.clone()], Some(&signer.pubkey()));
-            let (hash, last_valid_blockheight) = rpc_client
-                .get_latest_blockhash_with_commitment(rpc_client.commitment())
-                .await
-                .unwrap();
-            tx.sign(&[&signer], hash);
 
-            println!("Simulating tx...");
-            let sim_res = rpc_client
-                .simulate_transaction_with_config(
+            // Simulate against the rpc's latest blockhash, then fetch a fresh one to sign with
+            // so the simulation can't eat into the blockhash's lifetime.
+            debug!("Simulating tx...");
+            let sim_res = tx_settings
+                .simulate_transaction(
+                    &rpc_client,
                     &tx,
                     RpcSimulateTransactionConfig {
-                        sig_verify: true,
-                        replace_recent_blockhash: false,
+                        sig_verify: false,
+                        replace_recent_blockhash: true,
                         commitment: Some(rpc_client.commitment()),
                         encoding: Some(UiTransactionEncoding::Base64),
                         accounts: None,
-                        min_context_slot: Some(last_valid_blockheight),
+                        min_context_slot: None,
                         inner_instructions: true,
                     },
                 )
@@ -1014,17 +3407,32 @@ impl MinerV2 {
             match sim_res {
                 Ok(sim_res) => {
                     if let Some(err) = sim_res.value.err {
-                        println!("Simulaton error: {:?}", err);
+                        warn!("Simulaton error: {:?}", err);
                     } else {
-                        println!("Simulaton succeeded");
+                        debug!("Simulaton succeeded");
                     }
                 }
                 Err(err) => {
-                    println!("Simulaton error: {:?}", err);
+                    warn!("Simulaton error: {:?}", err);
                 }
             }
 
-            println!(
+            let (hash, last_valid_blockheight) =
+                match BlockhashCache::latest_blockhash(&rpc_client).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        if attempt >= max_attempts {
+                            return Err(format!("gave up after {} attempts: {}", attempt, e));
+                        }
+                        warn!("{}, retrying in {}ms...", e, delay.as_millis());
+                        sleep(delay).await;
+                        delay = (delay * 2).min(REGISTER_MAX_RETRY_DELAY);
+                        continue;
+                    }
+                };
+            tx.sign(&[&signer], hash);
+
+            info!(
                 "Sending signed tx every {} milliseconds until Confirmed or blockhash expires...",
                 send_interval
             );
@@ -1036,7 +3444,7 @@ impl MinerV2 {
                 min_context_slot: None,
             };
             let result = MinerV2::send_and_confirm_transaction(
-                rpc_client.clone(),
+                tx_settings,
                 tx,
                 last_valid_blockheight,
                 send_interval,
@@ -1046,94 +3454,39 @@ impl MinerV2 {
 
             match result {
                 Ok((sig, tx_time_elapsed)) => {
-                    println!("Success: {}", sig);
-                    println!("Took: {} seconds", tx_time_elapsed);
-                    break;
+                    info!("Success: {}", sig);
+                    info!("Took: {} seconds", tx_time_elapsed);
+                    return Ok(());
                 }
                 Err(e) => {
-                    println!("Error: {}", e);
+                    if attempt >= max_attempts {
+                        return Err(format!("gave up after {} attempts: {}", attempt, e));
+                    }
+                    warn!("Error: {}, retrying in {}ms...", e, delay.as_millis());
+                    sleep(delay).await;
+                    delay = (delay * 2).min(REGISTER_MAX_RETRY_DELAY);
                 }
             }
         }
     }
 
-    pub fn find_next_hash_par(
-        signer: &Keypair,
-        hash: KeccakHash,
-        difficulty: KeccakHash,
-        threads: u64,
-    ) -> (KeccakHash, u64) {
-        let found_solution = Arc::new(AtomicBool::new(false));
-        let solution = Arc::new(Mutex::<(KeccakHash, u64)>::new((
-            KeccakHash::new_from_array([0; 32]),
-            0,
-        )));
-        let pubkey = signer.pubkey();
-        let thread_handles: Vec<_> = (0..threads)
-            .map(|i| {
-                std::thread::spawn({
-                    let found_solution = found_solution.clone();
-                    let solution = solution.clone();
-                    let mut stdout = stdout();
-                    move || {
-                        let n = u64::MAX.saturating_div(threads).saturating_mul(i);
-                        let mut next_hash: KeccakHash;
-                        let mut nonce: u64 = n;
-                        loop {
-                            next_hash = hashv(&[
-                                hash.to_bytes().as_slice(),
-                                pubkey.to_bytes().as_slice(),
-                                nonce.to_le_bytes().as_slice(),
-                            ]);
-                            if nonce % 10_000 == 0 {
-                                if found_solution.load(std::sync::atomic::Ordering::Relaxed) {
-                                    return;
-                                }
-                                if n == 0 {
-                                    stdout
-                                        .write_all(
-                                            format!("\r{}", next_hash.to_string()).as_bytes(),
-                                        )
-                                        .ok();
-                                }
-                            }
-                            if next_hash.le(&difficulty) {
-                                stdout
-                                    .write_all(format!("\r{}", next_hash.to_string()).as_bytes())
-                                    .ok();
-                                found_solution.store(true, std::sync::atomic::Ordering::Relaxed);
-                                let mut w_solution = solution.lock().expect("failed to lock mutex");
-                                *w_solution = (next_hash, nonce);
-                                return;
-                            }
-                            nonce += 1;
-                        }
-                    }
-                })
-            })
-            .collect();
-
-        for thread_handle in thread_handles {
-            thread_handle.join().unwrap();
-        }
-
-        let r_solution = solution.lock().expect("Failed to get lock");
-        *r_solution
-    }
-
     pub async fn send_and_confirm(
         signer: &Keypair,
         rpc_client: Arc<RpcClient>,
+        tx_settings: &TxSettings,
         ixs: &[Instruction],
         dynamic_cus: bool,
         send_interval: u64,
-        priority_fee: u64,
+        priority_fee: PriorityFee,
     ) -> Result<(Signature, u64), String> {
         let client = rpc_client.clone();
 
         // Return error if balance is zero
-        let balance = client.get_balance(&signer.pubkey()).await.unwrap();
-        if balance <= 0 {
+        let balance = client
+            .get_balance(&signer.pubkey())
+            .await
+            .map_err(|e| format!("Failed to load SOL balance: {}", e))?;
+        if balance == 0 {
             return Err("Insufficient Sol balance".to_string());
             // return Err(ClientError {
             //     request: None,
@@ -1142,10 +3495,6 @@ impl MinerV2 {
         }
 
         // Build tx
-        let (_hash, slot) = client
-            .get_latest_blockhash_with_commitment(rpc_client.commitment())
-            .await
-            .unwrap();
         let send_cfg = RpcSendTransactionConfig {
             skip_preflight: true,
             preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -1158,8 +3507,9 @@ impl MinerV2 {
         // Simulate tx
         let mut sim_attempts = 0;
         'simulate: loop {
-            let sim_res = client
-                .simulate_transaction_with_config(
+            let sim_res = tx_settings
+                .simulate_transaction(
+                    &client,
                     &tx,
                     RpcSimulateTransactionConfig {
                         sig_verify: false,
@@ -1167,7 +3517,7 @@ impl MinerV2 {
                         commitment: Some(rpc_client.commitment()),
                         encoding: Some(UiTransactionEncoding::Base64),
                         accounts: None,
-                        min_context_slot: Some(slot),
+                        min_context_slot: None,
                         inner_instructions: false,
                     },
                 )
@@ -1175,16 +3525,17 @@ impl MinerV2 {
             match sim_res {
                 Ok(sim_res) => {
                     if let Some(err) = sim_res.value.err {
-                        println!("Simulaton error: {:?}", err);
+                        warn!("Simulaton error: {:?}", err);
                         sim_attempts += 1;
                     } else if let Some(units_consumed) = sim_res.value.units_consumed {
                         if dynamic_cus {
-                            println!("Dynamic CUs: {:?}", units_consumed);
-                            let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-                                units_consumed as u32 + 1000,
+                            debug!("Dynamic CUs: {:?}", units_consumed);
+                            let cu_limit = units_consumed as u32 + 1000;
+                            let cu_budget_ix =
+                                ComputeBudgetInstruction::set_compute_unit_limit(cu_limit);
+                            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                                priority_fee.cu_price(cu_limit),
                             );
-                            let cu_price_ix =
-                                ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
                             let mut final_ixs = vec![];
                             final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
                             final_ixs.extend_from_slice(ixs);
@@ -1194,7 +3545,7 @@ impl MinerV2 {
                     }
                 }
                 Err(err) => {
-                    println!("Simulaton error: {:?}", err);
+                    warn!("Simulaton error: {:?}", err);
                     sim_attempts += 1;
                 }
             }
@@ -1210,223 +3561,123 @@ impl MinerV2 {
         }
 
         // Update hash before sending transactions
-        let (hash, last_valid_blockheight) = client
-            .get_latest_blockhash_with_commitment(rpc_client.commitment())
+        let (hash, last_valid_blockheight) = BlockhashCache::latest_blockhash(&client)
             .await
-            .unwrap();
+            .map_err(|e| e.to_string())?;
 
         // Submit tx
         tx.sign(&[&signer], hash);
-        let tx_signed_unix_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
-
-        // let mut sigs = vec![];
-
-        // creates channel for sending the final tx result,
-        //     Result will be Ok(sig) or Err("blockhash expired")
-        let (tx_result_sender, mut tx_result_receiver): (
-            Sender<Result<Signature, String>>,
-            Receiver<Result<Signature, String>>,
-        ) = mpsc::channel(100);
-
-        // creates channel for getting sigs to confirm
-        let (sig_checks_sender, mut sig_checks_receiver): (
-            Sender<Result<Signature, String>>,
-            Receiver<Result<Signature, String>>,
-        ) = mpsc::channel(100);
-
-        // confirmation checks thread
-        let c_client = client.clone();
-        let confirms_thread_handle = tokio::spawn(async move {
-            let client = c_client;
-            let mut sigs: Vec<Signature> = vec![];
-            // receive sig_checks and add them to hashmap if new
-            loop {
-                if let Some(new_sig) = sig_checks_receiver.recv().await {
-                    if let Ok(new_sig) = new_sig {
-                        let mut is_new = true;
-                        for sig in sigs.iter() {
-                            if sig.to_string() == new_sig.to_string() {
-                                is_new = false;
-                            }
-                        }
-
-                        if is_new {
-                            sigs.push(new_sig);
-                        }
-                    }
-                }
-                // really should only have one sig here though
-                //for sig in sigs.iter {}
-                // confirmation checks
-                match client.get_signature_statuses(&sigs).await {
-                    Ok(signature_statuses) => {
-                        for signature_status in signature_statuses.value {
-                            if let Some(signature_status) = signature_status.as_ref() {
-                                if signature_status.confirmation_status.is_some() {
-                                    let current_commitment =
-                                        signature_status.confirmation_status.as_ref().unwrap();
-                                    match current_commitment {
-                                        TransactionConfirmationStatus::Processed => {}
-                                        TransactionConfirmationStatus::Confirmed
-                                        | TransactionConfirmationStatus::Finalized => {
-                                            println!("Transaction landed!");
-                                            let _ = tx_result_sender.send(Ok(sigs[0])).await;
-                                            return;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Handle confirmation errors
-                    Err(err) => {
-                        println!("{:?}", err.kind().to_string());
-                    }
-                }
-
-                // hash expiration checks
-                let current_blockheight = client.get_block_height().await.unwrap();
-                if current_blockheight > last_valid_blockheight {
-                    let err = Err("Last valid blockheight exceeded!".to_string());
-                    let _ = tx_result_sender.send(err).await;
-                    return;
-                }
-
-                // sleep 500ms to allow confirmations to potentially land
-                sleep(Duration::from_millis(500)).await;
-            }
-        });
-
-        let sender_thread_handle = tokio::spawn(async move {
-            let sig_checks_sender = sig_checks_sender.clone();
-            loop {
-                let sig_checks_sender = sig_checks_sender.clone();
-                let tx = tx.clone();
-                let client = client.clone();
-                tokio::spawn(async move {
-                    // send off tx and get sig
-                    let sig_checks_sender = sig_checks_sender.clone();
-
-                    if let Ok(sig) = client.send_transaction_with_config(&tx, send_cfg).await {
-                        match sig_checks_sender.send(Ok(sig)).await {
-                            Ok(_) => {}
-                            Err(_) => {
-                                return;
-                            }
-                        }
-                    } else {
-                        // Program will still keep trying until last_valid_blockheight expires
-                        // Transactions that get Err from RPC can still land.
-                        // TODO: log errors to see what they are and if any other handling needs to
-                        // be done.
-                    };
-                });
-                // sleep 100ms (allowing 10 sends per second)
-                sleep(Duration::from_millis(send_interval)).await;
-            }
-        });
-
-        // wait for a tx result to come through
-        let res = tx_result_receiver.recv().await.unwrap();
-        confirms_thread_handle.abort();
-        sender_thread_handle.abort();
-        let tx_finished_unix_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
-        let tx_time_elapsed = tx_finished_unix_ts - tx_signed_unix_ts;
-
-        match res {
-            Ok(res) => {
-                return Ok((res, tx_time_elapsed));
-            }
-            Err(_) => {
-                return Err("Blockheight exceeded".to_string());
-                // return Err(ClientError {
-                //     request: None,
-                //     kind: ClientErrorKind::Custom("Blockheight Exceeded for this signed transaction".into()),
-                // });
-            }
-        }
-
-        //return Err(ClientError {
-        //    request: None,
-        //    kind: ClientErrorKind::Custom("Max retries".into()),
-        //});
+        MinerV2::confirm_loop(
+            tx_settings,
+            tx,
+            last_valid_blockheight,
+            send_interval,
+            send_cfg,
+        )
+        .await
     }
 
-    pub async fn get_bus(rpc_client: &RpcClient, id: usize) -> ClientResult<Bus> {
-        let client = rpc_client;
-        let data = client.get_account_data(&BUS_ADDRESSES[id]).await?;
-        Ok(*Bus::try_from_bytes(&data).unwrap())
+    pub async fn get_busses(rpc_client: &RpcClient) -> ClientResult<Vec<BusAccount>> {
+        let accounts = rpc_client.get_multiple_accounts(&BUS_ADDRESSES).await?;
+        Ok(accounts
+            .into_iter()
+            .flatten()
+            .filter_map(|account| BusAccount::parse(&account.data).ok())
+            .collect())
     }
 
-    pub async fn get_ore_display_balance_v2(client: &RpcClient, pubkey: Pubkey) -> f64 {
-        let token_account_address =
-            spl_associated_token_account::get_associated_token_address(&pubkey, &ore::MINT_ADDRESS);
-        match client.get_token_account(&token_account_address).await {
-            Ok(token_account) => {
-                if let Some(token_account) = token_account {
-                    if let Some(amount) = token_account.token_amount.ui_amount {
-                        amount
-                    } else {
-                        0.0
-                    }
-                } else {
-                    0.0
-                }
-            }
-            Err(_) => 0.0
+    /// Display balance, `?` when it couldn't be loaded rather than a misleading zero.
+    pub async fn get_ore_display_balance(
+        client: &RpcClient,
+        pubkey: Pubkey,
+        decimals: usize,
+        token_program: TokenProgram,
+    ) -> String {
+        match get_ore_balance(client, pubkey, token_program).await {
+            Ok(amount) => format_ore(amount.unwrap_or(0), decimals),
+            Err(_) => "?".to_string(),
         }
     }
 
-    pub async fn get_ore_display_balance(client: &RpcClient, pubkey: Pubkey) -> String {
-        let token_account_address =
-            spl_associated_token_account::get_associated_token_address(&pubkey, &ore::MINT_ADDRESS);
-        match client.get_token_account(&token_account_address).await {
-            Ok(token_account) => {
-                if let Some(token_account) = token_account {
-                    token_account.token_amount.ui_amount_string
-                } else {
-                    "0.00".to_string()
-                }
-            }
-            Err(_) => "0.00".to_string(),
-        }
+    /// Moves `amount` ORE from the signer's token account to `destination`.
+    pub async fn transfer_ore(
+        client: Arc<RpcClient>,
+        tx_settings: &TxSettings,
+        signer: &Keypair,
+        source: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+        send_interval: u64,
+        priority_fee: PriorityFee,
+        token_program: TokenProgram,
+        decimals: usize,
+    ) -> Result<Signature, String> {
+        let mut ix = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &source,
+            &destination,
+            &signer.pubkey(),
+            &[],
+            amount,
+        )
+        .map_err(|e| e.to_string())?;
+        // Token-2022 uses the same transfer layout, spl_token just refuses to build it
+        ix.program_id = token_program.id();
+        let cu_limit_ix =
+            ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_TOKEN_TRANSFER);
+        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+            priority_fee.cu_price(CU_LIMIT_TOKEN_TRANSFER),
+        );
+        info!(
+            "Transferring {} ORE to {}...",
+            format_ore(amount, decimals),
+            destination
+        );
+        let (sig, _) = MinerV2::send_and_confirm(
+            signer,
+            client,
+            tx_settings,
+            &[cu_limit_ix, cu_price_ix, ix],
+            false,
+            send_interval,
+            priority_fee,
+        )
+        .await?;
+        info!("Transferred: {}", sig);
+        Ok(sig)
     }
 
+    /// Creates `owner`'s ORE token account if it doesn't exist yet, paid by `signer`, and
+    /// returns its address.
     pub async fn initialize_ata(
         client: Arc<RpcClient>,
+        tx_settings: &TxSettings,
         signer: &Keypair,
-        priority_fee: u64,
+        owner: Pubkey,
+        priority_fee: PriorityFee,
         send_interval: u64,
-    ) -> Pubkey {
+        token_program: TokenProgram,
+    ) -> Result<Pubkey, String> {
         // Build instructions.
-        let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
-            &signer.pubkey(),
-            &ore::MINT_ADDRESS,
-        );
+        let token_account_pubkey = token_program.ore_token_account(&owner);
 
         // Check if ata already exists
         if let Ok(Some(_ata)) = client.get_token_account(&token_account_pubkey).await {
-            return token_account_pubkey;
+            return Ok(token_account_pubkey);
         }
 
         // Sign and send transaction.
         let ix = spl_associated_token_account::instruction::create_associated_token_account(
             &signer.pubkey(),
-            &signer.pubkey(),
+            &owner,
             &ore::MINT_ADDRESS,
-            &spl_token::id(),
+            &token_program.id(),
         );
-        println!("Creating token account {}...", token_account_pubkey);
+        info!("Creating token account {}...", token_account_pubkey);
         match MinerV2::send_and_confirm(
             &signer,
             client.clone(),
+            tx_settings,
             &[ix],
             true,
             send_interval,
@@ -1434,15 +3685,258 @@ impl MinerV2 {
         )
         .await
         {
-            Ok(_sig) => println!("Created token account {:?}", token_account_pubkey),
-            Err(e) => println!("Transaction failed: {:?}", e),
+            Ok(_sig) => {
+                info!("Created token account {:?}", token_account_pubkey);
+                Ok(token_account_pubkey)
+            }
+            Err(e) => Err(format!(
+                "Failed to create token account {}: {}",
+                token_account_pubkey, e
+            )),
         }
+    }
+}
+
+/// Signed transfer of `lamports` from `sender` to `recipient`, paid by the sender.
+fn sol_transfer_tx(
+    sender: &Keypair,
+    recipient: &Pubkey,
+    lamports: u64,
+    hash: Hash,
+) -> Transaction {
+    let ix = system_instruction::transfer(&sender.pubkey(), recipient, lamports);
+    let mut tx = Transaction::new_with_payer(&[ix], Some(&sender.pubkey()));
+    tx.sign(&[sender], hash);
+    tx
+}
 
-        // Return token account address
-        token_account_pubkey
+/// Whether a send failed only because the rpc already processed this exact transaction,
+/// meaning an earlier send is in flight and just needs confirming.
+fn is_already_processed(e: &ClientError) -> bool {
+    matches!(
+        e.get_transaction_error(),
+        Some(TransactionError::AlreadyProcessed)
+    ) || e.to_string().contains("already been processed")
+}
+
+/// Runs one of the mine pipeline tasks, turning a panic into an error that names the task.
+/// A task that gives up returns its own error.
+async fn pipeline_task(
+    name: &'static str,
+    task: impl Future<Output = Result<(), String>>,
+) -> Result<(), String> {
+    match AssertUnwindSafe(task).catch_unwind().await {
+        Ok(Ok(())) => Err(format!("{} task stopped unexpectedly", name)),
+        Ok(Err(e)) => Err(e),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(format!("{} task panicked: {}", name, message))
+        }
     }
 }
 
 fn lamports_to_sol(lamports: u64) -> f64 {
     (lamports as f64) / (LAMPORTS_PER_SOL as f64)
 }
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_rpc_client::{
+        mock_sender::{MockSender, Mocks},
+        rpc_client::RpcClientConfig,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_sdk::commitment_config::CommitmentConfig;
+
+    use super::*;
+    use crate::utils::get_latest_blockhash;
+
+    fn queued_tx(encoded_unsigned_tx: String) -> TransactionQueueMessage {
+        TransactionQueueMessage {
+            wallets: vec![Keypair::new().to_base58_string()],
+            bus_ids: vec![3],
+            nonces: vec![42],
+            encoded_unsigned_tx,
+            hash_time_elapsed: 7,
+            expected_rewards: 11,
+            priority_fee: PriorityFee::PerCu(0),
+        }
+    }
+
+    #[test]
+    fn decode_tx_round_trips() {
+        let payer = Pubkey::new_unique();
+        let ix = system_instruction::transfer(&payer, &Pubkey::new_unique(), 1);
+        let tx = Transaction::new_with_payer(&[ix], Some(&payer));
+        let encoded = BASE64.encode(bincode::serialize(&tx).unwrap());
+        assert_eq!(decode_tx(&encoded).unwrap(), tx);
+    }
+
+    #[test]
+    fn decode_tx_rejects_bad_input() {
+        assert!(decode_tx("not base64!").is_err());
+        assert!(decode_tx(&BASE64.encode([1, 2, 3])).is_err());
+    }
+
+    #[test]
+    fn dropped_result_fails_with_the_wallets_to_requeue() {
+        let mssg = queued_tx("not base64!".to_string());
+        let e = decode_tx(&mssg.encoded_unsigned_tx).unwrap_err();
+        let result = TransactionResultMessage::dropped(&mssg, e);
+        assert!(result.failed);
+        assert!(result.error.is_some());
+        assert_eq!(result.wallets, mssg.wallets);
+        assert_eq!(result.bus_ids, mssg.bus_ids);
+        assert_eq!(result.nonces, mssg.nonces);
+        assert_eq!(result.fee_paid, 0);
+    }
+
+    type RequestCounts = Arc<Mutex<HashMap<RpcRequest, usize>>>;
+
+    /// Mock rpc that counts the requests it answers, to see which path a send took.
+    struct CountingSender {
+        inner: MockSender,
+        counts: RequestCounts,
+    }
+
+    #[async_trait]
+    impl RpcSender for CountingSender {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            *self.counts.lock().unwrap().entry(request).or_default() += 1;
+            self.inner.send(request, params).await
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.inner.get_transport_stats()
+        }
+
+        fn url(&self) -> String {
+            self.inner.url()
+        }
+    }
+
+    /// `url` picks the mock's canned answers, e.g. "account_in_use" for failed statuses.
+    fn counting_client(url: &str, counts: RequestCounts, mocks: Mocks) -> Arc<RpcClient> {
+        let sender = CountingSender {
+            inner: MockSender::new_with_mocks(url, mocks),
+            counts,
+        };
+        Arc::new(RpcClient::new_sender(
+            sender,
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        ))
+    }
+
+    fn count(counts: &RequestCounts, request: RpcRequest) -> usize {
+        counts.lock().unwrap().get(&request).copied().unwrap_or(0)
+    }
+
+    // Both entry points have to send through confirm_loop, which resends through the send
+    // client and waits on the confirm service's signature status checks
+    #[tokio::test]
+    async fn entry_points_send_and_confirm_through_confirm_loop() {
+        let counts = RequestCounts::default();
+        let client = counting_client("succeeds", counts.clone(), Mocks::default());
+        let confirm_service = ConfirmService::start(client.clone(), 256, None);
+        let signer = Keypair::new();
+        let send_cfg = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..RpcSendTransactionConfig::default()
+        };
+        let ix = system_instruction::transfer(&signer.pubkey(), &Pubkey::new_unique(), 1);
+
+        let (hash, last_valid_blockheight) = get_latest_blockhash(&client).await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix.clone()],
+            Some(&signer.pubkey()),
+            &[&signer],
+            hash,
+        );
+        let sent = MinerV2::send_and_confirm_transaction(
+            &TxSettings::new(client.clone(), confirm_service.clone()),
+            tx.clone(),
+            last_valid_blockheight,
+            100,
+            send_cfg,
+        );
+        let (sig, _) = tokio::time::timeout(Duration::from_secs(30), sent)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sig, tx.signatures[0]);
+        assert!(count(&counts, RpcRequest::SendTransaction) >= 1);
+        assert!(count(&counts, RpcRequest::GetSignatureStatuses) >= 1);
+
+        // send_and_confirm simulates first, the default mock simulation has no units consumed
+        let mocks = [(
+            RpcRequest::SimulateTransaction,
+            json!({
+                "context": { "slot": 1 },
+                "value": { "err": null, "logs": [], "unitsConsumed": 1000 },
+            }),
+        )]
+        .into_iter()
+        .collect();
+        let send_counts = RequestCounts::default();
+        let send_client = counting_client("succeeds", send_counts.clone(), mocks);
+        let tx_settings = TxSettings::new(send_client.clone(), confirm_service);
+        let statuses_before = count(&counts, RpcRequest::GetSignatureStatuses);
+        let sent = MinerV2::send_and_confirm(
+            &signer,
+            send_client,
+            &tx_settings,
+            &[ix],
+            false,
+            100,
+            PriorityFee::PerCu(0),
+        );
+        tokio::time::timeout(Duration::from_secs(30), sent)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(count(&send_counts, RpcRequest::SendTransaction) >= 1);
+        assert!(count(&counts, RpcRequest::GetSignatureStatuses) > statuses_before);
+    }
+
+    // A tx that lands but fails reports the failure, not an expired blockhash
+    #[tokio::test]
+    async fn send_and_confirm_returns_failed_status() {
+        let counts = RequestCounts::default();
+        let mocks = [(
+            RpcRequest::SimulateTransaction,
+            json!({
+                "context": { "slot": 1 },
+                "value": { "err": null, "logs": [], "unitsConsumed": 1000 },
+            }),
+        )]
+        .into_iter()
+        .collect();
+        let client = counting_client("account_in_use", counts.clone(), mocks);
+        let confirm_service = ConfirmService::start(client.clone(), 256, None);
+        let tx_settings = TxSettings::new(client.clone(), confirm_service);
+        let signer = Keypair::new();
+        let ix = system_instruction::transfer(&signer.pubkey(), &Pubkey::new_unique(), 1);
+        let sent = MinerV2::send_and_confirm(
+            &signer,
+            client,
+            &tx_settings,
+            &[ix],
+            false,
+            100,
+            PriorityFee::PerCu(0),
+        );
+        let e = tokio::time::timeout(Duration::from_secs(30), sent)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(e, TX_FAILED_ERROR);
+        assert!(count(&counts, RpcRequest::GetSignatureStatuses) >= 1);
+    }
+}
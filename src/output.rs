@@ -0,0 +1,48 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How command results are printed, picked with the global `--output`. The final `RESULT`
+/// summary line is always text so it can be scraped from logs whatever the format. With json
+/// and csv it and every log line go to stderr, so stdout is only the document.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON document. ORE amounts are raw token units.
+    Json,
+    /// A header line, then one line per record. ORE amounts are raw token units.
+    Csv,
+}
+
+/// The results of a command, printed through `OutputFormat::print` instead of ad-hoc
+/// `println!`s so every format is available wherever one is.
+pub trait Output: Serialize {
+    /// Column names of the csv rows.
+    const CSV_HEADER: &'static [&'static str];
+
+    fn csv_rows(&self) -> Vec<Vec<String>>;
+
+    fn text(&self) -> Vec<String>;
+}
+
+impl OutputFormat {
+    pub fn print<O: Output>(&self, output: &O) {
+        match self {
+            OutputFormat::Text => {
+                for line in output.text() {
+                    println!("{}", line);
+                }
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string(output).unwrap()),
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(std::io::stdout());
+                writer.write_record(O::CSV_HEADER).unwrap();
+                for row in output.csv_rows() {
+                    writer.write_record(&row).unwrap();
+                }
+                writer.flush().unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use solana_program::native_token::lamports_to_sol;
+
+// Landed transactions the ratio is averaged over
+const WINDOW: usize = 10;
+
+/// Rolling ORE mined per SOL spent on fees over the last landed transactions, for
+/// `--min-ore-per-sol`.
+pub struct ProfitabilityGate {
+    min_ore_per_sol: f64,
+    // (ore mined, lamports spent) per landed transaction
+    samples: VecDeque<(u64, u64)>,
+    landed: u64,
+}
+
+impl ProfitabilityGate {
+    pub fn new(min_ore_per_sol: f64) -> Self {
+        Self {
+            min_ore_per_sol,
+            samples: VecDeque::with_capacity(WINDOW),
+            landed: 0,
+        }
+    }
+
+    pub fn record(&mut self, ore: u64, lamports: u64) {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((ore, lamports));
+        self.landed += 1;
+    }
+
+    /// ORE per SOL over the window, None until the window has filled.
+    pub fn ratio(&self) -> Option<f64> {
+        if self.samples.len() < WINDOW {
+            return None;
+        }
+        let ore: u64 = self.samples.iter().map(|(ore, _)| ore).sum();
+        let lamports: u64 = self.samples.iter().map(|(_, lamports)| lamports).sum();
+        if lamports == 0 {
+            return None;
+        }
+        let ore = ore as f64 / 10f64.powi(ore::TOKEN_DECIMALS as i32);
+        Some(ore / lamports_to_sol(lamports))
+    }
+
+    pub fn is_unprofitable(&self) -> bool {
+        self.ratio()
+            .map_or(false, |ratio| ratio < self.min_ore_per_sol)
+    }
+
+    /// Whether to log the ratio after this transaction, once per window.
+    pub fn should_log(&self) -> bool {
+        self.landed % WINDOW as u64 == 0
+    }
+
+    pub fn min_ore_per_sol(&self) -> f64 {
+        self.min_ore_per_sol
+    }
+}
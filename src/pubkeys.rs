@@ -0,0 +1,53 @@
+use serde::Serialize;
+use solana_sdk::signature::Signer;
+
+use crate::{
+    miner_v2::MinerV2,
+    output::{Output, OutputFormat},
+    summary::Summary,
+    utils::{load_keypairs, LoadKeypairsOptions},
+};
+
+/// Serialized as a plain JSON array of pubkeys.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct PubkeysOutput(Vec<String>);
+
+impl Output for PubkeysOutput {
+    const CSV_HEADER: &'static [&'static str] = &["pubkey"];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        self.0.iter().map(|pubkey| vec![pubkey.clone()]).collect()
+    }
+
+    fn text(&self) -> Vec<String> {
+        self.0.clone()
+    }
+}
+
+impl MinerV2 {
+    /// Prints the pubkey of every keypair in the directory. Reads local files only, no rpc.
+    pub async fn pubkeys(
+        wallets_directory_string: Option<String>,
+        output: OutputFormat,
+        summary: &mut Summary,
+    ) {
+        let Some(wallets_dir) = wallets_directory_string else {
+            summary.fail("no wallets supplied. Pass a directory of keypair files with `--miner-wallets <DIR>`");
+        };
+        let keypairs = match load_keypairs(&wallets_dir, &LoadKeypairsOptions::default()).await {
+            Ok(keypairs) => keypairs,
+            Err(e) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let pubkeys: Vec<String> = keypairs
+            .iter()
+            .map(|(_, signer)| signer.pubkey().to_string())
+            .collect();
+        summary.success += pubkeys.len() as u64;
+        output.print(&PubkeysOutput(pubkeys));
+    }
+}
@@ -1,24 +1,70 @@
 use std::str::FromStr;
 
+use serde::Serialize;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::signature::Signer;
 
-use crate::{utils::get_proof, Miner};
+use crate::{
+    output::{Output, OutputFormat},
+    summary::Summary,
+    utils::{format_ore, get_proof_v2},
+    Miner,
+};
+
+#[derive(Serialize)]
+struct RewardsOutput {
+    address: String,
+    claimable_rewards: u64,
+    #[serde(skip)]
+    decimals: usize,
+}
+
+impl Output for RewardsOutput {
+    const CSV_HEADER: &'static [&'static str] = &["address", "claimable_rewards"];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.address.clone(), self.claimable_rewards.to_string()]]
+    }
+
+    fn text(&self) -> Vec<String> {
+        vec![format!(
+            "{:} ORE",
+            format_ore(self.claimable_rewards, self.decimals)
+        )]
+    }
+}
 
 impl Miner {
-    pub async fn rewards(&self, address: Option<String>) {
+    pub async fn rewards(
+        &self,
+        address: Option<String>,
+        output: OutputFormat,
+        summary: &mut Summary,
+    ) {
         let address = if let Some(address) = address {
             if let Ok(address) = Pubkey::from_str(&address) {
                 address
             } else {
                 println!("Invalid address: {:?}", address);
+                summary.failed += 1;
                 return;
             }
         } else {
             self.signer().pubkey()
         };
-        let proof = get_proof(&self.rpc_client, address).await;
-        let amount = (proof.claimable_rewards as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64);
-        println!("{:} ORE", amount);
+        let proof = match get_proof_v2(&self.rpc_client, address).await {
+            Ok(proof) => proof,
+            Err(e) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        output.print(&RewardsOutput {
+            address: address.to_string(),
+            claimable_rewards: proof.claimable_rewards,
+            decimals: self.decimals,
+        });
+        summary.success += 1;
     }
 }
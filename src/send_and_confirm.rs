@@ -17,7 +17,7 @@ use solana_sdk::{
 use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
 use tokio::{sync::mpsc::{self, Receiver, Sender}, time::sleep};
 
-use crate::Miner;
+use crate::{utils::get_latest_blockhash, Miner};
 
 const RPC_RETRIES: usize = 0;
 const SIMULATION_RETRIES: usize = 4;
@@ -48,10 +48,6 @@ impl Miner {
         }
 
         // Build tx
-        let (_hash, slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
         let send_cfg = RpcSendTransactionConfig {
             skip_preflight: true,
             preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -64,8 +60,10 @@ impl Miner {
         // Simulate tx
         let mut sim_attempts = 0;
         'simulate: loop {
-            let sim_res = client
-                .simulate_transaction_with_config(
+            let sim_res = self
+                .tx_settings
+                .simulate_transaction(
+                    &client,
                     &tx,
                     RpcSimulateTransactionConfig {
                         sig_verify: false,
@@ -73,7 +71,7 @@ impl Miner {
                         commitment: Some(self.rpc_client.commitment()),
                         encoding: Some(UiTransactionEncoding::Base64),
                         accounts: None,
-                        min_context_slot: Some(slot),
+                        min_context_slot: None,
                         inner_instructions: false,
                     },
                 )
@@ -86,11 +84,12 @@ impl Miner {
                     } else if let Some(units_consumed) = sim_res.value.units_consumed {
                         if dynamic_cus {
                             println!("Dynamic CUs: {:?}", units_consumed);
-                            let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-                                units_consumed as u32 + 1000,
+                            let cu_limit = units_consumed as u32 + 1000;
+                            let cu_budget_ix =
+                                ComputeBudgetInstruction::set_compute_unit_limit(cu_limit);
+                            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                                self.priority_fee.cu_price(cu_limit),
                             );
-                            let cu_price_ix =
-                                ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
                             let mut final_ixs = vec![];
                             final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
                             final_ixs.extend_from_slice(ixs);
@@ -115,10 +114,7 @@ impl Miner {
         }
 
         // Update hash before sending transactions
-        let (hash, _slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
+        let (hash, _slot) = get_latest_blockhash(&client).await?;
 
         // Submit tx
         tx.sign(&[&signer], hash);
@@ -213,10 +209,6 @@ impl Miner {
         }
 
         // Build tx
-        let (_hash, slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
         let send_cfg = RpcSendTransactionConfig {
             skip_preflight: true,
             preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -229,8 +221,10 @@ impl Miner {
         // Simulate tx
         let mut sim_attempts = 0;
         'simulate: loop {
-            let sim_res = client
-                .simulate_transaction_with_config(
+            let sim_res = self
+                .tx_settings
+                .simulate_transaction(
+                    &client,
                     &tx,
                     RpcSimulateTransactionConfig {
                         sig_verify: false,
@@ -238,7 +232,7 @@ impl Miner {
                         commitment: Some(self.rpc_client.commitment()),
                         encoding: Some(UiTransactionEncoding::Base64),
                         accounts: None,
-                        min_context_slot: Some(slot),
+                        min_context_slot: None,
                         inner_instructions: false,
                     },
                 )
@@ -251,11 +245,12 @@ impl Miner {
                     } else if let Some(units_consumed) = sim_res.value.units_consumed {
                         if dynamic_cus {
                             println!("Dynamic CUs: {:?}", units_consumed);
-                            let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-                                units_consumed as u32 + 1000,
+                            let cu_limit = units_consumed as u32 + 1000;
+                            let cu_budget_ix =
+                                ComputeBudgetInstruction::set_compute_unit_limit(cu_limit);
+                            let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(
+                                self.priority_fee.cu_price(cu_limit),
                             );
-                            let cu_price_ix =
-                                ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
                             let mut final_ixs = vec![];
                             final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
                             final_ixs.extend_from_slice(ixs);
@@ -281,10 +276,9 @@ impl Miner {
         }
 
         // Update hash before sending transactions
-        let (hash, last_valid_blockheight) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+        let (hash, last_valid_blockheight) = get_latest_blockhash(&client)
             .await
-            .unwrap();
+            .map_err(|e| e.to_string())?;
 
         // Submit tx
         tx.sign(&[&signer], hash);
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::summary::Summary;
+
+/// Anonymized run metrics posted by `--share-stats`. Only aggregate rates and a coarse fee
+/// bucket are included: no pubkeys, signatures, balances or ORE/SOL amounts.
+#[derive(Serialize)]
+pub struct SharedStats {
+    pub command: &'static str,
+    pub landing_rate: f64,
+    pub avg_tx_seconds: f64,
+    pub priority_fee_bucket: &'static str,
+    pub rpc_region: Option<String>,
+}
+
+impl SharedStats {
+    pub fn from_summary(summary: &Summary, priority_fee: u64, rpc_region: Option<String>) -> Self {
+        Self {
+            command: summary.command,
+            landing_rate: round(summary.landing_rate(), 4),
+            avg_tx_seconds: round(summary.avg_tx_seconds(), 2),
+            priority_fee_bucket: priority_fee_bucket(priority_fee),
+            rpc_region,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize shared stats")
+    }
+
+    pub async fn post(&self, endpoint: &str) -> Result<(), String> {
+        let res = reqwest::Client::new()
+            .post(endpoint)
+            .header("Content-Type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .body(self.to_json())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("endpoint returned {}", res.status()));
+        }
+        Ok(())
+    }
+}
+
+// Rounds to `decimals` places so the posted rates stay coarse
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+fn priority_fee_bucket(priority_fee: u64) -> &'static str {
+    match priority_fee {
+        0 => "0",
+        1..=999 => "1-999",
+        1_000..=9_999 => "1k-10k",
+        10_000..=99_999 => "10k-100k",
+        100_000..=999_999 => "100k-1m",
+        _ => "1m+",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_json_escapes_region() {
+        let stats = SharedStats {
+            command: "mine",
+            landing_rate: round(0.123456, 4),
+            avg_tx_seconds: round(12.346, 2),
+            priority_fee_bucket: priority_fee_bucket(5_000),
+            rpc_region: Some("eu \"west\"".to_string()),
+        };
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["command"], "mine");
+        assert_eq!(json["landing_rate"], 0.1235);
+        assert_eq!(json["avg_tx_seconds"], 12.35);
+        assert_eq!(json["priority_fee_bucket"], "1k-10k");
+        assert_eq!(json["rpc_region"], "eu \"west\"");
+    }
+}
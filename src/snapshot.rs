@@ -0,0 +1,226 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use ore::TOKEN_DECIMALS;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use solana_sdk::signer::Signer;
+use spl_token::solana_program::program_pack::Pack;
+
+use crate::{
+    miner_v2::MinerV2,
+    summary::Summary,
+    utils::{
+        format_ore, load_keypairs, proof_pubkey, LoadKeypairsOptions, ProofAccount, TokenProgram,
+    },
+};
+
+const SNAPSHOT_HEADER: &str = "pubkey,sol_lamports,ore_raw,ore_claimable_raw";
+
+// getMultipleAccounts accepts at most 100 addresses per request
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Balances of one wallet in a snapshot file, all in raw units.
+#[derive(Clone, Copy, Default)]
+struct WalletBalances {
+    sol: u64,
+    ore: u64,
+    ore_claimable: u64,
+}
+
+impl MinerV2 {
+    pub async fn snapshot(
+        rpc_client: Arc<RpcClient>,
+        wallets_directory_string: Option<String>,
+        out: Option<String>,
+        token_program: TokenProgram,
+        summary: &mut Summary,
+    ) {
+        let Some(wallets_dir) = wallets_directory_string else {
+            println!("Please provide a wallets directory with `--miner-wallets <DIR>`.");
+            summary.failed += 1;
+            return;
+        };
+        let pubkeys: Vec<Pubkey> =
+            match load_keypairs(&wallets_dir, &LoadKeypairsOptions::default()).await {
+                Ok(keypairs) => keypairs.iter().map(|(_, signer)| signer.pubkey()).collect(),
+                Err(e) => {
+                    println!("{}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            };
+        println!("Found {} wallets", pubkeys.len());
+
+        let token_accounts: Vec<Pubkey> = pubkeys
+            .iter()
+            .map(|pubkey| token_program.ore_token_account(pubkey))
+            .collect();
+        let proof_addresses: Vec<Pubkey> =
+            pubkeys.iter().map(|pubkey| proof_pubkey(*pubkey)).collect();
+
+        let mut balances = vec![WalletBalances::default(); pubkeys.len()];
+        for start in (0..pubkeys.len()).step_by(MAX_ACCOUNTS_PER_REQUEST) {
+            let end = (start + MAX_ACCOUNTS_PER_REQUEST).min(pubkeys.len());
+            let (wallets, tokens, proofs) = match tokio::try_join!(
+                rpc_client.get_multiple_accounts(&pubkeys[start..end]),
+                rpc_client.get_multiple_accounts(&token_accounts[start..end]),
+                rpc_client.get_multiple_accounts(&proof_addresses[start..end]),
+            ) {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    println!("Failed to load wallet balances: {}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            };
+            for (i, balance) in balances[start..end].iter_mut().enumerate() {
+                if let Some(wallet) = &wallets[i] {
+                    balance.sol = wallet.lamports;
+                }
+                if let Some(token) = &tokens[i] {
+                    // Token-2022 accounts can carry extensions after the base account layout
+                    let len = token.data.len().min(spl_token::state::Account::LEN);
+                    if let Ok(token) = spl_token::state::Account::unpack(&token.data[..len]) {
+                        balance.ore = token.amount;
+                    }
+                }
+                if let Some(proof) = &proofs[i] {
+                    if let Ok(proof) = ProofAccount::parse(&proof.data) {
+                        balance.ore_claimable = proof.claimable_rewards;
+                    }
+                }
+            }
+        }
+
+        let taken_at = chrono::Utc::now();
+        let out = out
+            .unwrap_or_else(|| format!("snapshot-{}.csv", taken_at.format("%Y%m%dT%H%M%SZ")));
+        let mut contents = format!("# taken_at {}\n{}\n", taken_at.to_rfc3339(), SNAPSHOT_HEADER);
+        for (pubkey, balance) in pubkeys.iter().zip(balances.iter()) {
+            contents.push_str(&format!(
+                "{},{},{},{}\n",
+                pubkey, balance.sol, balance.ore, balance.ore_claimable
+            ));
+        }
+        if let Err(e) = tokio::fs::write(&out, contents).await {
+            println!("Failed to write snapshot {}: {}", out, e);
+            summary.failed += 1;
+            return;
+        }
+
+        let total = total_balances(balances.iter());
+        println!("Total Sol: {}", lamports_to_sol(total.sol as i128));
+        println!("Total Ore Balance: {}", format_ore(total.ore, TOKEN_DECIMALS as usize));
+        println!(
+            "Total Ore Claimable: {}",
+            format_ore(total.ore_claimable, TOKEN_DECIMALS as usize)
+        );
+        println!("Wrote snapshot of {} wallets to {}", pubkeys.len(), out);
+        summary.success += pubkeys.len() as u64;
+    }
+
+    pub async fn snapshot_diff(a: String, b: String, summary: &mut Summary) {
+        let (before, after) = match (read_snapshot(&a).await, read_snapshot(&b).await) {
+            (Ok(before), Ok(after)) => (before, after),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+
+        let mut pubkeys: Vec<&String> = before.keys().chain(after.keys()).collect();
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        println!("Wallet deltas from {} to {}:", a, b);
+        for pubkey in pubkeys {
+            let (old, new) = (before.get(pubkey), after.get(pubkey));
+            let note = match (old, new) {
+                (None, Some(_)) => " (added)",
+                (Some(_), None) => " (removed)",
+                _ => "",
+            };
+            let old = old.copied().unwrap_or_default();
+            let new = new.copied().unwrap_or_default();
+            println!(
+                "{}{}: SOL {} ORE {} Claimable {}",
+                pubkey,
+                note,
+                lamports_to_sol(new.sol as i128 - old.sol as i128),
+                format_ore_delta(old.ore, new.ore),
+                format_ore_delta(old.ore_claimable, new.ore_claimable)
+            );
+        }
+
+        let old = total_balances(before.values());
+        let new = total_balances(after.values());
+        println!("\nFleet totals:");
+        println!(
+            "SOL: {} -> {} ({})",
+            lamports_to_sol(old.sol as i128),
+            lamports_to_sol(new.sol as i128),
+            lamports_to_sol(new.sol as i128 - old.sol as i128)
+        );
+        println!(
+            "ORE: {} -> {} ({})",
+            format_ore(old.ore, TOKEN_DECIMALS as usize),
+            format_ore(new.ore, TOKEN_DECIMALS as usize),
+            format_ore_delta(old.ore, new.ore)
+        );
+        println!(
+            "Claimable: {} -> {} ({})",
+            format_ore(old.ore_claimable, TOKEN_DECIMALS as usize),
+            format_ore(new.ore_claimable, TOKEN_DECIMALS as usize),
+            format_ore_delta(old.ore_claimable, new.ore_claimable)
+        );
+        summary.success += 1;
+    }
+}
+
+async fn read_snapshot(path: &str) -> Result<BTreeMap<String, WalletBalances>, String> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read snapshot {}: {}", path, e))?;
+    let mut balances = BTreeMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') || line == SNAPSHOT_HEADER {
+            continue;
+        }
+        let invalid = || format!("Invalid snapshot line {} in {}: {}", i + 1, path, line);
+        let fields: Vec<&str> = line.split(',').collect();
+        let [pubkey, sol, ore, ore_claimable] = fields[..] else {
+            return Err(invalid());
+        };
+        let parse = |s: &str| s.parse::<u64>().map_err(|_| invalid());
+        balances.insert(
+            pubkey.to_string(),
+            WalletBalances {
+                sol: parse(sol)?,
+                ore: parse(ore)?,
+                ore_claimable: parse(ore_claimable)?,
+            },
+        );
+    }
+    Ok(balances)
+}
+
+fn total_balances<'a>(balances: impl Iterator<Item = &'a WalletBalances>) -> WalletBalances {
+    balances.fold(WalletBalances::default(), |total, b| WalletBalances {
+        sol: total.sol + b.sol,
+        ore: total.ore + b.ore,
+        ore_claimable: total.ore_claimable + b.ore_claimable,
+    })
+}
+
+fn format_ore_delta(old: u64, new: u64) -> String {
+    if new >= old {
+        format!("+{}", format_ore(new - old, TOKEN_DECIMALS as usize))
+    } else {
+        format!("-{}", format_ore(old - new, TOKEN_DECIMALS as usize))
+    }
+}
+
+fn lamports_to_sol(lamports: i128) -> String {
+    format!("{}", lamports as f64 / LAMPORTS_PER_SOL as f64)
+}
@@ -0,0 +1,93 @@
+use std::time::Instant;
+
+use crate::utils::format_ore;
+
+/// Exit code for a run that completed without errors but found no work, e.g. a claim where
+/// no wallet had rewards. Distinct from clap's usage error code 2.
+pub const NOTHING_TO_DO_EXIT_CODE: i32 = 3;
+
+/// Counters for the final `RESULT` line every command prints on completion. The line
+/// always has the same keys in the same order so it can be scraped from logs.
+#[derive(Clone, Copy)]
+pub struct Summary {
+    pub command: &'static str,
+    pub success: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    pub ore_mined: u64,
+    pub ore_claimed: u64,
+    pub lamports_spent: u64,
+    /// Total seconds landed transactions took to confirm.
+    pub tx_seconds: u64,
+    /// Set when the command had nothing to do, makes the process exit with
+    /// `NOTHING_TO_DO_EXIT_CODE`.
+    pub nothing_to_do: bool,
+    /// Print the `RESULT` line to stderr, set when stdout carries a json or csv document.
+    pub to_stderr: bool,
+    started_at: Instant,
+}
+
+impl Summary {
+    pub fn new(command: &'static str) -> Self {
+        Self {
+            command,
+            success: 0,
+            failed: 0,
+            skipped: 0,
+            ore_mined: 0,
+            ore_claimed: 0,
+            lamports_spent: 0,
+            tx_seconds: 0,
+            nothing_to_do: false,
+            to_stderr: false,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Prints the summary and exits with a nonzero code, used by `--fail-fast` to stop the
+    /// whole run on the first hard failure.
+    pub fn fail(&self, reason: &str) -> ! {
+        self.print();
+        eprintln!("error: {}", reason);
+        std::process::exit(1);
+    }
+
+    /// Fraction of attempted transactions that landed, 0 when nothing was attempted.
+    pub fn landing_rate(&self) -> f64 {
+        let attempted = self.success + self.failed;
+        if attempted == 0 {
+            return 0.0;
+        }
+        self.success as f64 / attempted as f64
+    }
+
+    pub fn avg_tx_seconds(&self) -> f64 {
+        if self.success == 0 {
+            return 0.0;
+        }
+        self.tx_seconds as f64 / self.success as f64
+    }
+
+    pub fn print(&self) {
+        let line = format!(
+            "RESULT command={} success={} failed={} skipped={} ore_mined={} ore_claimed={} sol_spent={} elapsed={}s",
+            self.command,
+            self.success,
+            self.failed,
+            self.skipped,
+            format_ore(self.ore_mined, ore::TOKEN_DECIMALS as usize),
+            format_ore(self.ore_claimed, ore::TOKEN_DECIMALS as usize),
+            (self.lamports_spent as f64) / (solana_program::native_token::LAMPORTS_PER_SOL as f64),
+            self.elapsed_secs()
+        );
+        if self.to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    pub fn elapsed_secs(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+}
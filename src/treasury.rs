@@ -1,27 +1,91 @@
+use serde::Serialize;
+
 use crate::{
-    utils::{get_treasury, treasury_tokens_pubkey},
+    output::{Output, OutputFormat},
+    summary::Summary,
+    utils::{format_ore, get_treasury, treasury_tokens_pubkey},
     Miner,
 };
 
+/// Program-wide treasury state.
+#[derive(Serialize)]
+struct TreasuryOutput {
+    admin: String,
+    balance: u64,
+    difficulty: String,
+    last_reset_at: i64,
+    reward_rate: u64,
+    total_claimed_rewards: u64,
+    #[serde(skip)]
+    decimals: usize,
+}
+
+impl Output for TreasuryOutput {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "admin",
+        "balance",
+        "difficulty",
+        "last_reset_at",
+        "reward_rate",
+        "total_claimed_rewards",
+    ];
+
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.admin.clone(),
+            self.balance.to_string(),
+            self.difficulty.clone(),
+            self.last_reset_at.to_string(),
+            self.reward_rate.to_string(),
+            self.total_claimed_rewards.to_string(),
+        ]]
+    }
+
+    fn text(&self) -> Vec<String> {
+        vec![
+            format!("{:} ORE", format_ore(self.balance, self.decimals)),
+            format!("Admin: {}", self.admin),
+            format!("Difficulty: {}", self.difficulty),
+            format!("Last reset at: {}", self.last_reset_at),
+            format!(
+                "Reward rate: {} ORE",
+                format_ore(self.reward_rate, self.decimals)
+            ),
+            format!(
+                "Total claimed rewards: {} ORE",
+                format_ore(self.total_claimed_rewards, self.decimals)
+            ),
+        ]
+    }
+}
+
 impl Miner {
-    pub async fn treasury(&self) {
+    pub async fn treasury(&self, output: OutputFormat, summary: &mut Summary) {
         let client = self.rpc_client.clone();
-        if let Ok(Some(treasury_tokens)) = client.get_token_account(&treasury_tokens_pubkey()).await
+        if let Ok(Some(treasury_tokens)) = client
+            .get_token_account(&treasury_tokens_pubkey(self.token_program))
+            .await
         {
-            let treasury = get_treasury(&self.rpc_client).await;
-            let balance = treasury_tokens.token_amount.ui_amount_string;
-            println!("{:} ORE", balance);
-            println!("Admin: {}", treasury.admin);
-            println!("Difficulty: {}", treasury.difficulty.to_string());
-            println!("Last reset at: {}", treasury.last_reset_at);
-            println!(
-                "Reward rate: {} ORE",
-                (treasury.reward_rate as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
-            );
-            println!(
-                "Total claimed rewards: {} ORE",
-                (treasury.total_claimed_rewards as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
-            );
+            let treasury = match get_treasury(&self.rpc_client).await {
+                Ok(treasury) => treasury,
+                Err(e) => {
+                    println!("{}", e);
+                    summary.failed += 1;
+                    return;
+                }
+            };
+            output.print(&TreasuryOutput {
+                admin: treasury.admin.to_string(),
+                balance: treasury_tokens.token_amount.amount.parse().unwrap_or(0),
+                difficulty: treasury.difficulty.to_string(),
+                last_reset_at: treasury.last_reset_at,
+                reward_rate: treasury.reward_rate,
+                total_claimed_rewards: treasury.total_claimed_rewards,
+                decimals: self.decimals,
+            });
+            summary.success += 1;
+        } else {
+            summary.failed += 1;
         }
     }
 }
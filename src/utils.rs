@@ -1,47 +1,549 @@
+use bip39::{Language, Mnemonic};
 use cached::proc_macro::cached;
+use clap::ValueEnum;
+use log::{info, warn};
+use serde::Serialize;
 use ore::{
     self,
-    state::{Proof, Treasury},
+    state::{Bus, Proof, Treasury},
     utils::AccountDeserialize,
-    MINT_ADDRESS, PROOF, TREASURY_ADDRESS,
+    BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION, MINT_ADDRESS, PROOF, TOKEN_DECIMALS, TREASURY_ADDRESS,
 };
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::{pubkey::Pubkey, sysvar};
-use solana_sdk::clock::Clock;
-use spl_associated_token_account::get_associated_token_address;
+use std::{
+    io::{stdout, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSimulateTransactionConfig,
+    rpc_request::RpcError,
+    rpc_response::{Response, RpcSimulateTransactionResult},
+};
+use solana_program::{native_token::lamports_to_sol, pubkey::Pubkey, sysvar};
+use solana_rpc_client::{http_sender::HttpSender, rpc_client::RpcClientConfig};
+use solana_sdk::{
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    derivation_path::DerivationPath,
+    keccak::Hash as KeccakHash,
+    signature::{read_keypair_file, Keypair},
+    signer::keypair::{
+        generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed_and_derivation_path,
+    },
+    signer::Signer,
+    transaction::Transaction,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{confirm_service::ConfirmService, failover_sender::FailoverSender};
+
+pub const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+pub const RPC_KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Error a transaction send gives up with after `--confirm-timeout-secs`.
+pub const CONFIRM_TIMEOUT_ERROR: &str = "confirmation timeout";
+
+/// Error for a transaction that landed but failed, its fee was still paid.
+pub const TX_FAILED_ERROR: &str = "Transaction Failed.";
+
+/// The clients and limits transactions are sent and confirmed with, from the global rpc
+/// flags.
+#[derive(Clone)]
+pub struct TxSettings {
+    /// Client transactions are sent through, `--send-rpc` when set.
+    pub send_client: Arc<RpcClient>,
+    /// Checks sent transactions against `--confirm-rpc` when set.
+    pub confirm_service: ConfirmService,
+    /// Caps how many transaction sends and confirmation checks are in flight at once, from
+    /// `--max-rpc-concurrency`.
+    pub rpc_limit: Option<Arc<Semaphore>>,
+    /// How long a simulation may take before it's abandoned, from `--sim-timeout`.
+    pub sim_timeout: Duration,
+    /// How long a transaction may take to confirm before it's abandoned, from
+    /// `--confirm-timeout-secs`. `None` waits until the blockhash expires.
+    pub confirm_timeout: Option<Duration>,
+    /// Lamports a wallet must keep after paying fees or sending SOL, from
+    /// `--min-sol-reserve`. 0 when not set.
+    pub min_sol_reserve: u64,
+}
+
+impl TxSettings {
+    /// Sends through `send_client` and confirms with `confirm_service`, with the flags'
+    /// defaults.
+    pub fn new(send_client: Arc<RpcClient>, confirm_service: ConfirmService) -> Self {
+        TxSettings {
+            send_client,
+            confirm_service,
+            rpc_limit: None,
+            sim_timeout: Duration::from_secs(10),
+            confirm_timeout: None,
+            min_sol_reserve: 0,
+        }
+    }
 
-pub async fn get_treasury(client: &RpcClient) -> Treasury {
-    let data = client
-        .get_account_data(&TREASURY_ADDRESS)
+    /// Errors if spending `lamports` from a wallet holding `balance` would leave it below
+    /// `--min-sol-reserve`. Always passes when no reserve is set.
+    pub fn keeps_sol_reserve(&self, balance: u64, lamports: u64) -> Result<(), String> {
+        let reserve = self.min_sol_reserve;
+        if reserve > 0 && balance.saturating_sub(lamports) < reserve {
+            return Err(format!(
+                "spending {} SOL of its {} SOL would leave it below the --min-sol-reserve of {} SOL",
+                lamports_to_sol(lamports),
+                lamports_to_sol(balance),
+                lamports_to_sol(reserve)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Like `keeps_sol_reserve`, loading the wallet's balance first. Skips the rpc call when
+    /// no reserve is set.
+    pub async fn check_sol_reserve(
+        &self,
+        client: &RpcClient,
+        wallet: &Pubkey,
+        lamports: u64,
+    ) -> Result<(), String> {
+        if self.min_sol_reserve == 0 {
+            return Ok(());
+        }
+        let balance = client
+            .get_balance(wallet)
+            .await
+            .map_err(|e| format!("Failed to load balance to check --min-sol-reserve: {}", e))?;
+        self.keeps_sol_reserve(balance, lamports)
+    }
+
+    /// Simulates `tx`, giving up after `--sim-timeout`. An rpc can stall on a
+    /// `min_context_slot` it never reaches, so a timeout is returned as an ordinary simulation
+    /// error and callers retry or move on as they would for any other.
+    pub async fn simulate_transaction(
+        &self,
+        client: &RpcClient,
+        tx: &Transaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> Result<Response<RpcSimulateTransactionResult>, ClientError> {
+        match tokio::time::timeout(
+            self.sim_timeout,
+            client.simulate_transaction_with_config(tx, config),
+        )
         .await
-        .expect("Failed to get treasury account");
-    *Treasury::try_from_bytes(&data).expect("Failed to parse treasury account")
+        {
+            Ok(res) => res,
+            Err(_) => Err(ClientErrorKind::Custom(format!(
+                "Simulation timed out after {} seconds",
+                self.sim_timeout.as_secs()
+            ))
+            .into()),
+        }
+    }
 }
 
-pub async fn get_proof(client: &RpcClient, authority: Pubkey) -> Proof {
-    let proof_address = proof_pubkey(authority);
-    let data = client
-        .get_account_data(&proof_address)
+/// Waits for a free slot under `limit`, held until the permit is dropped. `None` right away
+/// when there is no limit.
+pub async fn rpc_permit(limit: Option<&Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    limit?.clone().acquire_owned().await.ok()
+}
+
+/// Builds an rpc client. With more than one url the requests fail over between them, see
+/// `FailoverSender`. When `max_connections` is set the underlying http client keeps up
+/// to that many idle keep-alive connections open to the rpc host, so concurrent requests
+/// don't have to reconnect.
+pub fn new_rpc_client(
+    urls: &[String],
+    commitment: CommitmentConfig,
+    max_connections: Option<usize>,
+) -> RpcClient {
+    let config = RpcClientConfig::with_commitment(commitment);
+    let mut senders: Vec<HttpSender> = urls
+        .iter()
+        .map(|url| http_sender(url, max_connections))
+        .collect();
+    if senders.len() == 1 {
+        return RpcClient::new_sender(senders.remove(0), config);
+    }
+    RpcClient::new_sender(FailoverSender::new(senders), config)
+}
+
+fn http_sender(url: &str, max_connections: Option<usize>) -> HttpSender {
+    let Some(max_connections) = max_connections else {
+        return HttpSender::new_with_timeout(url, RPC_TIMEOUT);
+    };
+    let client = reqwest::Client::builder()
+        .default_headers(HttpSender::default_headers())
+        .timeout(RPC_TIMEOUT)
+        .pool_max_idle_per_host(max_connections)
+        .pool_idle_timeout(RPC_KEEP_ALIVE)
+        .tcp_keepalive(RPC_KEEP_ALIVE)
+        .build()
+        .expect("Failed to build rpc http client");
+    HttpSender::new_with_client(url, client)
+}
+
+/// Error returned by the account accessors below.
+#[derive(Debug)]
+pub enum AccountError {
+    /// The rpc request failed, including when the account doesn't exist.
+    Fetch { account: &'static str, reason: String },
+    /// The account data couldn't be deserialized.
+    Parse { account: &'static str },
+    /// The account deserialized, but its fields don't hold together, most likely because the
+    /// linked ore crate doesn't match the deployed program.
+    Invalid { account: &'static str, reason: String },
+}
+
+impl std::fmt::Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountError::Fetch { account, reason } => {
+                write!(f, "Failed to get {} account: {}", account, reason)
+            }
+            AccountError::Parse { account } => write!(f, "Failed to parse {} account", account),
+            AccountError::Invalid { account, reason } => write!(
+                f,
+                "Unexpected {} account: {}. The ore crate this was built with may not match the program",
+                account, reason
+            ),
+        }
+    }
+}
+
+/// Error returned by `get_latest_blockhash`.
+#[derive(Debug)]
+pub enum BlockhashError {
+    /// The rpc request itself failed.
+    Fetch { reason: String },
+    /// The rpc answered, but with an empty or unparseable blockhash.
+    Malformed { reason: String },
+}
+
+impl std::fmt::Display for BlockhashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockhashError::Fetch { reason } => {
+                write!(f, "Failed to get latest blockhash: {}", reason)
+            }
+            BlockhashError::Malformed { reason } => {
+                write!(f, "RPC returned an invalid latest blockhash: {}", reason)
+            }
+        }
+    }
+}
+
+impl From<BlockhashError> for ClientError {
+    fn from(e: BlockhashError) -> Self {
+        ClientError {
+            request: None,
+            kind: ClientErrorKind::Custom(e.to_string()),
+        }
+    }
+}
+
+/// Latest blockhash and last valid block height at the client's commitment.
+pub async fn get_latest_blockhash(client: &RpcClient) -> Result<(Hash, u64), BlockhashError> {
+    match client
+        .get_latest_blockhash_with_commitment(client.commitment())
+        .await
+    {
+        Ok((hash, _)) if hash == Hash::default() => Err(BlockhashError::Malformed {
+            reason: "empty blockhash".to_string(),
+        }),
+        Ok(res) => Ok(res),
+        Err(e) => match e.kind() {
+            ClientErrorKind::SerdeJson(_) | ClientErrorKind::RpcError(RpcError::ParseError(_)) => {
+                Err(BlockhashError::Malformed {
+                    reason: e.to_string(),
+                })
+            }
+            _ => Err(BlockhashError::Fetch {
+                reason: e.to_string(),
+            }),
+        },
+    }
+}
+
+async fn get_account_data(
+    client: &RpcClient,
+    address: &Pubkey,
+    account: &'static str,
+) -> Result<Vec<u8>, AccountError> {
+    client
+        .get_account_data(address)
+        .await
+        .map_err(|e| AccountError::Fetch {
+            account,
+            reason: e.to_string(),
+        })
+}
+
+// The ore account structs are only read by the parse fns below, which copy them into these
+// CLI-side structs and check the fields hold together. If the linked ore crate renames or
+// changes a field, the mismatch shows up here instead of as wrong numbers elsewhere.
+
+/// A miner's `Proof` account.
+#[derive(Clone, Copy, Debug)]
+pub struct ProofAccount {
+    pub authority: Pubkey,
+    pub claimable_rewards: u64,
+    pub hash: KeccakHash,
+    pub total_hashes: u64,
+    pub total_rewards: u64,
+}
+
+impl ProofAccount {
+    pub fn parse(data: &[u8]) -> Result<Self, AccountError> {
+        let proof =
+            Proof::try_from_bytes(data).map_err(|_| AccountError::Parse { account: "miner" })?;
+        // Claims only ever take from rewards the proof has earned
+        if proof.claimable_rewards > proof.total_rewards {
+            return Err(AccountError::Invalid {
+                account: "miner",
+                reason: format!(
+                    "claimable rewards {} exceed total rewards {}",
+                    proof.claimable_rewards, proof.total_rewards
+                ),
+            });
+        }
+        Ok(Self {
+            authority: proof.authority,
+            claimable_rewards: proof.claimable_rewards,
+            hash: proof.hash.into(),
+            total_hashes: proof.total_hashes,
+            total_rewards: proof.total_rewards,
+        })
+    }
+}
+
+/// The program-wide `Treasury` account.
+#[derive(Clone, Copy, Debug)]
+pub struct TreasuryAccount {
+    pub admin: Pubkey,
+    pub difficulty: KeccakHash,
+    pub last_reset_at: i64,
+    pub reward_rate: u64,
+    pub total_claimed_rewards: u64,
+}
+
+impl TreasuryAccount {
+    pub fn parse(data: &[u8]) -> Result<Self, AccountError> {
+        let treasury = Treasury::try_from_bytes(data)
+            .map_err(|_| AccountError::Parse { account: "treasury" })?;
+        let invalid = |reason: &str| AccountError::Invalid {
+            account: "treasury",
+            reason: reason.to_string(),
+        };
+        let difficulty: KeccakHash = treasury.difficulty.into();
+        if difficulty == KeccakHash::default() {
+            return Err(invalid("difficulty is zero, no hash could meet it"));
+        }
+        // Valid between epochs, mining just earns nothing until the next reset
+        if treasury.reward_rate == 0 {
+            warn!("The treasury reward rate is zero, mining earns no ORE until the next reset.");
+        }
+        if treasury.last_reset_at < 0 {
+            return Err(invalid("last reset is before the unix epoch"));
+        }
+        Ok(Self {
+            admin: treasury.admin,
+            difficulty,
+            last_reset_at: treasury.last_reset_at,
+            reward_rate: treasury.reward_rate,
+            total_claimed_rewards: treasury.total_claimed_rewards,
+        })
+    }
+}
+
+/// One of the `BUS_COUNT` reward `Bus` accounts.
+#[derive(Clone, Copy, Debug)]
+pub struct BusAccount {
+    pub id: usize,
+    pub rewards: u64,
+}
+
+impl BusAccount {
+    pub fn parse(data: &[u8]) -> Result<Self, AccountError> {
+        let bus = Bus::try_from_bytes(data).map_err(|_| AccountError::Parse { account: "bus" })?;
+        if bus.id >= BUS_COUNT as u64 {
+            return Err(AccountError::Invalid {
+                account: "bus",
+                reason: format!("id {} is out of range for {} busses", bus.id, BUS_COUNT),
+            });
+        }
+        Ok(Self {
+            id: bus.id as usize,
+            rewards: bus.rewards,
+        })
+    }
+}
+
+pub async fn get_treasury(client: &RpcClient) -> Result<TreasuryAccount, AccountError> {
+    let data = get_account_data(client, &TREASURY_ADDRESS, "treasury").await?;
+    TreasuryAccount::parse(&data)
+}
+
+pub async fn get_bus(client: &RpcClient, id: usize) -> Result<BusAccount, AccountError> {
+    let data = get_account_data(client, &BUS_ADDRESSES[id], "bus").await?;
+    BusAccount::parse(&data)
+}
+
+#[deprecated(note = "panics on a missing or invalid account, use get_proof_v2")]
+#[allow(dead_code)]
+pub async fn get_proof(client: &RpcClient, authority: Pubkey) -> ProofAccount {
+    get_proof_v2(client, authority)
         .await
-        .expect("Failed to get miner account");
-    *Proof::try_from_bytes(&data).expect("Failed to parse miner account")
+        .expect("Failed to get miner account")
 }
 
-pub async fn get_proof_v2(client: &RpcClient, authority: Pubkey) -> Result<Proof, String> {
+pub async fn get_proof_v2(
+    client: &RpcClient,
+    authority: Pubkey,
+) -> Result<ProofAccount, AccountError> {
     let proof_address = proof_pubkey(authority);
-    let data = client.get_account_data(&proof_address).await;
-    match data {
-        Ok(data) => return Ok(*Proof::try_from_bytes(&data).unwrap()),
-        Err(_) => return Err("Failed to get miner account".to_string()),
+    let data = get_account_data(client, &proof_address, "miner").await?;
+    let proof = ProofAccount::parse(&data)?;
+    if proof.authority != authority {
+        return Err(AccountError::Invalid {
+            account: "miner",
+            reason: format!("authority is {}, expected {}", proof.authority, authority),
+        });
     }
+    Ok(proof)
 }
 
-pub async fn get_clock_account(client: &RpcClient) -> Clock {
-    let data = client
-        .get_account_data(&sysvar::clock::ID)
+// getMultipleAccounts accepts at most 100 accounts per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Proofs of every authority, fetched in batches. None for a proof that doesn't exist or
+/// can't be parsed.
+pub async fn get_proofs(
+    client: &RpcClient,
+    authorities: &[Pubkey],
+) -> Result<Vec<Option<ProofAccount>>, AccountError> {
+    let mut proofs = Vec::with_capacity(authorities.len());
+    for chunk in authorities.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let addresses: Vec<Pubkey> = chunk
+            .iter()
+            .map(|authority| proof_pubkey(*authority))
+            .collect();
+        let accounts = client
+            .get_multiple_accounts(&addresses)
+            .await
+            .map_err(|e| AccountError::Fetch {
+                account: "miner",
+                reason: e.to_string(),
+            })?;
+        proofs.extend(accounts.into_iter().map(|account| {
+            account.and_then(|account| ProofAccount::parse(&account.data).ok())
+        }));
+    }
+    Ok(proofs)
+}
+
+/// The cluster's `Clock` sysvar. Epoch timing compares against its consensus
+/// `unix_timestamp`, never local time, so a drifting system clock can't skew it.
+pub async fn get_clock(client: &RpcClient) -> Result<Clock, AccountError> {
+    let data = get_account_data(client, &sysvar::clock::ID, "clock").await?;
+    bincode::deserialize::<Clock>(&data).map_err(|_| AccountError::Parse { account: "clock" })
+}
+
+/// The owner's ORE balance in raw units. `Ok(None)` means the token account doesn't exist,
+/// so the balance really is zero, as opposed to an error where it's unknown.
+pub async fn get_ore_balance(
+    client: &RpcClient,
+    owner: Pubkey,
+    token_program: TokenProgram,
+) -> Result<Option<u64>, AccountError> {
+    let token_account = client
+        .get_token_account(&token_program.ore_token_account(&owner))
         .await
-        .expect("Failed to get miner account");
-    bincode::deserialize::<Clock>(&data).expect("Failed to deserialize clock")
+        .map_err(|e| AccountError::Fetch {
+            account: "token",
+            reason: e.to_string(),
+        })?;
+    token_account
+        .map(|token_account| {
+            token_account
+                .token_amount
+                .amount
+                .parse()
+                .map_err(|_| AccountError::Parse { account: "token" })
+        })
+        .transpose()
+}
+
+/// On-chain unix timestamp at which the treasury's current epoch ends and can be reset.
+pub fn epoch_end(treasury: &TreasuryAccount) -> i64 {
+    treasury.last_reset_at.saturating_add(EPOCH_DURATION)
+}
+
+/// Waits until the on-chain clock passes the end of the current epoch, so work can be
+/// scheduled right after a reset.
+pub async fn wait_for_next_epoch(client: &RpcClient) -> Result<(), AccountError> {
+    let treasury = get_treasury(client).await?;
+    let threshold = epoch_end(&treasury);
+    loop {
+        let clock = get_clock(client).await?;
+        if clock.unix_timestamp >= threshold {
+            info!("Epoch boundary crossed.");
+            return Ok(());
+        }
+        let remaining = threshold - clock.unix_timestamp;
+        info!("Waiting {} seconds for the next epoch...", remaining);
+        tokio::time::sleep(Duration::from_secs(remaining as u64 + 1)).await;
+    }
+}
+
+/// Number of leading zero bits in a difficulty target, i.e. how many zero bits a hash has
+/// to start with to be valid.
+pub fn difficulty_zero_bits(difficulty: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in difficulty {
+        if *byte != 0 {
+            return bits + byte.leading_zeros();
+        }
+        bits += 8;
+    }
+    bits
+}
+
+/// Hashes per second since `started_at`, from a running total shared by every thread.
+pub fn hash_rate(hashes: &AtomicU64, started_at: Instant) -> f64 {
+    hashes.load(Ordering::Relaxed) as f64 / started_at.elapsed().as_secs_f64().max(0.001)
+}
+
+/// Draws a spinner with the running hashrate from `hashes` until `done` is set.
+pub fn spawn_hash_progress(
+    hashes: Arc<AtomicU64>,
+    done: Arc<AtomicBool>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let spinner = ['|', '/', '-', '\\'];
+        let started_at = Instant::now();
+        let mut stdout = stdout();
+        let mut frame = 0;
+        while !done.load(Ordering::Relaxed) {
+            let total = hashes.load(Ordering::Relaxed);
+            let rate = hash_rate(&hashes, started_at);
+            stdout
+                .write_all(
+                    format!("\r{} {:.0} H/s ({} hashes)", spinner[frame % 4], rate, total)
+                        .as_bytes(),
+                )
+                .ok();
+            stdout.flush().ok();
+            frame += 1;
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    })
 }
 
 #[cached]
@@ -50,6 +552,574 @@ pub fn proof_pubkey(authority: Pubkey) -> Pubkey {
 }
 
 #[cached]
-pub fn treasury_tokens_pubkey() -> Pubkey {
-    get_associated_token_address(&TREASURY_ADDRESS, &MINT_ADDRESS)
+pub fn treasury_tokens_pubkey(token_program: TokenProgram) -> Pubkey {
+    token_program.ore_token_account(&TREASURY_ADDRESS)
+}
+
+const TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Token program that owns the ORE mint, and so every ORE token account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenProgram {
+    /// The classic SPL token program.
+    #[default]
+    Spl,
+    /// Token-2022 (token extensions).
+    Token2022,
+}
+
+impl TokenProgram {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            TokenProgram::Spl => spl_token::id(),
+            TokenProgram::Token2022 => TOKEN_2022_PROGRAM_ID,
+        }
+    }
+
+    /// Associated ORE token account of `owner` under this token program.
+    pub fn ore_token_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &MINT_ADDRESS, &self.id())
+    }
+}
+
+/// Checks that the ORE mint is owned by `token_program`, so derived token accounts exist.
+pub async fn check_token_program(
+    client: &RpcClient,
+    token_program: TokenProgram,
+) -> Result<(), String> {
+    let mint = client
+        .get_account(&MINT_ADDRESS)
+        .await
+        .map_err(|e| format!("Failed to get ORE mint account: {}", e))?;
+    if mint.owner != token_program.id() {
+        return Err(format!(
+            "The ORE mint is owned by {}, not the selected {:?} token program {}. Pass the matching --token-program.",
+            mint.owner,
+            token_program,
+            token_program.id()
+        ));
+    }
+    Ok(())
+}
+
+/// Re-estimates an `--auto-priority-fee` from recent prioritization fees, capped at
+/// `--max-priority-fee`, and returns it. A fixed priority fee is returned as is. On error
+/// the last estimate is kept.
+pub async fn refresh_priority_fee(client: &RpcClient, priority_fee: PriorityFee) -> PriorityFee {
+    let PriorityFee::Auto {
+        percentile,
+        max,
+        fee: last_fee,
+    } = priority_fee
+    else {
+        return priority_fee;
+    };
+    let fee = match get_priority_fee_percentile(client, percentile).await {
+        Ok(fee) => match max {
+            Some(max) if fee > max => {
+                info!("Capping priority fee at --max-priority-fee {}", max);
+                max
+            }
+            _ => fee,
+        },
+        Err(e) => {
+            warn!("{}, keeping priority fee of {} microlamports", e, last_fee);
+            last_fee
+        }
+    };
+    PriorityFee::Auto {
+        percentile,
+        max,
+        fee,
+    }
+}
+
+/// Picks the given percentile (0-100) of recent prioritization fees paid for transactions
+/// touching the ore program and busses, in microlamports per compute unit.
+pub async fn get_priority_fee_percentile(
+    client: &RpcClient,
+    percentile: u8,
+) -> Result<u64, String> {
+    let mut accounts = vec![ore::ID];
+    accounts.extend_from_slice(&BUS_ADDRESSES);
+    let mut fees: Vec<u64> = client
+        .get_recent_prioritization_fees(&accounts)
+        .await
+        .map_err(|e| format!("Failed to get recent prioritization fees: {}", e))?
+        .into_iter()
+        .map(|f| f.prioritization_fee)
+        .collect();
+    if fees.is_empty() {
+        return Err("No recent prioritization fees returned".to_string());
+    }
+    fees.sort_unstable();
+    let rank = (percentile.min(100) as usize * fees.len()).div_ceil(100);
+    let fee = fees[rank.saturating_sub(1)];
+    info!(
+        "Priority fee: {} microlamports (p{} of {} recent fees)",
+        fee,
+        percentile,
+        fees.len()
+    );
+    Ok(fee)
+}
+
+/// Formats a raw ORE amount with the given number of decimal places. Digits past the
+/// token's precision are padded with zeros, extra digits are truncated rather than rounded.
+pub fn format_ore(raw: u64, decimals: usize) -> String {
+    let token_decimals = TOKEN_DECIMALS as usize;
+    let base = 10u64.pow(token_decimals as u32);
+    let whole = raw / base;
+    if decimals == 0 {
+        return whole.to_string();
+    }
+    let mut frac = format!("{:0width$}", raw % base, width = token_decimals);
+    if decimals <= token_decimals {
+        frac.truncate(decimals);
+    } else {
+        frac.push_str(&"0".repeat(decimals - token_decimals));
+    }
+    format!("{}.{}", whole, frac)
+}
+
+/// Parses a claim amount with an explicit unit: `1.5ore` in whole ORE or `1500000000raw` in
+/// the token's smallest unit. Bare numbers are rejected since it's easy to be off by a factor
+/// of 10^decimals. Returns the raw amount, which must be positive.
+pub fn parse_ore_amount(s: &str) -> Result<u64, String> {
+    let s = s.trim().to_lowercase();
+    let raw = if let Some(raw) = s.strip_suffix("raw") {
+        raw.trim()
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid raw amount `{}`, expected a whole number", raw))?
+    } else if let Some(ore) = s.strip_suffix("ore") {
+        let token_decimals = TOKEN_DECIMALS as usize;
+        let ore = ore.trim();
+        let (whole, frac) = ore.split_once('.').unwrap_or((ore, ""));
+        if frac.len() > token_decimals {
+            return Err(format!(
+                "`{}` has more than {} decimal places",
+                ore, token_decimals
+            ));
+        }
+        let invalid = || format!("Invalid ORE amount `{}`", ore);
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse::<u64>().map_err(|_| invalid())?
+        };
+        let frac = if frac.is_empty() {
+            0
+        } else {
+            format!("{:0<width$}", frac, width = token_decimals)
+                .parse::<u64>()
+                .map_err(|_| invalid())?
+        };
+        whole
+            .checked_mul(10u64.pow(token_decimals as u32))
+            .and_then(|w| w.checked_add(frac))
+            .ok_or_else(|| format!("ORE amount `{}` is too large", ore))?
+    } else {
+        return Err(format!(
+            "Ambiguous amount `{}`, add a unit: e.g. `1.5ore` for ORE or `1500000000raw` for raw token units",
+            s
+        ));
+    };
+    if raw == 0 {
+        return Err("Amount must be greater than zero".to_string());
+    }
+    Ok(raw)
+}
+
+/// `--threads` for the mine commands: a fixed count, or `auto` for the parallelism the OS
+/// reports for this machine.
+#[derive(Clone, Copy, Debug)]
+pub enum Threads {
+    Auto,
+    Count(u64),
+}
+
+impl Threads {
+    /// The thread count to mine with, printing what `auto` resolved to.
+    pub fn resolve(self) -> u64 {
+        match self {
+            Threads::Count(count) => count,
+            Threads::Auto => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get() as u64);
+                info!("--threads auto: using {} threads", threads);
+                threads
+            }
+        }
+    }
+}
+
+pub fn parse_threads(s: &str) -> Result<Threads, String> {
+    if s == "auto" {
+        return Ok(Threads::Auto);
+    }
+    match s.parse() {
+        Ok(0) => Err("expected at least 1 thread".to_string()),
+        Ok(count) => Ok(Threads::Count(count)),
+        Err(_) => Err("expected a thread count or auto".to_string()),
+    }
+}
+
+/// How the priority fee is set: a fixed price per compute unit, or a total per transaction
+/// that gets spread over the transaction's compute unit limit.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityFee {
+    /// Microlamports per compute unit.
+    PerCu(u64),
+    /// Lamports per transaction.
+    Total(u64),
+    /// Microlamports per compute unit, re-estimated from recent prioritization fees before
+    /// each batch by `refresh_priority_fee`. `fee` is the latest estimate.
+    Auto {
+        percentile: u8,
+        max: Option<u64>,
+        fee: u64,
+    },
+}
+
+impl PriorityFee {
+    /// Compute unit price in microlamports for a transaction with the given compute unit
+    /// limit. A total is rounded down so the transaction never pays more than requested.
+    pub fn cu_price(&self, cu_limit: u32) -> u64 {
+        match self {
+            PriorityFee::PerCu(price) => *price,
+            PriorityFee::Total(lamports) => {
+                lamports.saturating_mul(1_000_000) / cu_limit.max(1) as u64
+            }
+            PriorityFee::Auto { fee, .. } => *fee,
+        }
+    }
+}
+
+/// Estimates the fee of a transaction: the base fee per signature plus the priority fee
+/// for the requested compute units.
+pub fn estimate_tx_fee(signatures: u64, cu_limit: u32, priority_fee: PriorityFee) -> u64 {
+    let cu_price = priority_fee.cu_price(cu_limit);
+    signatures * 5000 + (cu_limit as u64 * cu_price).div_ceil(1_000_000)
+}
+
+#[derive(Default)]
+pub struct LoadKeypairsOptions {
+    /// Fail the whole load instead of skipping `.json` files that aren't keypairs.
+    pub strict: bool,
+}
+
+/// Reads every `.json` keypair file in `dir`, ignoring other files such as a `.DS_Store` or
+/// README. Files that can't be read as a keypair are skipped with one warning for the lot
+/// (or fail the load when `strict`), duplicate keys are dropped, and the result is sorted
+/// by path so every command walks the wallets in the same order.
+pub async fn load_keypairs(
+    dir: &str,
+    opts: &LoadKeypairsOptions,
+) -> Result<Vec<(PathBuf, Keypair)>, String> {
+    let mut dir_reader = tokio::fs::read_dir(dir)
+        .await
+        .map_err(|e| format!("Failed to read wallets directory {}: {}", dir, e))?;
+    let mut paths = vec![];
+    loop {
+        match dir_reader.next_entry().await {
+            Ok(Some(entry)) => paths.push(entry.path()),
+            Ok(None) => break,
+            Err(e) => return Err(format!("Failed to read wallets directory {}: {}", dir, e)),
+        }
+    }
+    paths.sort();
+
+    let mut keypairs: Vec<(PathBuf, Keypair)> = vec![];
+    let mut unreadable = 0;
+    for path in paths {
+        if path.is_dir() || path.extension().map_or(true, |ext| ext != "json") {
+            continue;
+        }
+        match read_keypair_file(&path) {
+            Ok(keypair) => {
+                if keypairs.iter().any(|(_, k)| k.pubkey() == keypair.pubkey()) {
+                    warn!(
+                        "Skipping duplicate keypair {} in {}",
+                        keypair.pubkey(),
+                        path.display()
+                    );
+                    continue;
+                }
+                keypairs.push((path, keypair));
+            }
+            Err(e) => {
+                if opts.strict {
+                    return Err(format!(
+                        "Failed to read keypair file {}: {}",
+                        path.display(),
+                        e
+                    ));
+                }
+                unreadable += 1;
+            }
+        }
+    }
+    if unreadable > 0 {
+        warn!("Skipped {} .json files in {} that aren't keypairs", unreadable, dir);
+    }
+    Ok(keypairs)
+}
+
+/// Where a command's wallets come from: a directory of keypair files, `--miner-wallets`, or
+/// the first `--derivation-count` wallets of the seed phrase in `--mnemonic-file`.
+#[derive(Clone, Debug)]
+pub enum WalletSource {
+    Dir(String),
+    Mnemonic { file: String, count: u32 },
+}
+
+impl WalletSource {
+    /// The source picked by the args, clap makes sure at most one was given.
+    pub fn from_args(
+        miner_wallets: Option<String>,
+        mnemonic_file: Option<String>,
+        derivation_count: Option<u32>,
+    ) -> Option<Self> {
+        match (miner_wallets, mnemonic_file, derivation_count) {
+            (Some(dir), _, _) => Some(WalletSource::Dir(dir)),
+            (None, Some(file), Some(count)) => Some(WalletSource::Mnemonic { file, count }),
+            _ => None,
+        }
+    }
+
+    /// Loads the wallets, see `load_keypairs` for directories. Derived wallets use the
+    /// standard Solana path `m/44'/501'/i'/0'`, which also stands in for their file path.
+    pub async fn load(
+        &self,
+        opts: &LoadKeypairsOptions,
+    ) -> Result<Vec<(PathBuf, Keypair)>, String> {
+        let (file, count) = match self {
+            WalletSource::Dir(dir) => return load_keypairs(dir, opts).await,
+            WalletSource::Mnemonic { file, count } => (file, *count),
+        };
+        let phrase = tokio::fs::read_to_string(file)
+            .await
+            .map_err(|e| format!("Failed to read mnemonic file {}: {}", file, e))?;
+        let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+        // Checks every word is in the English BIP39 list and the checksum matches, a typo
+        // would otherwise derive a different set of valid but empty wallets
+        Mnemonic::validate(&phrase, Language::English)
+            .map_err(|e| format!("Invalid seed phrase in {}: {}", file, e))?;
+        let seed = generate_seed_from_seed_phrase_and_passphrase(&phrase, "");
+        let mut keypairs = vec![];
+        for i in 0..count {
+            let path = DerivationPath::new_bip44(Some(i), Some(0));
+            let keypair = keypair_from_seed_and_derivation_path(&seed, Some(path))
+                .map_err(|e| format!("Failed to derive wallet {}: {}", i, e))?;
+            keypairs.push((PathBuf::from(format!("m/44'/501'/{}'/0'", i)), keypair));
+        }
+        Ok(keypairs)
+    }
+}
+
+/// Inclusive nonce range searched by thread `i` of `threads`. The last thread runs up to
+/// `u64::MAX` so the whole nonce space is covered without overflowing. Zero threads are
+/// treated as one.
+pub fn nonce_range(i: u64, threads: u64) -> (u64, u64) {
+    let threads = threads.max(1);
+    let chunk = u64::MAX / threads;
+    let start = chunk.saturating_mul(i);
+    let end = if i + 1 >= threads {
+        u64::MAX
+    } else {
+        start + chunk - 1
+    };
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+    use ore::utils::Discriminator;
+    use serde_json::{json, Value};
+    use solana_client::rpc_request::RpcRequest;
+
+    use super::*;
+
+    // Account data as the program stores it, the discriminator padded to 8 bytes then the
+    // struct
+    fn account_data<T: Discriminator + Pod>(account: &T) -> Vec<u8> {
+        let mut data = vec![0; 8];
+        data[0] = T::discriminator() as u8;
+        data.extend_from_slice(bytemuck::bytes_of(account));
+        data
+    }
+
+    #[test]
+    fn proof_account_maps_fields() {
+        let mut proof = Proof::zeroed();
+        proof.authority = Pubkey::new_unique();
+        proof.claimable_rewards = 5;
+        proof.hash = KeccakHash::new_unique().into();
+        proof.total_hashes = 7;
+        proof.total_rewards = 11;
+        let parsed = ProofAccount::parse(&account_data(&proof)).unwrap();
+        assert_eq!(parsed.authority, proof.authority);
+        assert_eq!(parsed.claimable_rewards, 5);
+        assert_eq!(parsed.hash, KeccakHash::from(proof.hash));
+        assert_eq!(parsed.total_hashes, 7);
+        assert_eq!(parsed.total_rewards, 11);
+
+        proof.claimable_rewards = 12;
+        assert!(matches!(
+            ProofAccount::parse(&account_data(&proof)),
+            Err(AccountError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn treasury_account_maps_fields() {
+        let mut treasury = Treasury::zeroed();
+        treasury.admin = Pubkey::new_unique();
+        treasury.difficulty = KeccakHash::new_unique().into();
+        treasury.last_reset_at = 1_700_000_000;
+        treasury.total_claimed_rewards = 13;
+        // A zero reward rate only warns
+        let parsed = TreasuryAccount::parse(&account_data(&treasury)).unwrap();
+        assert_eq!(parsed.admin, treasury.admin);
+        assert_eq!(parsed.difficulty, KeccakHash::from(treasury.difficulty));
+        assert_eq!(parsed.last_reset_at, 1_700_000_000);
+        assert_eq!(parsed.reward_rate, 0);
+        assert_eq!(parsed.total_claimed_rewards, 13);
+
+        treasury.difficulty = KeccakHash::default().into();
+        assert!(matches!(
+            TreasuryAccount::parse(&account_data(&treasury)),
+            Err(AccountError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn bus_account_maps_fields() {
+        let mut bus = Bus::zeroed();
+        bus.id = 3;
+        bus.rewards = 17;
+        let parsed = BusAccount::parse(&account_data(&bus)).unwrap();
+        assert_eq!(parsed.id, 3);
+        assert_eq!(parsed.rewards, 17);
+
+        bus.id = BUS_COUNT as u64;
+        assert!(matches!(
+            BusAccount::parse(&account_data(&bus)),
+            Err(AccountError::Invalid { .. })
+        ));
+    }
+
+    fn mock_blockhash_client(response: Value) -> RpcClient {
+        let mocks = [(RpcRequest::GetLatestBlockhash, response)]
+            .into_iter()
+            .collect();
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+    }
+
+    fn blockhash_response(blockhash: Value) -> Value {
+        json!({
+            "context": { "slot": 1 },
+            "value": { "blockhash": blockhash, "lastValidBlockHeight": 1234 },
+        })
+    }
+
+    #[tokio::test]
+    async fn get_latest_blockhash_parses_response() {
+        let hash = Hash::new_unique();
+        let client = mock_blockhash_client(blockhash_response(json!(hash.to_string())));
+        assert_eq!(get_latest_blockhash(&client).await.unwrap(), (hash, 1234));
+    }
+
+    #[tokio::test]
+    async fn get_latest_blockhash_rejects_malformed_responses() {
+        let responses = [
+            json!({ "context": { "slot": 1 }, "value": null }),
+            blockhash_response(json!(42)),
+            blockhash_response(json!("not a blockhash")),
+            blockhash_response(json!(Hash::default().to_string())),
+        ];
+        for response in responses {
+            let client = mock_blockhash_client(response.clone());
+            match get_latest_blockhash(&client).await {
+                Err(BlockhashError::Malformed { .. }) => {}
+                res => panic!(
+                    "expected a malformed blockhash for {}, got {:?}",
+                    response, res
+                ),
+            }
+        }
+    }
+
+    fn mnemonic_source(name: &str, phrase: &str) -> WalletSource {
+        let dir = std::env::temp_dir().join(format!("ore-cli-utils-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        std::fs::write(&file, phrase).unwrap();
+        WalletSource::Mnemonic {
+            file: file.to_string_lossy().into_owned(),
+            count: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn mnemonic_wallets_derive_from_a_valid_phrase() {
+        let phrase = "abandon abandon abandon abandon abandon abandon\n\
+            abandon abandon abandon abandon abandon about\n";
+        let source = mnemonic_source("valid.txt", phrase);
+        let keypairs = source.load(&LoadKeypairsOptions::default()).await.unwrap();
+        assert_eq!(keypairs.len(), 2);
+        assert_ne!(keypairs[0].1.pubkey(), keypairs[1].1.pubkey());
+    }
+
+    #[tokio::test]
+    async fn mnemonic_wallets_reject_bad_words_and_checksums() {
+        let phrases = [
+            ("checksum.txt", "abandon ".repeat(12)),
+            ("word.txt", format!("{}abandonn", "abandon ".repeat(11))),
+        ];
+        for (name, phrase) in phrases {
+            let source = mnemonic_source(name, &phrase);
+            let e = source
+                .load(&LoadKeypairsOptions::default())
+                .await
+                .unwrap_err();
+            assert!(e.starts_with("Invalid seed phrase"), "{}", e);
+        }
+    }
+
+    #[test]
+    fn parse_threads_rejects_zero() {
+        assert!(matches!(parse_threads("auto"), Ok(Threads::Auto)));
+        assert!(matches!(parse_threads("4"), Ok(Threads::Count(4))));
+        assert!(parse_threads("0").is_err());
+        assert!(parse_threads("four").is_err());
+    }
+
+    #[test]
+    fn nonce_range_last_partition_reaches_max() {
+        for threads in [1, 3, 8, 13] {
+            assert_eq!(nonce_range(threads - 1, threads).1, u64::MAX);
+        }
+    }
+
+    #[test]
+    fn nonce_ranges_are_contiguous() {
+        for threads in [2, 3, 8, 13] {
+            assert_eq!(nonce_range(0, threads).0, 0);
+            for i in 1..threads {
+                let (_, previous_end) = nonce_range(i - 1, threads);
+                let (start, end) = nonce_range(i, threads);
+                assert_eq!(start, previous_end + 1);
+                assert!(start <= end);
+            }
+        }
+    }
+
+    #[test]
+    fn nonce_range_zero_threads_covers_everything() {
+        assert_eq!(nonce_range(0, 0), (0, u64::MAX));
+    }
 }
@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_program::{native_token::lamports_to_sol, pubkey::Pubkey};
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signature};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{
+    hash::parse_hex_hash,
+    summary::Summary,
+    utils::{format_ore, get_treasury},
+    Miner,
+};
+
+impl Miner {
+    /// Audits a landed transaction: every mine instruction in it is checked against the
+    /// difficulty, and fully recomputed with `validate_hash` when the proof hash it was mined
+    /// against is known. The program re-seeds the proof hash after every mine, so the
+    /// current proof state can't stand in for it.
+    pub async fn verify_tx(
+        &self,
+        signature: String,
+        proof_hash: Option<String>,
+        difficulty: Option<String>,
+        summary: &mut Summary,
+    ) {
+        let signature = match Signature::from_str(&signature) {
+            Ok(signature) => signature,
+            Err(e) => {
+                println!("Invalid signature {}: {}", signature, e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let inputs = (
+            proof_hash.as_deref().map(parse_hex_hash).transpose(),
+            difficulty.as_deref().map(parse_hex_hash).transpose(),
+        );
+        let (proof_hash, difficulty) = match inputs {
+            (Ok(proof_hash), Ok(difficulty)) => (proof_hash, difficulty),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let treasury = match get_treasury(&self.rpc_client).await {
+            Ok(treasury) => treasury,
+            Err(e) => {
+                println!("{}", e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let difficulty = difficulty.unwrap_or(treasury.difficulty);
+
+        let tx = match self
+            .rpc_client
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(self.rpc_client.commitment()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await
+        {
+            Ok(tx) => tx,
+            Err(e) => {
+                println!("Failed to get transaction {}: {}", signature, e);
+                summary.failed += 1;
+                return;
+            }
+        };
+        let Some(versioned_tx) = tx.transaction.transaction.decode() else {
+            println!("Failed to decode transaction {}", signature);
+            summary.failed += 1;
+            return;
+        };
+
+        println!("Slot: {}", tx.slot);
+        if let Some(block_time) = tx.block_time {
+            println!("Block time: {}", block_time);
+        }
+        let succeeded = match tx.transaction.meta.as_ref() {
+            Some(meta) => {
+                println!("Fee: {} SOL", lamports_to_sol(meta.fee));
+                match &meta.err {
+                    None => println!("Status: success"),
+                    Some(err) => println!("Status: failed ({:?})", err),
+                }
+                meta.err.is_none()
+            }
+            None => {
+                println!("Status: unknown, the rpc returned no transaction meta");
+                false
+            }
+        };
+
+        // Mine instructions are recognized by comparing against one built locally
+        let template = ore::instruction::mine(
+            Pubkey::default(),
+            Pubkey::default(),
+            KeccakHash::default().into(),
+            0,
+        );
+        let keys = versioned_tx.message.static_account_keys();
+        let mut mines = 0;
+        let mut invalid = 0;
+        for ix in versioned_tx.message.instructions() {
+            let program_id = keys.get(ix.program_id_index as usize);
+            if program_id != Some(&template.program_id)
+                || ix.data.len() != template.data.len()
+                || ix.data[0] != template.data[0]
+            {
+                continue;
+            }
+            mines += 1;
+            let Some(signer) = ix.accounts.first().and_then(|i| keys.get(*i as usize)) else {
+                println!("\nMine instruction signer is not in the transaction's account keys");
+                invalid += 1;
+                continue;
+            };
+            let hash = KeccakHash::new_from_array(ix.data[1..33].try_into().unwrap());
+            let nonce = u64::from_le_bytes(ix.data[33..41].try_into().unwrap());
+            println!("\nMine by {}", signer);
+            println!("Nonce: {}", nonce);
+            println!("Hash: {}", hash);
+            let valid = match proof_hash {
+                Some(proof_hash) => {
+                    self.validate_hash(hash, proof_hash, *signer, nonce, difficulty)
+                }
+                None => {
+                    println!("No --proof-hash given, only checking the difficulty.");
+                    hash.le(&difficulty)
+                }
+            };
+            if valid {
+                println!("Hash is valid.");
+            } else {
+                println!("Hash failed validation.");
+                invalid += 1;
+            }
+        }
+
+        if mines == 0 {
+            println!("\nTransaction has no ORE mine instructions.");
+            summary.skipped += 1;
+            return;
+        }
+        if succeeded {
+            println!(
+                "\nEarned about {} ORE for {} mines at the current reward rate.",
+                format_ore(treasury.reward_rate * mines, self.decimals),
+                mines
+            );
+        }
+        if invalid == 0 && succeeded {
+            summary.success += 1;
+        } else {
+            summary.failed += 1;
+        }
+    }
+}
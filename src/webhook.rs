@@ -0,0 +1,136 @@
+use std::{sync::OnceLock, time::Duration};
+
+use clap::ValueEnum;
+use log::warn;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::{sync::mpsc, time::sleep};
+
+use crate::{summary::Summary, utils::format_ore};
+
+const MAX_ATTEMPTS: u32 = 5;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+static WEBHOOK: OnceLock<Webhook> = OnceLock::new();
+
+/// Events `--webhook` can be sent, picked with `--webhook-events`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// The first transaction of the run landed.
+    FirstSuccess,
+    /// `--webhook-failures` transactions failed in a row.
+    Failures,
+    /// A fee payer's balance dropped too low to keep paying for transactions.
+    LowBalance,
+    /// The command finished, with its final summary.
+    Summary,
+}
+
+#[derive(Serialize)]
+struct Payload {
+    event: WebhookEvent,
+    timestamp: String,
+    command: &'static str,
+    data: Value,
+}
+
+/// Posts events to the `--webhook` url from a background task, retrying with backoff, so a
+/// slow or flaky endpoint never holds up mining.
+pub struct Webhook {
+    url: String,
+    events: Vec<WebhookEvent>,
+    command: &'static str,
+    payloads: mpsc::UnboundedSender<Payload>,
+}
+
+impl Webhook {
+    /// Starts the shared webhook. Later calls are ignored.
+    pub fn start(url: String, events: Vec<WebhookEvent>, command: &'static str) {
+        let (payloads, mut payload_reader) = mpsc::unbounded_channel();
+        let webhook = Webhook {
+            url: url.clone(),
+            events,
+            command,
+            payloads,
+        };
+        if WEBHOOK.set(webhook).is_err() {
+            return;
+        }
+        tokio::spawn(async move {
+            while let Some(payload) = payload_reader.recv().await {
+                post(&url, &payload).await;
+            }
+        });
+    }
+
+    /// Queues `event` if a webhook is configured and subscribed to it.
+    pub fn notify(event: WebhookEvent, data: Value) {
+        let Some(webhook) = WEBHOOK.get() else {
+            return;
+        };
+        if !webhook.events.contains(&event) {
+            return;
+        }
+        let _ = webhook.payloads.send(Payload {
+            event,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            command: webhook.command,
+            data,
+        });
+    }
+
+    /// Sends the final summary, waiting for it since the process is about to exit.
+    pub async fn finish(summary: &Summary) {
+        let Some(webhook) = WEBHOOK.get() else {
+            return;
+        };
+        if !webhook.events.contains(&WebhookEvent::Summary) {
+            return;
+        }
+        let payload = Payload {
+            event: WebhookEvent::Summary,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            command: webhook.command,
+            data: json!({
+                "success": summary.success,
+                "failed": summary.failed,
+                "skipped": summary.skipped,
+                "ore_mined": format_ore(summary.ore_mined, ore::TOKEN_DECIMALS as usize),
+                "ore_claimed": format_ore(summary.ore_claimed, ore::TOKEN_DECIMALS as usize),
+                "lamports_spent": summary.lamports_spent,
+                "elapsed_secs": summary.elapsed_secs(),
+            }),
+        };
+        post(&webhook.url, &payload).await;
+    }
+}
+
+async fn post(url: &str, payload: &Payload) {
+    let client = reqwest::Client::new();
+    let body = serde_json::to_string(payload).unwrap();
+    let mut delay = FIRST_RETRY_DELAY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let res = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .body(body.clone())
+            .send()
+            .await;
+        let error = match res {
+            Ok(res) if res.status().is_success() => return,
+            Ok(res) => format!("endpoint returned {}", res.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt == MAX_ATTEMPTS {
+            warn!(
+                "Failed to send {:?} webhook after {} attempts: {}",
+                payload.event, MAX_ATTEMPTS, error
+            );
+            return;
+        }
+        sleep(delay).await;
+        delay *= 2;
+    }
+}